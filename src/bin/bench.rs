@@ -0,0 +1,157 @@
+//! Frame time of a crowded base, to see how the game scales with the number of entities.
+//!
+//! Run e.g.
+//! `cargo run --release --bin bench -- --zombies 100,200,400,800 --blocks 300`.
+//!
+//! For every zombie count a fresh game is started with the given number of blocks built
+//! around the player and the zombies spread around the base. The player is invincible
+//! and keeps shooting, so bullets, collisions and attacks are all part of the measurement.
+
+use std::{collections::HashSet, process, str::FromStr, time::Instant};
+
+use bevy::ecs::system::SystemState;
+use my_game::{headless::{headless_app, HeadlessPlugin, InputScript}, prelude::*};
+
+const DEFAULT_ZOMBIES: [usize; 4] = [100, 200, 400, 800];
+const DEFAULT_BLOCKS: usize = 300;
+const DEFAULT_FRAMES: u32 = 300;
+const WARMUP_FRAMES: u32 = 30;
+
+// Zombies are spread between these distances from the player
+const ZOMBIE_MIN_DIST: f32 = 60.0;
+const ZOMBIE_MAX_DIST: f32 = 600.0;
+// Blocks are built on the grid between these distances from the player
+const BLOCK_MIN_DIST: f32 = 40.0;
+const BLOCK_MAX_DIST: f32 = 400.0;
+const GRID_SIZE: f32 = 20.0;
+
+const SCRIPT: &str = "
+0.0 mouse 300 0
+0.0 click Left
+";
+
+struct BenchArgs
+{
+    zombies: Vec<usize>,
+    blocks: usize,
+    frames: u32,
+    seed: u64
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", arg, value))
+}
+
+fn parse_args() -> Result<BenchArgs, String> {
+    let mut bench_args = BenchArgs {
+        zombies: DEFAULT_ZOMBIES.to_vec(),
+        blocks: DEFAULT_BLOCKS,
+        frames: DEFAULT_FRAMES,
+        seed: 0
+    };
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+
+        match arg.as_str() {
+            "--zombies" => bench_args.zombies = value.split(',')
+                .map(|count| parse_value(&arg, count))
+                .collect::<Result<_, _>>()?,
+            "--blocks" => bench_args.blocks = parse_value(&arg, &value)?,
+            "--frames" => bench_args.frames = parse_value(&arg, &value)?,
+            "--seed" => bench_args.seed = parse_value(&arg, &value)?,
+            _ => return Err(format!("Unknown argument {}", arg))
+        }
+    }
+
+    Ok(bench_args)
+}
+
+fn build_app(seed: u64) -> App {
+    let mut app = headless_app(HeadlessPlugin::default(), seed);
+    app.insert_resource(InputScript::parse(SCRIPT).unwrap());
+
+    app
+}
+
+type PopulateParams<'w, 's> = (Commands<'w, 's>, Res<'w, GameAssets>, Res<'w, ItemRegistry>, Res<'w, ZombieArchetypes>, ResMut<'w, GameRng>, Res<'w, Balance>);
+
+fn populate(world: &mut World, zombies: usize, blocks: usize) {
+    let mut state: SystemState<PopulateParams> = SystemState::new(world);
+    let (mut commands, game_assets, registry, archetypes, mut game_rng, balance) = state.get_mut(world);
+
+    let mut taken = HashSet::new();
+
+    while taken.len() < blocks {
+        let dist = game_rng.gameplay.gen_range(BLOCK_MIN_DIST..BLOCK_MAX_DIST);
+        let angle = game_rng.gameplay.gen_range(0.0..std::f32::consts::TAU);
+        let cell = ((angle.cos() * dist / GRID_SIZE).round() as i32, (angle.sin() * dist / GRID_SIZE).round() as i32);
+
+        if !taken.insert(cell) {
+            continue;
+        }
+
+        // Mostly walls, with some fences in between to slow the zombies down
+        let block_type = if taken.len() % 4 == 0 { ItemTypes::Fence } else { ItemTypes::WallBlock };
+        let trans = Transform::from_xyz(cell.0 as f32 * GRID_SIZE, cell.1 as f32 * GRID_SIZE, 1.0);
+
+        construct::spawn_block(&mut commands, &game_assets, &registry, &balance, block_type, &trans);
+    }
+
+    for _ in 0..zombies {
+        let dist = game_rng.gameplay.gen_range(ZOMBIE_MIN_DIST..ZOMBIE_MAX_DIST);
+        let angle = game_rng.gameplay.gen_range(0.0..std::f32::consts::TAU);
+        let pos = Vec3::new(angle.cos() * dist, angle.sin() * dist, 2.0);
+
+        zombie::spawn_zombie_kind(&mut commands, &archetypes, "normal", pos, &game_assets, ZombieStats::default(), &mut game_rng.waves);
+    }
+
+    state.apply(world);
+}
+
+fn keep_player_alive(world: &mut World) {
+    for mut health in world.query_filtered::<&mut EntityHealth, With<Player>>().iter_mut(world) {
+        health.val = 100.0;
+    }
+}
+
+fn run(bench_args: &BenchArgs, zombies: usize) -> f64 {
+    let mut app = build_app(bench_args.seed);
+
+    while *app.world.resource::<State<AppState>>().current() != AppState::InGame {
+        app.update();
+    }
+
+    populate(&mut app.world, zombies, bench_args.blocks);
+
+    for _ in 0..WARMUP_FRAMES {
+        keep_player_alive(&mut app.world);
+        app.update();
+    }
+
+    let start = Instant::now();
+
+    for _ in 0..bench_args.frames {
+        keep_player_alive(&mut app.world);
+        app.update();
+    }
+
+    start.elapsed().as_secs_f64() * 1000.0 / bench_args.frames as f64
+}
+
+fn main() {
+    let bench_args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    println!("{:>8} {:>8} {:>10}", "zombies", "blocks", "ms/frame");
+
+    for &zombies in bench_args.zombies.iter() {
+        let frame_time = run(&bench_args, zombies);
+
+        println!("{:>8} {:>8} {:>10.3}", zombies, bench_args.blocks, frame_time);
+    }
+}
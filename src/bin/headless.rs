@@ -0,0 +1,138 @@
+//! Headless simulation of a survival run, used for automated testing and balancing.
+//!
+//! Run e.g.
+//! `cargo run --bin headless -- --minutes 5 --seed 42 --script run.txt --expect-survival`.
+//!
+//! The script format is described in `my_game::headless`.
+//!
+//! The process exits with status 1 if any of the `--expect-*`, `--max-*` or `--min-*`
+//! assertions fail.
+//!
+//! The map and the zombie spawns are fixed by the seed, but bevy does not fix the order
+//! of unrelated systems between runs, so fights can end slightly differently.
+
+use std::{fs, process, str::FromStr};
+
+use my_game::{headless::{headless_app, HeadlessPlugin, InputScript, SimulationStats}, prelude::*};
+
+const DEFAULT_MINUTES: f32 = 5.0;
+const DEFAULT_TIMESTEP: f32 = 1.0/60.0;
+
+struct RunArgs
+{
+    minutes: f32,
+    timestep: f32,
+    seed: u64,
+    script: Option<String>,
+    expect_survival: bool,
+    max_zombies: Option<usize>,
+    min_mined: Option<u32>
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", arg, value))
+}
+
+fn parse_args() -> Result<RunArgs, String> {
+    let mut run_args = RunArgs {
+        minutes: DEFAULT_MINUTES,
+        timestep: DEFAULT_TIMESTEP,
+        seed: 0,
+        script: None,
+        expect_survival: false,
+        max_zombies: None,
+        min_mined: None
+    };
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--expect-survival" {
+            run_args.expect_survival = true;
+            continue;
+        }
+
+        let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+
+        match arg.as_str() {
+            "--minutes" => run_args.minutes = parse_value(&arg, &value)?,
+            "--timestep" => run_args.timestep = parse_value(&arg, &value)?,
+            "--seed" => run_args.seed = parse_value(&arg, &value)?,
+            "--max-zombies" => run_args.max_zombies = Some(parse_value(&arg, &value)?),
+            "--min-mined" => run_args.min_mined = Some(parse_value(&arg, &value)?),
+            "--script" => run_args.script = Some(value),
+            _ => return Err(format!("Unknown argument {}", arg))
+        }
+    }
+
+    Ok(run_args)
+}
+
+fn main() {
+    let run_args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    let script = match &run_args.script {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path, e))
+            .and_then(|contents| InputScript::parse(&contents)),
+        None => Ok(InputScript::default())
+    }.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    let mut app = headless_app(HeadlessPlugin { timestep: run_args.timestep }, run_args.seed);
+    app.insert_resource(script);
+
+    let steps = (run_args.minutes * 60.0 / run_args.timestep).ceil() as u64;
+    let mut survived = true;
+    let mut steps_run = 0;
+
+    while steps_run < steps {
+        app.update();
+        steps_run += 1;
+
+        if *app.world.resource::<State<AppState>>().current() == AppState::GameDestruct {
+            survived = false;
+            break;
+        }
+    }
+
+    let zombies = app.world.query::<&Zombie>().iter(&app.world).count();
+    let mined = app.world.resource::<SimulationStats>().mined;
+    let wave = app.world.resource::<CurrentWave>();
+
+    println!("Simulated {:.1}s in {} steps with seed {}", steps_run as f32 * run_args.timestep, steps_run, run_args.seed);
+    println!("Player survived: {}", survived);
+    println!("Zombies alive: {}", zombies);
+    println!("Resources mined: {}", mined);
+    println!("Wave: {} ({:?} phase)", wave.number, wave.phase);
+
+    let mut failed = false;
+
+    if run_args.expect_survival && !survived {
+        eprintln!("FAILED: the player died");
+        failed = true;
+    }
+
+    if let Some(max_zombies) = run_args.max_zombies {
+        if zombies > max_zombies {
+            eprintln!("FAILED: {} zombies alive, expected at most {}", zombies, max_zombies);
+            failed = true;
+        }
+    }
+
+    if let Some(min_mined) = run_args.min_mined {
+        if mined < min_mined {
+            eprintln!("FAILED: {} resources mined, expected at least {}", mined, min_mined);
+            failed = true;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{prelude::*, utils::entity_destruct};
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
 
 pub struct ChestPlugin;
 
@@ -12,35 +12,49 @@ impl Plugin for ChestPlugin
 
 pub fn chest_init_test(
     mut commands: Commands,
-    game_assets: Res<GameAssets>
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>
 ) {
+    let mut inv_items = InventoryItems::new(inventory::INVENTORY_SLOTS);
+    inv_items.slots[0] = Some(Item{item_type:ItemTypes::Coal, quantity: 20});
+
     spawn_chest(&mut commands,
         &game_assets,
+        &registry,
         &Transform::from_xyz(0.0, 100.0, 2.5),
-        InventoryItems { items: vec![Item{item_type:ItemTypes::Coal, quantity: 20}] });
+        inv_items);
 }
 
 pub fn spawn_chest(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     parent_trans: &Transform,
     inv_items: InventoryItems
-) {
-    (*commands)
+) -> Entity {
+    let def = registry.get(ItemTypes::Chest);
+
+    let mut chest = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: ItemTypes::Chest.sprite_index(),
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    chest
         .insert_bundle(TransformBundle{
-            local: parent_trans.clone(),
+            local: *parent_trans,
             ..Default::default()
         })
         .insert(inv_items)
         .insert(InteractableEntity {interact_type: InteractionType::ChestOpen})
-        .insert(EntityHealth{val: 500.0, func_destruct: entity_destruct});
+        .insert(Chest);
+
+    insert_block_stats(&mut chest, def, entity_destruct);
+
+    chest.id()
 }
\ No newline at end of file
@@ -1,70 +1,116 @@
 use crate::prelude::*;
 
-const MAX_CONSTRUCT_DIST: f32 = 50.0;
-
 pub struct ConstructionPlugin;
 
 impl Plugin for ConstructionPlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_update(AppState::InGame)
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
             .with_system(build));
     }
 }
 
+/// Places blocks at the cursor of each player. Only the host places blocks, for its clients too.
+#[allow(clippy::too_many_arguments)]
 fn build(
     mut commands: Commands,
     game_assets: Res<GameAssets>,
-    query: Query<&Transform, With<Player>>,
-    block: Res<BlockSelection>,
-    mut player_inv_q: Query<&mut InventoryItems, With<Player>>,
-    btn: Res<Input<MouseButton>>,
-    mouseloc: Res<MouseLoc>,
+    registry: Res<ItemRegistry>,
+    mut player_q: Query<(&Transform, &PlayerInput, &mut InventoryItems, &Hotbar), With<Player>>,
+    deposit_query: Query<(&Transform, &Deposit)>,
+    rig_query: Query<&Transform, With<MiningRig>>,
+    network: Res<Network>,
+    balance: Res<Balance>
 ) {
+    if network.is_client() {
+        return;
+    }
 
-    let player_vec = query.single().translation;
-    let mouse_vec: Vec3 = Vec3::new(mouseloc.x, mouseloc.y, 0.0);
+    for (player_trans, input, mut player_inv, hotbar) in player_q.iter_mut() {
+        let player_vec = player_trans.translation;
+        let mouse_vec = input.cursor;
 
-    if btn.just_pressed(MouseButton::Right) && player_vec.distance(mouse_vec) < MAX_CONSTRUCT_DIST {
-        let mut player_inv = player_inv_q.single_mut();
+        if !input.actions.just_pressed(Action::Build) || player_vec.distance(mouse_vec) >= balance.player.build_distance {
+            continue;
+        }
 
-        if player_inv.has_item(block.block.clone()){
-            let x_remain = mouseloc.x%20.0;
-            let y_remain = mouseloc.y%20.0;
+        // Place whatever the active hotbar slot holds, weapons and materials are skipped
+        let (slot, block) = match hotbar.selected(&player_inv) {
+            Some((slot, item)) if registry.get(item.item_type).placeable => (slot, item.item_type),
+            _ => continue
+        };
 
-            let mut x_diff = -x_remain;
-            let mut y_diff = -y_remain;
+        let x_remain = mouse_vec.x%20.0;
+        let y_remain = mouse_vec.y%20.0;
 
-            if x_remain > 10.0 {
-                x_diff = 20.0 - x_remain;
-            }
+        let mut x_diff = -x_remain;
+        let mut y_diff = -y_remain;
+
+        if x_remain > 10.0 {
+            x_diff = 20.0 - x_remain;
+        }
+
+        if y_remain > 10.0 {
+            y_diff = 20.0 - y_remain;
+        }
+
+        let x_pos = mouse_vec.x + x_diff;
+        let y_pos = mouse_vec.y + y_diff;
+
+        let spawn_pos = Vec3::new(x_pos, y_pos, 3.0);
+        let spawn_trans = Transform::from_translation(spawn_pos);
 
-            if y_remain > 10.0 {
-                y_diff = 20.0 - y_remain;
+        if block == ItemTypes::MiningRig {
+            if miningrig::deposit_at(deposit_query.iter(), spawn_pos).is_none() {
+                println!("A mining rig has to be placed on an ore deposit!");
+                continue;
             }
 
-            let x_pos = mouseloc.x + x_diff;
-            let y_pos = mouseloc.y + y_diff;
-
-            let spawn_pos = Vec3::new(x_pos, y_pos, 3.0);
-            let spawn_trans = Transform::from_translation(spawn_pos);
-
-            match block.block {
-                ItemTypes::WallBlock => wall::spawn_wall(&mut commands, spawn_pos, &game_assets),
-                ItemTypes::TurretBlock => turret::spawn_turret(&mut commands, spawn_pos, &game_assets),
-                ItemTypes::TripMine => tripmine::spawn_tripmine(&mut commands, &game_assets, &spawn_trans),
-                ItemTypes::Fence => fence::spawn_fence(&mut commands, &game_assets, &spawn_trans),
-                ItemTypes::Wheat => wheat::spawn_wheat(&mut commands, &game_assets, &spawn_trans),
-                ItemTypes::WindMill => windmill::spawn_windmill(&mut commands, &game_assets, &spawn_trans),
-                ItemTypes::WoodFence => woodfence::spawn_woodfence(&mut commands, &game_assets, &spawn_trans),
-                ItemTypes::MiningRig => miningrig::spawn_miningrig(&mut commands, spawn_pos, &game_assets),
-                ItemTypes::CraftingTable => craftingtable::spawn_craftingtable(&mut commands, &game_assets, &spawn_trans),
-                ItemTypes::Chest => chest::spawn_chest(&mut commands, &game_assets, &spawn_trans, InventoryItems { ..Default::default() }),
-                _ => println!("Could not match selection type!")
+            // Rigs on the same deposit would share its ore
+            let cell = navigation::world_to_cell(spawn_pos);
+
+            if rig_query.iter().any(|trans| navigation::world_to_cell(trans.translation) == cell) {
+                println!("This deposit already has a mining rig!");
+                continue;
             }
+        }
+
+        if spawn_block(&mut commands, &game_assets, &registry, &balance, block, &spawn_trans).is_some() {
+            player_inv.take_from_slot(slot, &Item { item_type: block, quantity: 1 });
+        }
+    }
+}
+
+pub fn spawn_block(
+    commands: &mut Commands,
+    game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
+    balance: &Balance,
+    block_type: ItemTypes,
+    spawn_trans: &Transform
+) -> Option<Entity> {
+    let spawn_pos = spawn_trans.translation;
+
+    if !registry.get(block_type).placeable {
+        println!("{} can not be placed!", registry.get(block_type).name);
+        return None;
+    }
 
-            player_inv.tick_or_remove(block.block.clone());
+    match block_type {
+        ItemTypes::WallBlock => Some(wall::spawn_wall(commands, spawn_pos, game_assets, registry)),
+        ItemTypes::TurretBlock => Some(turret::spawn_turret(commands, spawn_pos, game_assets, registry, balance)),
+        ItemTypes::TripMine => Some(tripmine::spawn_tripmine(commands, game_assets, registry, spawn_trans)),
+        ItemTypes::Fence => Some(fence::spawn_fence(commands, game_assets, registry, spawn_trans)),
+        ItemTypes::Seeds => Some(wheat::spawn_wheat(commands, game_assets, registry, spawn_trans)),
+        ItemTypes::WindMill => Some(windmill::spawn_windmill(commands, game_assets, registry, spawn_trans)),
+        ItemTypes::WoodFence => Some(woodfence::spawn_woodfence(commands, game_assets, registry, spawn_trans)),
+        ItemTypes::MiningRig => Some(miningrig::spawn_miningrig(commands, spawn_pos, game_assets, registry)),
+        ItemTypes::CraftingTable => Some(craftingtable::spawn_craftingtable(commands, game_assets, registry, spawn_trans)),
+        ItemTypes::Chest => Some(chest::spawn_chest(commands, game_assets, registry, spawn_trans, InventoryItems::new(inventory::INVENTORY_SLOTS))),
+        _ => {
+            eprintln!("Could not match selection type!");
+            None
         }
     }
 }
\ No newline at end of file
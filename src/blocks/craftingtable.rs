@@ -1,4 +1,26 @@
-use crate::{prelude::*, utils::entity_destruct};
+//! Crafting on a `CraftingTable`.
+//!
+//! Press E next to a crafting table to open the crafting menu. Right click the
+//! output of a recipe to craft it, and press E again to close the menu. The
+//! recipes are loaded from `assets/recipes.ron`:
+//!
+//! ```text
+//! (
+//!     inputs: [(item_type: IronIngot, quantity: 2), (item_type: Coal, quantity: 1)],
+//!     output: (item_type: TurretBlock, quantity: 1),
+//! ),
+//! ```
+
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::{prelude::*, utils::{entity_destruct, destruct_cleanup}, systems::registry::insert_block_stats};
+
+pub const RECIPES_PATH: &str = "assets/recipes.ron";
+
+const ROW_HEIGHT: f32 = 24.0;
+const CLICK_DIST: f32 = 10.0;
 
 pub struct CraftingTablePlugin;
 
@@ -6,32 +28,256 @@ impl Plugin for CraftingTablePlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_update(AppState::InGame));
+        let recipes = read_recipes(RECIPES_PATH, app.world.resource::<ItemRegistry>())
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        app.insert_resource(recipes)
+        .add_system_set(SystemSet::on_enter(AppState::Crafting)
+            .with_system(crafting_menu_init))
+        .add_system_set(SystemSet::on_update(AppState::Crafting)
+            .with_system(craft)
+            .with_system(craftable_highlight)
+            .with_system(crafting_handler))
+        .add_system_set(SystemSet::on_exit(AppState::Crafting)
+            .with_system(destruct_cleanup::<CraftingMenu>));
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Recipe
+{
+    pub inputs: Vec<Item>,
+    pub output: Item
+}
+
+impl Recipe
+{
+    /// The inventory after crafting, or `None` if an input is missing or the output
+    /// does not fit. Inputs are taken first, so they can make room for the output.
+    fn crafted(&self, inv_items: &InventoryItems, registry: &ItemRegistry) -> Option<InventoryItems> {
+        let mut crafted = inv_items.clone();
+
+        for input in self.inputs.iter() {
+            crafted.remove_item(input).ok()?;
+        }
+
+        crafted.add_item(self.output.clone(), registry.max_stack(self.output.item_type)).ok()?;
+
+        Some(crafted)
+    }
+
+    pub fn can_craft(&self, inv_items: &InventoryItems, registry: &ItemRegistry) -> bool {
+        self.crafted(inv_items, registry).is_some()
+    }
+
+    pub fn craft(&self, inv_items: &mut InventoryItems, registry: &ItemRegistry) -> bool {
+        match self.crafted(inv_items, registry) {
+            Some(crafted) => {
+                *inv_items = crafted;
+                true
+            },
+            None => false
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Recipes
+{
+    pub recipes: Vec<Recipe>
+}
+
+pub fn read_recipes(path: &str, registry: &ItemRegistry) -> Result<Recipes, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let recipes: Recipes = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    let mut errors: Vec<String> = Vec::new();
+
+    for (i, recipe) in recipes.recipes.iter().enumerate() {
+        let mut error = |msg: &str| errors.push(format!("Recipe {}: {}", i + 1, msg));
+
+        if recipe.inputs.is_empty() {
+            error("needs at least one input");
+        }
+
+        for item in recipe.inputs.iter().chain([&recipe.output]) {
+            if item.quantity == 0 || item.quantity > registry.max_stack(item.item_type) {
+                error(&format!("quantity of {:?} has to be between 1 and its max_stack", item.item_type));
+            }
+        }
+
+        for (j, input) in recipe.inputs.iter().enumerate() {
+            if recipe.inputs[..j].iter().any(|other| other.item_type == input.item_type) {
+                error(&format!("{:?} is used more than once", input.item_type));
+            }
+
+            if input.item_type == recipe.output.item_type {
+                error("the output can not also be an input");
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(recipes)
+    } else {
+        Err(format!("Invalid recipes in {}:\n{}", path, errors.join("\n")))
     }
 }
 
 pub fn spawn_craftingtable(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     parent_trans: &Transform
-) {
-    (*commands)
+) -> Entity {
+    let def = registry.get(ItemTypes::CraftingTable);
+
+    let mut table = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: ItemTypes::CraftingTable.sprite_index(),
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    table
         .insert_bundle(TransformBundle{
-            local: parent_trans.clone(),
+            local: *parent_trans,
+            ..Default::default()
+        })
+        .insert(InteractableEntity {interact_type: InteractionType::CraftingOpen});
+
+    insert_block_stats(&mut table, def, entity_destruct);
+
+    table.id()
+}
+
+fn crafting_menu_init(
+    mut commands: Commands,
+    recipes: Res<Recipes>,
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    inv_asset: Res<InventoryAsset>
+) {
+    let height = ROW_HEIGHT * recipes.recipes.len() as f32;
+
+    let text_style = TextStyle {
+        font: inv_asset.font.clone(),
+        font_size: 10.0,
+        color: Color::WHITE
+    };
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.1, 0.1, 0.1, 0.9),
+                custom_size: Some(Vec2::new(200.0, height + 10.0)),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 30.0),
             ..Default::default()
         })
-        .insert(Attackable(TargetPriority::Low))
-        .insert(EntityHealth {
-            val: 50.0,
-            func_destruct: entity_destruct 
+        .insert(CraftingMenu)
+        .add_children(|parent| {
+            // Start from the top, one recipe per row
+            let offy = height / 2.0 - ROW_HEIGHT / 2.0;
+
+            for (i, recipe) in recipes.recipes.iter().enumerate() {
+                let y = offy - (i as f32) * ROW_HEIGHT;
+
+                let items = recipe.inputs.iter()
+                    .enumerate()
+                    .map(|(j, input)| (input, -80.0 + (j as f32) * 30.0, None))
+                    .chain([(&recipe.output, 60.0, Some(CraftingRecipe(i)))]);
+
+                for (item, x, crafting_recipe) in items {
+                    let mut item_sprite = parent.spawn_bundle(SpriteSheetBundle {
+                        texture_atlas: game_assets.texture_atlas.clone(),
+                        sprite: TextureAtlasSprite {
+                            index: registry.sprite_index(item.item_type),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_xyz(x, y, 1.0),
+                        ..Default::default()
+                    });
+
+                    if let Some(crafting_recipe) = crafting_recipe {
+                        item_sprite.insert(crafting_recipe);
+                    }
+
+                    parent.spawn_bundle(Text2dBundle {
+                        text: Text::from_section(item.quantity.to_string(), text_style.clone()),
+                        transform: Transform::from_xyz(x + 10.0, y - 4.0, 2.0),
+                        ..Default::default()
+                    });
+                }
+
+                parent.spawn_bundle(Text2dBundle {
+                    text: Text::from_section("=", text_style.clone())
+                        .with_alignment(TextAlignment::CENTER),
+                    transform: Transform::from_xyz(35.0, y, 2.0),
+                    ..Default::default()
+                });
+            }
         });
-}
\ No newline at end of file
+}
+
+fn craft(
+    actions: Res<Input<Action>>,
+    mouse_loc: Res<MouseLoc>,
+    recipe_query: Query<(&GlobalTransform, &CraftingRecipe)>,
+    mut player_inv: Query<&mut InventoryItems, With<LocalPlayer>>,
+    recipes: Res<Recipes>,
+    registry: Res<ItemRegistry>,
+    mut network: ResMut<Network>
+) {
+    if !actions.just_pressed(Action::Craft) {
+        return;
+    }
+
+    for (glob_trans, crafting_recipe) in recipe_query.iter() {
+        let mut length = glob_trans.translation() - mouse_loc.get_vec3();
+        length.z = 0.0;
+
+        if length.length() < CLICK_DIST {
+            let recipe = &recipes.recipes[crafting_recipe.0];
+
+            if recipe.craft(&mut player_inv.single_mut(), &registry) {
+                network.send_to_host(network::ClientMessage::Craft(crafting_recipe.0));
+            } else {
+                println!("Can not craft {}!", registry.get(recipe.output.item_type).name);
+            }
+
+            break;
+        }
+    }
+}
+
+// Dim the recipes that can not be crafted with the current inventory
+fn craftable_highlight(
+    mut recipe_query: Query<(&CraftingRecipe, &mut TextureAtlasSprite)>,
+    player_inv: Query<&InventoryItems, With<LocalPlayer>>,
+    recipes: Res<Recipes>,
+    registry: Res<ItemRegistry>
+) {
+    let inv_items = player_inv.single();
+
+    for (crafting_recipe, mut sprite) in recipe_query.iter_mut() {
+        let alpha = if recipes.recipes[crafting_recipe.0].can_craft(inv_items, &registry) { 1.0 } else { 0.3 };
+
+        sprite.color.set_a(alpha);
+    }
+}
+
+fn crafting_handler(
+    mut actions: ResMut<Input<Action>>,
+    mut state: ResMut<State<AppState>>
+) {
+    if actions.clear_just_pressed(Action::Interact) {
+        state.set(AppState::InGame).unwrap();
+    }
+}
@@ -1,6 +1,4 @@
-use crate::{prelude::*, utils::entity_destruct};
-
-const FENCE_SPEED: f32 = 3.0;
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
 
 pub struct FencePlugin;
 
@@ -8,7 +6,7 @@ impl Plugin for FencePlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_update(AppState::InGame)
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
         .with_system(fence_behaviour));
     }
 }
@@ -16,35 +14,54 @@ impl Plugin for FencePlugin
 pub fn spawn_fence(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     parent_trans: &Transform
-) {
-    (*commands)
+) -> Entity {
+    let def = registry.get(ItemTypes::Fence);
+
+    let mut fence = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: ItemTypes::Fence.sprite_index(),
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    fence
         .insert_bundle(TransformBundle{
-            local: parent_trans.clone(),
+            local: *parent_trans,
             ..Default::default()
         })
-        .insert(EntityHealth{val: 500.0, func_destruct: entity_destruct})
         .insert(Fence);
+
+    insert_block_stats(&mut fence, def, entity_destruct);
+
+    fence.id()
 }
 
+type Slowed = (Without<Fence>, Without<Bullet>);
+
 fn fence_behaviour(
-    mut entity_query: Query<(&Transform, &mut Rigidbody), (Without<Fence>, Without<Bullet>)>,
-    fence_query: Query<&Transform, With<Fence>>
+    mut entity_query: Query<(&Transform, &mut Rigidbody), Slowed>,
+    fence_query: Query<&Transform, With<Fence>>,
+    hash: Res<SpatialHash>,
+    balance: Res<Balance>
 ) {
-    for (ent_trans, mut ent_rb) in entity_query.iter_mut() {
-        for fence_trans in fence_query.iter() {
+    let slow_speed = balance.fence.slow_speed;
+
+    for fence_trans in fence_query.iter() {
+        for entity in hash.within_radius(fence_trans.translation, 20.0) {
+            let (ent_trans, mut ent_rb) = match entity_query.get_mut(entity) {
+                Ok(ent) => ent,
+                Err(_) => continue
+            };
+
             if (ent_trans.translation - fence_trans.translation).length() < 20.0 {
-                ent_rb.vx = ent_rb.vx.clamp(-FENCE_SPEED, FENCE_SPEED);
-                ent_rb.vy = ent_rb.vy.clamp(-FENCE_SPEED, FENCE_SPEED);
+                ent_rb.vx = ent_rb.vx.clamp(-slow_speed, slow_speed);
+                ent_rb.vy = ent_rb.vy.clamp(-slow_speed, slow_speed);
             }
         }
     }
@@ -1,86 +1,186 @@
-use crate::{prelude::*, utils::entity_destruct};
+//! Mining rigs and the ore deposits they are placed on.
+//!
+//! Deposits are generated with the world, see `environment`. A rig can only be placed on a
+//! deposit without a rig, and mines the ore of that deposit at its rate while it is powered.
+//! A deposit holds a limited amount of ore, set in the `mining` balance, and slowly regrows
+//! while it is not full.
 
-use super::windmill::POWER_RADIUS;
+use std::time::Duration;
+
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
 
 pub struct MiningRigPlugin;
 
 const MINED_DROP_RADIUS: f32 = 40.0;
-const ITEM_MINE_TIME: f32 = 55.0;
+
+pub const ORES: [OreKind; 2] = [OreKind::Iron, OreKind::Coal];
 
 impl Plugin for MiningRigPlugin
 {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(miningrig_behaviour));
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(miningrig_behaviour)
+            .with_system(deposit_regeneration));
+    }
+}
+
+impl OreKind
+{
+    pub fn item(self) -> ItemTypes {
+        match self {
+            OreKind::Iron => ItemTypes::IronIngot,
+            OreKind::Coal => ItemTypes::Coal
+        }
     }
+
+}
+
+/// The deposit on the build grid cell of `pos`, if there is one.
+pub fn deposit_at<'a, T>(deposits: impl Iterator<Item = (&'a Transform, T)>, pos: Vec3) -> Option<T> {
+    let cell = navigation::world_to_cell(pos);
+
+    deposits
+        .filter(|(trans, _)| navigation::world_to_cell(trans.translation) == cell)
+        .map(|(_, deposit)| deposit)
+        .next()
+}
+
+pub fn spawn_deposit(
+    commands: &mut Commands,
+    spawn_pos: Vec3,
+    ore: OreKind,
+    quantity: u32,
+    game_asset: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
+    balance: &Balance
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: game_asset.texture_atlas.clone(),
+            sprite: TextureAtlasSprite {
+                index: registry.deposit_sprite(ore),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert_bundle(TransformBundle{
+            // Above the ground and below the rig
+            local: Transform::from_translation(spawn_pos.truncate().extend(0.5)),
+            ..Default::default()
+        })
+        .insert(Deposit {
+            ore,
+            quantity: quantity.min(balance.mining.capacity(ore)),
+            // The duration is set from the balance once the deposit regrows
+            regen_timer: Timer::new(Duration::ZERO, true)
+        })
+        .id()
 }
 
 pub fn spawn_miningrig(
     commands: &mut Commands,
     spawn_pos: Vec3,
     game_asset: &Res<GameAssets>,
-) {
-    (*commands)
+    registry: &Res<ItemRegistry>
+) -> Entity {
+    let def = registry.get(ItemTypes::MiningRig);
+
+    let mut rig = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_asset.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: ItemTypes::MiningRig.sprite_index(),
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    rig
         .insert_bundle(TransformBundle{
             local: Transform::from_translation(spawn_pos),
             ..Default::default()
         })
-        .insert(MiningRig(Timer::from_seconds(ITEM_MINE_TIME, true)))
-        .insert(StaticEntity)
-        .insert(Attackable(TargetPriority::Medium))
-        .insert(EntityHealth{val: 1000.0, func_destruct: entity_destruct})
-        .insert(BoxCollider {
-            size: Vec2::new(20.0, 20.0)
-        });
+        // The duration is set to the rate of the deposit once the rig mines
+        .insert(MiningRig(Timer::new(Duration::ZERO, true)))
+        .insert(StaticEntity);
+
+    insert_block_stats(&mut rig, def, entity_destruct);
+
+    rig.id()
 }
 
+#[allow(clippy::too_many_arguments)]
 fn miningrig_behaviour(
     mut commands: Commands,
-    mut rig_query: Query<(&mut MiningRig, &Transform)>,
-    power_query: Query<&Transform, With<WindMill>>,
+    mut rig_query: Query<(&mut MiningRig, &Transform, &PowerNode)>,
+    mut deposit_query: Query<(&Transform, &mut Deposit)>,
+    time: Res<Time>,
+    game_asset: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    mut game_rng: ResMut<GameRng>,
+    mut mined_writer: EventWriter<ItemMinedEvent>,
+    balance: Res<Balance>
+) {
+    for (mut rig, trans, power) in rig_query.iter_mut() {
+        let mut deposit = match deposit_at(deposit_query.iter_mut(), trans.translation) {
+            Some(deposit) => deposit,
+            None => continue
+        };
+
+        let mine_time = Duration::from_secs_f32(balance.mining.mine_time(deposit.ore));
+
+        if rig.0.duration() != mine_time {
+            rig.0.set_duration(mine_time);
+        }
+
+        // An exhausted deposit has to regrow before the rig can mine again
+        if deposit.quantity == 0 {
+            continue;
+        }
+
+        // Browned out rigs mine slower
+        if rig.0.tick(time.delta().mul_f32(power.satisfaction)).just_finished() {
+            let rng = &mut game_rng.gameplay;
+
+            let rand_radius: f32 = rng.gen::<f32>()*MINED_DROP_RADIUS;
+            let rand_angle: f32 = std::f32::consts::TAU * rng.gen::<f32>();
+
+            let spawn_pos = Vec3::new(rand_angle.cos()*rand_radius, rand_angle.sin()*rand_radius, 0.0);
+            let spawn_trans = Transform::from_translation(spawn_pos + trans.translation);
+
+            let drop_item = Item {
+                item_type: deposit.ore.item(),
+                quantity: 1
+            };
+
+            deposit.quantity -= 1;
+
+            spawn_dropped(&mut commands,
+                &game_asset,
+                &registry,
+                &spawn_trans,
+                drop_item.clone());
+
+            mined_writer.send(ItemMinedEvent { item: drop_item });
+        }
+    }
+}
+
+fn deposit_regeneration(
+    mut query: Query<&mut Deposit>,
     time: Res<Time>,
-    game_asset: Res<GameAssets>
+    balance: Res<Balance>
 ) {
-    'outer: for (mut rig, trans) in rig_query.iter_mut() {
-        for power_trans in power_query.iter() {
-            if (power_trans.translation - trans.translation).length() <= POWER_RADIUS {
-                if rig.0.tick(time.delta()).just_finished() {
-                    println!("Spawning item!");
-
-                    let mut rng = thread_rng();
-
-                    let rand_radius: f32 = rng.gen::<f32>()*MINED_DROP_RADIUS;
-                    let rand_angle: f32 = 2.0 * 3.14 * rng.gen::<f32>();
-
-                    let spawn_pos = Vec3::new(rand_angle.cos()*rand_radius, rand_angle.sin()*rand_radius, 0.0);
-                    let spawn_trans = Transform::from_translation(spawn_pos + trans.translation);
-
-                    let drop_types: Vec<ItemTypes> = vec![
-                        ItemTypes::IronIngot,
-                        ItemTypes::Coal
-                    ];
-            
-                    let drop_item = Item {
-                        item_type: drop_types[rng.gen_range(0..=1)],
-                        quantity: 1
-                    };
-
-                    spawn_dropped(&mut commands,
-                        &game_asset,
-                        &spawn_trans,
-                        drop_item.clone());
-
-                    continue 'outer;
-                }
-            }
+    let regen_time = Duration::from_secs_f32(balance.mining.deposit_regen_time);
+
+    for mut deposit in query.iter_mut() {
+        if deposit.regen_timer.duration() != regen_time {
+            deposit.regen_timer.set_duration(regen_time);
+        }
+
+        if deposit.quantity < balance.mining.capacity(deposit.ore) && deposit.regen_timer.tick(time.delta()).just_finished() {
+            deposit.quantity += 1;
         }
     }
-}
\ No newline at end of file
+}
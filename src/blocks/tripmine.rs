@@ -1,10 +1,8 @@
-use crate::prelude::*;
+use std::time::Duration;
+
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
 
-const TRIGGER_DIST: f32 = 20.0;
-const BLAST_RADIUS: f32 = 100.0;
-const EXPLOSION_DMG_PER_FRAME: f32 = 200.0;
 // const EXPLOSIVE_ACC: f32 = 400.0;
-const EXPLOSION_TIME: f32 = 0.5;
 
 pub struct TripMinePlugin;
 
@@ -12,7 +10,7 @@ impl Plugin for TripMinePlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_update(AppState::InGame)
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
         .with_system(tripmine_detonator)
         .with_system(explosion_behaviour));
     }
@@ -22,12 +20,16 @@ fn tripmine_detonator(
     mut commands: Commands,
     tripmine_query: Query<(Entity, &Transform), With<TripMine>>,
     zombie_query: Query<&Transform, With<Zombie>>,
-    game_assets: Res<GameAssets>
+    game_assets: Res<GameAssets>,
+    hash: Res<SpatialHash>,
+    balance: Res<Balance>
 ) {
+    let trigger_dist = balance.tripmine.trigger_distance;
+
     'outer: for (tm_entity, tm_trans) in tripmine_query.iter() {
-        for zomb_trans in zombie_query.iter() {
-            if (tm_trans.translation - zomb_trans.translation).length() < TRIGGER_DIST {
-                tripwire_destruct(&mut commands, &tm_entity, &game_assets, &tm_trans);
+        for zomb_trans in hash.within_radius(tm_trans.translation, trigger_dist).filter_map(|e| zombie_query.get(e).ok()) {
+            if (tm_trans.translation - zomb_trans.translation).length() < trigger_dist {
+                tripwire_destruct(&mut commands, &tm_entity, &game_assets, tm_trans);
                 continue 'outer;
             }
         }
@@ -37,24 +39,33 @@ fn tripmine_detonator(
 pub fn spawn_tripmine(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     parent_trans: &Transform
-) {
-    (*commands)
+) -> Entity {
+    let def = registry.get(ItemTypes::TripMine);
+
+    let mut tripmine = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: ItemTypes::TripMine.sprite_index(),
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    tripmine
         .insert_bundle(TransformBundle{
-            local: parent_trans.clone(),
+            local: *parent_trans,
             ..Default::default()
         })
         .insert(StaticEntity)
         .insert(TripMine);
+
+    insert_block_stats(&mut tripmine, def, entity_destruct);
+
+    tripmine.id()
 }
 
 fn tripwire_destruct(
@@ -67,12 +78,12 @@ fn tripwire_destruct(
     spawn_explosion(commands, game_assets, parent_trans)
 }
 
-fn spawn_explosion(
+pub fn spawn_explosion(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
     parent_trans: &Transform
 ) {
-    let expl_trans = parent_trans.clone().with_scale(Vec3::ONE*4.0);
+    let expl_trans = parent_trans.with_scale(Vec3::ONE*4.0);
 
     (*commands)
         .spawn_bundle(SpriteSheetBundle {
@@ -88,23 +99,34 @@ fn spawn_explosion(
             local: expl_trans,
             ..Default::default()
         })
-        .insert(Explosion(Timer::from_seconds(EXPLOSION_TIME, false)));
+        // The duration is taken from the balance on the first tick
+        .insert(Explosion(Timer::default()));
 }
 
+type ExplosionTarget<'a> = (&'a Transform, &'a mut EntityHealth);
+
 fn explosion_behaviour(
     mut commands: Commands,
     mut expl_query: Query<(Entity, &mut Transform, &mut Explosion), With<Explosion>>,
-    mut zombie_query: Query<(&Transform, &mut EntityHealth), (With<EntityHealth>, Without<Explosion>)>,
-    time: Res<Time>
+    mut zombie_query: Query<ExplosionTarget, (With<EntityHealth>, Without<Explosion>)>,
+    time: Res<Time>,
+    balance: Res<Balance>
 ) {
+    let explosion = &balance.tripmine;
+    let explosion_time = Duration::from_secs_f32(explosion.explosion_time);
+
     for (expl_entity, mut expl_trans, mut expl_expl) in expl_query.iter_mut() {
         for (zombie_trans, mut zombie_health) in zombie_query.iter_mut() {
             let expl_to_zomb: Vec3 = zombie_trans.translation - expl_trans.translation;            
-            if expl_to_zomb.length() <= BLAST_RADIUS  {
-                zombie_health.val -= EXPLOSION_DMG_PER_FRAME*time.delta_seconds();
+            if expl_to_zomb.length() <= explosion.blast_radius  {
+                zombie_health.val -= explosion.explosion_damage*time.delta_seconds();
             }
         }
 
+        if expl_expl.0.duration() != explosion_time {
+            expl_expl.0.set_duration(explosion_time);
+        }
+
         expl_trans.scale *= 0.85;
         if expl_expl.0.tick(time.delta()).just_finished() {
             commands.entity(expl_entity).despawn();
@@ -1,6 +1,24 @@
-use crate::prelude::{*, weapons::BLLT_RANDOM};
+//! Turrets shooting at zombies in range.
+//!
+//! Every turret picks its target by its `TargetingMode`, among the zombies it can see
+//! within its range, and can lead a moving target. The mode, range and leading are kept per
+//! turret and saved with it. Turrets fire Ammo from an internal store. Pressing E next to a
+//! turret refills the store from the inventory, and T switches to the next targeting mode.
 
-use super::windmill::POWER_RADIUS;
+use crate::prelude::{*, registry::insert_block_stats};
+
+// Sheet index of the debris left by a destroyed turret
+const DEBRIS_SPRITE: usize = 5;
+
+const TARGETING_MODES: [TargetingMode; 4] = [
+    TargetingMode::Nearest,
+    TargetingMode::LowestHealth,
+    TargetingMode::HighestHealth,
+    TargetingMode::FirstToBase
+];
+
+type ZombieTarget<'a> = (&'a Transform, &'a Rigidbody, &'a EntityHealth, &'a ZombieArchetype);
+type TurretState<'a> = (&'a Transform, &'a PowerNode, &'a TurretTargeting, &'a mut AmmoStore, &'a mut TurretShootTimer, &'a mut TurretCoolTimer, &'a mut TurretBulletTimer);
 
 pub struct TurretPlugin;
 
@@ -10,33 +28,44 @@ impl Plugin for TurretPlugin
         app
         .add_system_set(SystemSet::on_enter(AppState::GameSetup)
             .with_system(turret_setup))
-        .add_system_set(SystemSet::on_update(AppState::InGame)
-            .with_system(turret_targeting));
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(turret_targeting)
+            .with_system(turret_interact)
+            .with_system(turret_capacity)
+            .with_system(scatter_debris));
     }
 }
 
 fn turret_setup(
     mut commands: Commands,
-    game_assets: Res<GameAssets>
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    balance: Res<Balance>
 ) {
     let spawn_pos = Vec3::new(0.0, 120.0, 3.0);
-    spawn_turret(&mut commands, spawn_pos, &game_assets);
+    spawn_turret(&mut commands, spawn_pos, &game_assets, &registry, &balance);
 }
 
 pub fn spawn_turret(
     commands: &mut Commands,
     spawn_pos: Vec3,
-    game_assets: &Res<GameAssets>
-) {
-    commands
+    game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
+    balance: &Balance
+) -> Entity {
+    let def = registry.get(ItemTypes::TurretBlock);
+
+    let mut turret = commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: 5,
+                index: def.sprite_index,
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    turret
         .insert_bundle(TransformBundle{
             local: Transform::from_translation(spawn_pos),
             ..Default::default()
@@ -45,67 +74,177 @@ pub fn spawn_turret(
         .insert(TurretShootTimer(Timer::from_seconds(1.5, true)))
         .insert(TurretBulletTimer(Timer::from_seconds(0.5, true)))
         .insert(TurretCoolTimer(Timer::from_seconds(6.0, true)))
-        .insert(EntityHealth{val: 300.0, func_destruct: turret_destruct})
-        .insert(Attackable(TargetPriority::Medium));
+        .insert(AmmoStore { rounds: balance.turret.start_ammo, capacity: balance.turret.ammo_capacity })
+        .insert(TurretTargeting { mode: TargetingMode::Nearest, range: balance.turret.range, lead: true })
+        .insert(InteractableEntity { interact_type: InteractionType::Turret });
+
+    insert_block_stats(&mut turret, def, turret_destruct);
+
+    turret.id()
+}
+
+impl TargetingMode
+{
+    pub fn next(self) -> Self {
+        let index = TARGETING_MODES.iter().position(|mode| *mode == self).unwrap();
+
+        TARGETING_MODES[(index + 1) % TARGETING_MODES.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TargetingMode::Nearest => "nearest",
+            TargetingMode::LowestHealth => "lowest health",
+            TargetingMode::HighestHealth => "highest health",
+            TargetingMode::FirstToBase => "first to reach the base"
+        }
+    }
+
+    // Lower is better
+    fn score(self, turret: Vec3, (trans, _, health, archetype): ZombieTarget, field: &FlowField) -> (f32, f32) {
+        let dist = (trans.translation - turret).length();
+
+        match self {
+            TargetingMode::Nearest => (0.0, dist),
+            TargetingMode::LowestHealth => (health.val, dist),
+            TargetingMode::HighestHealth => (-archetype.0.scale, -health.val),
+            // Zombies without a known path, e.g. attacking a wall, go by their straight distance
+            TargetingMode::FirstToBase => (0.0, field.walking_distance(trans.translation)
+                .unwrap_or_else(|| (trans.translation - navigation::cell_to_world(field.origin)).length()))
+        }
+    }
+}
+
+/// Where a bullet fired from `from` meets a target at `pos` moving with `velocity`.
+fn lead_target(from: Vec3, pos: Vec3, velocity: Vec3, bullet_speed: f32) -> Vec3 {
+    let mut aim = pos;
+
+    // Converges quickly, as bullets are much faster than zombies
+    for _ in 0..3 {
+        let flight_time = (aim - from).length() / bullet_speed;
+        aim = pos + velocity * flight_time;
+    }
+
+    aim
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn turret_targeting(
-    mut turret_query: Query<(&Transform, &mut TurretShootTimer, &mut TurretCoolTimer, &mut TurretBulletTimer), (With<Turret>, Without<Zombie>)>,
-    zombie_query: Query<&Transform, (With<Zombie>, Without<Turret>)>,
+    mut turret_query: Query<TurretState, (With<Turret>, Without<Zombie>)>,
+    zombie_query: Query<ZombieTarget, (With<Zombie>, Without<Turret>)>,
+    field: Res<FlowField>,
     static_query: Query<&Transform, (With<StaticEntity>, Without<Pathfinder>)>,
-    power_query: Query<&Transform, With<WindMill>>,
+    mut game_rng: ResMut<GameRng>,
+    balance: Res<Balance>,
     time: Res<Time>,
     mut commands: Commands
 ) {
+    let bullets = &balance.turret;
     let static_vec: Vec<&Transform> = static_query.iter().collect();
 
-    'outer: for (turret, mut t_shoot, mut t_cool, mut t_bullet) in turret_query.iter_mut() {
-        for power_trans in power_query.iter() {
-            if (turret.translation - power_trans.translation).length() < POWER_RADIUS {
-                let mut target_shoot: Vec3 = Vec3::ZERO;
-                let mut shoot: bool = false;
+    for (turret, power, targeting, mut ammo, mut t_shoot, mut t_cool, mut t_bullet) in turret_query.iter_mut() {
+        if power.satisfaction <= 0.0 || ammo.rounds == 0 {
+            continue;
+        }
 
-                for zombie in zombie_query.iter() {
+        // Browned out turrets fire and cool down slower
+        let delta = time.delta().mul_f32(power.satisfaction);
 
-                    if (turret.translation - zombie.translation).length() > 150.0 {
-                        continue;
-                    }
-        
-                    if !zombie::is_hindered(&static_vec, &turret, &zombie) {
-                        target_shoot = zombie.translation;
-                        shoot = true;
-                    }
+        let target = zombie_query.iter()
+            .filter(|(zombie, ..)| (turret.translation - zombie.translation).length() <= targeting.range)
+            .filter(|(zombie, ..)| !zombie::is_hindered(&static_vec, &turret, zombie))
+            .map(|zombie| (targeting.mode.score(turret.translation, zombie, &field), zombie))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+            .map(|(_, (zombie, rb, ..))| {
+                if targeting.lead {
+                    lead_target(turret.translation, zombie.translation, Vec3::new(rb.vx, rb.vy, 0.0), bullets.bullet_speed)
+                } else {
+                    zombie.translation
                 }
+            });
 
-                if !t_cool.0.just_finished() {
-                    t_cool.0.tick(time.delta());
-                } else {
-                    if shoot {
-                        if t_shoot.0.tick(time.delta()).just_finished() {
-                            t_cool.0.tick(time.delta());
-                        }
+        let shoot = target.is_some();
+        let target_shoot = target.unwrap_or(Vec3::ZERO);
+
+        if !t_cool.0.just_finished() {
+            t_cool.0.tick(delta);
+        } else {
+            if shoot {
+                if t_shoot.0.tick(delta).just_finished() {
+                    t_cool.0.tick(delta);
+                }
+
+                if t_bullet.0.tick(delta).just_finished() {
 
-                        if t_bullet.0.tick(time.delta()).just_finished() {
+                    // Target zombie and shoot!
 
-                            // Target zombie and shoot!
+                    let angle = angle_between(turret.translation, target_shoot);
 
-                            let angle = angle_between(turret.translation, target_shoot);
+                    let rand_angle: f32 = (game_rng.gameplay.gen::<f32>() - 0.5) * bullets.spread;
 
-                            let mut rng = rand::thread_rng();
-                            let rand_angle: f32 = (rng.gen::<f32>() - 0.5) * 7.0 * BLLT_RANDOM;
+                    weapons::spawn_bullet(&mut commands, turret.translation, angle, rand_angle, bullets.bullet_speed, bullets.bullet_damage);
 
-                            weapons::spawn_bullet(&mut commands, turret.translation, angle, rand_angle);
+                    ammo.rounds -= 1;
 
-                        }
+                    if ammo.rounds == 0 {
+                        println!("A turret ran out of ammo");
                     }
-                }
 
-                continue 'outer;
+                }
             }
         }
     }
 }
 
+fn turret_interact(
+    mut turret_reader: EventReader<TurretInteractEvent>,
+    mut turret_query: Query<(&mut AmmoStore, &mut TurretTargeting), With<Turret>>,
+    mut player_query: Query<&mut InventoryItems, With<Player>>
+) {
+    for interaction in turret_reader.iter() {
+        let (mut ammo, mut targeting) = match turret_query.get_mut(interaction.turret_entity) {
+            Ok(turret) => turret,
+            Err(_) => continue
+        };
+
+        if interaction.cycle_mode {
+            targeting.mode = targeting.mode.next();
+
+            println!("The turret targets the {} zombie", targeting.mode.name());
+            continue;
+        }
+
+        let mut inv_items = match player_query.get_mut(interaction.player_entity) {
+            Ok(inv_items) => inv_items,
+            Err(_) => continue
+        };
+
+        let rounds = (ammo.capacity.saturating_sub(ammo.rounds) as u32).min(inv_items.quantity(ItemTypes::Ammo)) as u16;
+
+        if rounds > 0 {
+            inv_items.remove_item(&Item { item_type: ItemTypes::Ammo, quantity: rounds }).unwrap();
+            ammo.rounds += rounds;
+        }
+
+        println!("The turret has {}/{} rounds", ammo.rounds, ammo.capacity);
+    }
+}
+
+// A reloaded balance may have changed the size of the ammo store
+fn turret_capacity(
+    mut turret_query: Query<&mut AmmoStore, With<Turret>>,
+    balance: Res<Balance>
+) {
+    if !balance.is_changed() {
+        return;
+    }
+
+    for mut ammo in turret_query.iter_mut() {
+        ammo.capacity = balance.turret.ammo_capacity;
+        ammo.rounds = ammo.rounds.min(ammo.capacity);
+    }
+}
+
 fn turret_destruct(
     commands: &mut Commands,
     entity: &Entity,
@@ -123,25 +262,34 @@ fn spawn_destroyed(
     game_assets: &Res<GameAssets>
 )
 {
-    let mut rng = thread_rng();
-
     for _ in 0..5
     {
-        let rand_vec: Vec3 = Vec3::new(rng.gen::<f32>()*20.0, rng.gen::<f32>()*20.0, rng.gen::<f32>()*20.0);
-
         (*commands)
             .spawn_bundle(SpriteSheetBundle {
                 texture_atlas: game_assets.texture_atlas.clone(),
                 sprite: TextureAtlasSprite {
-                    index: ItemTypes::TurretBlock.sprite_index(),
+                    index: DEBRIS_SPRITE,
                     ..Default::default()
                 },
                 ..Default::default()
             })
             .insert_bundle(TransformBundle{
-                local: Transform::from_translation(spawn_pos + rand_vec),
+                local: Transform::from_translation(spawn_pos),
                 ..Default::default()
             })
             .insert(TempTurretDestroyed::new());
     }
-}
\ No newline at end of file
+}
+
+// The destruct callback has no access to resources, so the debris is spread out here,
+// with the gameplay rng to keep seeded runs repeatable.
+fn scatter_debris(
+    mut debris_query: Query<&mut Transform, Added<TempTurretDestroyed>>,
+    mut game_rng: ResMut<GameRng>
+) {
+    let rng = &mut game_rng.gameplay;
+
+    for mut trans in debris_query.iter_mut() {
+        trans.translation += Vec3::new(rng.gen::<f32>()*20.0, rng.gen::<f32>()*20.0, rng.gen::<f32>()*20.0);
+    }
+}
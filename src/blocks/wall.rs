@@ -1,4 +1,4 @@
-use crate::{prelude::*, utils::entity_destruct};
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
 
 pub struct WallPlugin;
 
@@ -12,27 +12,31 @@ impl Plugin for WallPlugin
 pub fn spawn_wall(
     commands: &mut Commands,
     spawn_pos: Vec3,
-    game_asset: &Res<GameAssets>
-) {
-    (*commands)
+    game_asset: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>
+) -> Entity {
+    let def = registry.get(ItemTypes::WallBlock);
+
+    let mut wall = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_asset.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: 0,
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    wall
         .insert_bundle(TransformBundle{
             local: Transform::from_translation(spawn_pos),
             ..Default::default()
         })
         .insert(Wall)
-        .insert(StaticEntity)
-        .insert(Attackable(TargetPriority::Low))
-        .insert(EntityHealth{val: 400.0, func_destruct: entity_destruct})
-        .insert(BoxCollider {
-            size: Vec2::new(20.0, 20.0)
-        });
+        .insert(StaticEntity);
+
+    insert_block_stats(&mut wall, def, entity_destruct);
+
+    wall.id()
 }
\ No newline at end of file
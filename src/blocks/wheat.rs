@@ -1,6 +1,22 @@
-use crate::{prelude::*, utils::entity_destruct};
+//! Wheat crops planted from seeds.
+//!
+//! A crop advances one state every `wheat.grow_time` seconds of the balance. It is
+//! mature from `MATURE_STATE` on and can be harvested with E for wheat and new seeds,
+//! until it withers at `WITHERED_STATE`. Zombies and animals walking over a crop
+//! trample it back to the first state.
 
-const TIME_STATE_CHANGE: f32 = 45.0;
+use std::time::Duration;
+
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
+
+pub const MATURE_STATE: u8 = 2;
+pub const WITHERED_STATE: u8 = 5;
+
+const TRAMPLE_DIST: f32 = 10.0;
+const WITHERED_COLOR: Color = Color::rgb(0.6, 0.45, 0.25);
+
+// Entities that trample crops they walk over
+type TramplerFilter = Or<(With<Zombie>, With<Animal>)>;
 
 pub struct WheatPlugin;
 
@@ -8,51 +24,134 @@ impl Plugin for WheatPlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(wheat_behaviour));
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(wheat_behaviour)
+            .with_system(wheat_trample)
+            .with_system(wheat_harvest)
+            .with_system(wheat_sprite.after(wheat_behaviour).after(wheat_trample)));
     }
 }
 
 pub fn wheat_behaviour(
-    mut wheat_query: Query<(&mut Wheat, &mut TextureAtlasSprite), With<Wheat>>,
-    time: Res<Time>
+    mut wheat_query: Query<&mut Wheat>,
+    time: Res<Time>,
+    balance: Res<Balance>
+) {
+    let grow_time = Duration::from_secs_f32(balance.wheat.grow_time);
+
+    for mut wheat in wheat_query.iter_mut() {
+        if wheat.timer.duration() != grow_time {
+            wheat.timer.set_duration(grow_time);
+        }
+
+        if wheat.timer.tick(time.delta()).just_finished() && wheat.state < WITHERED_STATE {
+            wheat.state += 1;
+        }
+    }
+}
+
+fn wheat_trample(
+    mut wheat_query: Query<(&Transform, &mut Wheat)>,
+    trampler_query: Query<&Transform, TramplerFilter>,
+    hash: Res<SpatialHash>
+) {
+    for (trans, mut wheat) in wheat_query.iter_mut() {
+        if wheat.state == 0 {
+            continue;
+        }
+
+        let trampled = hash.within_radius(trans.translation, TRAMPLE_DIST)
+            .filter_map(|e| trampler_query.get(e).ok())
+            .any(|trampler| (trampler.translation - trans.translation).length() < TRAMPLE_DIST);
+
+        if trampled {
+            wheat.state = 0;
+            wheat.timer.reset();
+        }
+    }
+}
+
+fn wheat_harvest(
+    mut commands: Commands,
+    mut harvest_reader: EventReader<HarvestInteractEvent>,
+    wheat_query: Query<(&Transform, &Wheat)>,
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    balance: Res<Balance>
 ) {
-    for (mut wheat, mut sprite) in wheat_query.iter_mut() {
-        if wheat.timer.tick(time.delta()).just_finished() {
-            if wheat.state < 2 {
-                sprite.index += 1;
-                wheat.state += 1;
-            }
+    let yields = &balance.wheat;
+
+    for harvest in harvest_reader.iter() {
+        let (trans, wheat) = match wheat_query.get(harvest.crop_entity) {
+            Ok(crop) => crop,
+            Err(_) => continue
+        };
+
+        let drops = if wheat.state >= WITHERED_STATE {
+            vec![Item { item_type: ItemTypes::Seeds, quantity: yields.withered_seeds }]
+        } else if wheat.state >= MATURE_STATE {
+            vec![
+                Item { item_type: ItemTypes::Wheat, quantity: yields.harvest_wheat },
+                Item { item_type: ItemTypes::Seeds, quantity: yields.harvest_seeds }
+            ]
+        } else {
+            println!("The wheat is not ready to be harvested yet");
+            continue;
+        };
+
+        commands.entity(harvest.crop_entity).despawn();
+
+        for item in drops {
+            spawn_dropped(&mut commands, &game_assets, &registry, trans, item);
         }
     }
+}
 
+fn wheat_sprite(
+    mut wheat_query: Query<(&Wheat, &mut TextureAtlasSprite), Changed<Wheat>>,
+    registry: Res<ItemRegistry>
+) {
+    let seeds_sprite = registry.sprite_index(ItemTypes::Seeds);
+
+    for (wheat, mut sprite) in wheat_query.iter_mut() {
+        sprite.index = seeds_sprite + wheat.state.min(MATURE_STATE) as usize;
+
+        sprite.color = if wheat.state >= WITHERED_STATE { WITHERED_COLOR } else { Color::WHITE };
+    }
 }
 
 pub fn spawn_wheat(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     parent_trans: &Transform
-) {
-    (*commands)
+) -> Entity {
+    let def = registry.get(ItemTypes::Seeds);
+
+    let mut wheat = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: 17,
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    wheat
         .insert_bundle(TransformBundle{
-            local: parent_trans.clone(),
+            local: *parent_trans,
             ..Default::default()
         })
-        .insert(Attackable(TargetPriority::Low))
-        .insert(EntityHealth {
-            val: 1.0,
-            func_destruct: entity_destruct 
-        })
         .insert(Wheat {
             state: 0,
-            timer: Timer::from_seconds(TIME_STATE_CHANGE, true)
-        });
-}
\ No newline at end of file
+            // The duration is set from the balance once the crop grows
+            timer: Timer::new(Duration::ZERO, true)
+        })
+        .insert(InteractableEntity {interact_type: InteractionType::Harvest});
+
+    insert_block_stats(&mut wheat, def, entity_destruct);
+
+    wheat.id()
+}
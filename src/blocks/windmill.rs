@@ -1,7 +1,6 @@
-use crate::{prelude::*, utils::entity_destruct};
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
 
-const ROT_SPEED: f32 = 1.5*3.14;
-pub const POWER_RADIUS: f32 = 80.0;
+const ROT_SPEED: f32 = 1.5*std::f32::consts::PI;
 
 pub struct WindMillPlugin;
 
@@ -9,7 +8,7 @@ impl Plugin for WindMillPlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(windmill_behaviour));
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running).with_system(windmill_behaviour));
     }
 }
 
@@ -27,14 +26,17 @@ pub fn windmill_behaviour(
 pub fn spawn_windmill(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     parent_trans: &Transform
-) {
+) -> Entity {
+    let def = registry.get(ItemTypes::WindMill);
+
     // Blade of windmill
     let blade_entity = (*commands)
     .spawn_bundle(SpriteSheetBundle {
         texture_atlas: game_assets.texture_atlas.clone(),
         sprite: TextureAtlasSprite {
-            index: ItemTypes::WindMill.sprite_index() + 1,
+            index: def.sprite_index + 1,
             custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
             ..Default::default()
         },
@@ -48,30 +50,28 @@ pub fn spawn_windmill(
     .id();
 
     // Windmill structure spawning
-    (*commands)
+    let mut windmill = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: ItemTypes::WindMill.sprite_index(),
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    windmill
         .insert_bundle(TransformBundle{
-            local: parent_trans.clone(),
+            local: *parent_trans,
             ..Default::default()
         })
-        .insert(Attackable(TargetPriority::Low))
         .insert(StaticEntity)
-        .insert(BoxCollider {
-            size: Vec2 { x: 20.0, y: 20.0 }
-        })
-        .insert(EntityHealth {
-            val: 200.0,
-            func_destruct: entity_destruct 
-        })
         .insert(WindMill)
         .add_child(blade_entity);
 
+    insert_block_stats(&mut windmill, def, entity_destruct);
+
+    windmill.id()
+
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::{prelude::*, utils::entity_destruct};
+use crate::{prelude::*, utils::entity_destruct, systems::registry::insert_block_stats};
 
 pub struct WoodFencePlugin;
 
@@ -12,26 +12,31 @@ impl Plugin for WoodFencePlugin
 pub fn spawn_woodfence(
     commands: &mut Commands,
     game_asset: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     spawn_trans: &Transform
-) {
-    (*commands)
+) -> Entity {
+    let def = registry.get(ItemTypes::WoodFence);
+
+    let mut woodfence = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_asset.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: ItemTypes::WoodFence.sprite_index(),
+                index: def.sprite_index,
                 custom_size: Some(Vec2 { x: 20.0, y: 20.0 }),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    woodfence
         .insert_bundle(TransformBundle{
-            local: spawn_trans.clone(),
+            local: *spawn_trans,
             ..Default::default()
         })
-        .insert(StaticEntity)
-        .insert(Attackable(TargetPriority::Low))
-        .insert(EntityHealth{val: 400.0, func_destruct: entity_destruct})
-        .insert(BoxCollider {
-            size: Vec2::new(20.0, 20.0)
-        });
+        .insert(WoodFence)
+        .insert(StaticEntity);
+
+    insert_block_stats(&mut woodfence, def, entity_destruct);
+
+    woodfence.id()
 }
\ No newline at end of file
@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
 use crate::{prelude::*, GameAssets};
 
 #[derive(Component)]
@@ -27,7 +31,7 @@ pub struct Animal
     pub stroll_direction: Vec3
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Deserialize)]
 pub enum TargetPriority {
     High = 3,
     Medium = 2,
@@ -38,6 +42,8 @@ pub enum TargetPriority {
 pub struct Pathfinder
 {
     pub target: Vec3,
+    // Next cell on the way around obstacles to the target, see `navigation`
+    pub waypoint: Option<Vec3>,
     pub target_entity: bool,
     pub target_priority: TargetPriority
 }
@@ -49,82 +55,196 @@ pub struct Wheat
     pub timer: Timer
 }
 
-#[derive(Component)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TargetingMode
+{
+    Nearest,
+    LowestHealth,
+    // The largest zombies first, then the healthiest
+    HighestHealth,
+    // The zombie with the shortest way left to the player
+    FirstToBase
+}
+
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct TurretTargeting
 {
-    pub target: Vec3,
-    pub shoot: bool
+    pub mode: TargetingMode,
+    pub range: f32,
+    // Aim where a moving target will be when the bullet gets there
+    pub lead: bool
 }
 
+/// Fixed number of slots, each holding a stack of up to the `max_stack` of its item.
 #[derive(Component, Clone)]
 pub struct InventoryItems
 {
-    pub items: Vec<Item>
+    pub slots: Vec<Option<Item>>
+}
+
+/// A removal asked for more than the inventory holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotEnoughItems
+{
+    pub item_type: ItemTypes,
+    pub available: u32
 }
 
 impl InventoryItems
 {
-    pub fn get_index(&self, item_type: ItemTypes) -> usize
-    {
-        return self
-        .items
-        .iter()
-        .position(|p| p.item_type.eq(&item_type))
-        .unwrap_or_else(|| usize::MAX);
+    pub fn new(size: usize) -> Self {
+        InventoryItems {
+            slots: vec![None; size]
+        }
+    }
+
+    /// The stacks in the inventory, in slot order.
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.slots.iter().flatten()
+    }
+
+    /// Every item type in the inventory once, in the order of their first slot.
+    pub fn item_types(&self) -> Vec<ItemTypes> {
+        let mut types: Vec<ItemTypes> = Vec::new();
+
+        for item in self.items() {
+            if !types.contains(&item.item_type) {
+                types.push(item.item_type);
+            }
+        }
+
+        types
     }
 
     pub fn has_item(&self, item_type: ItemTypes) -> bool
     {
-        self.get_index(item_type) != usize::MAX
+        self.items().any(|item| item.item_type == item_type)
     }
 
-    pub fn add_item(&mut self, item: Item) {
-        if self.has_item(item.item_type)
-        {
-            let item_index = self.get_index(item.item_type);
-            self.items[item_index].quantity += 1;
-        } else {
-            self.items.push(item);
-        }
+    /// Total over all stacks of `item_type`.
+    pub fn quantity(&self, item_type: ItemTypes) -> u32
+    {
+        self.items()
+        .filter(|item| item.item_type == item_type)
+        .map(|item| item.quantity as u32)
+        .sum()
+    }
+
+    /// Whether both hold the same number of every item, no matter how it is stacked.
+    pub fn same_contents(&self, other: &InventoryItems) -> bool
+    {
+        self.slots.len() == other.slots.len()
+            && self.item_types().into_iter().chain(other.item_types())
+                .all(|item_type| self.quantity(item_type) == other.quantity(item_type))
     }
 
-    pub fn remove_item(&mut self, item: Item)
+    /// How many of `item_type` still fit, on top of existing stacks and in empty slots.
+    pub fn room_for(&self, item_type: ItemTypes, max_stack: u16) -> u32
     {
-        assert!(self.has_item(item.item_type));
+        self.slots.iter().map(|slot| match slot {
+            None => max_stack as u32,
+            Some(item) if item.item_type == item_type => max_stack.saturating_sub(item.quantity) as u32,
+            Some(_) => 0
+        })
+        .sum()
+    }
 
-        let invitem_index = self.get_index(item.item_type);
+    pub fn can_add(&self, item: &Item, max_stack: u16) -> bool
+    {
+        self.room_for(item.item_type, max_stack) >= item.quantity as u32
+    }
+
+    /// Adds as much of `item` as fits, topping up existing stacks before using empty
+    /// slots. What does not fit is returned as the error.
+    pub fn add_item(&mut self, item: Item, max_stack: u16) -> Result<(), Item>
+    {
+        let mut left = item.quantity;
+
+        for stack in self.slots.iter_mut().flatten() {
+            if left == 0 {
+                break;
+            }
+
+            if stack.item_type == item.item_type && stack.quantity < max_stack {
+                let moved = left.min(max_stack - stack.quantity);
+                stack.quantity += moved;
+                left -= moved;
+            }
+        }
 
-        assert!(self.items[invitem_index].quantity >= item.quantity);
+        for slot in self.slots.iter_mut() {
+            if left == 0 {
+                break;
+            }
 
-        if self.items[invitem_index].quantity == item.quantity {
-            self.items.remove(invitem_index);
+            if slot.is_none() {
+                let moved = left.min(max_stack);
+                *slot = Some(Item { item_type: item.item_type, quantity: moved });
+                left -= moved;
+            }
+        }
+
+        if left == 0 {
+            Ok(())
         } else {
-            self.items[invitem_index].quantity -= item.quantity
+            Err(Item { item_type: item.item_type, quantity: left })
         }
     }
 
-    pub fn tick_or_remove(&mut self, item_type: ItemTypes)
+    /// Removes `item.quantity` of its type, taking from the last stacks first. Nothing is
+    /// removed if the inventory does not hold enough.
+    pub fn remove_item(&mut self, item: &Item) -> Result<(), NotEnoughItems>
     {
-        let index = self.get_index(item_type);
+        let available = self.quantity(item.item_type);
 
-        if index == usize::MAX {
-            return;
+        if available < item.quantity as u32 {
+            return Err(NotEnoughItems { item_type: item.item_type, available });
         }
 
-        self.items[index].quantity -= 1;
+        let mut left = item.quantity;
+
+        for slot in self.slots.iter_mut().rev() {
+            if left == 0 {
+                break;
+            }
 
-        if self.items[index].quantity == 0 {
-            self.items.remove(index);
+            if let Some(stack) = slot {
+                if stack.item_type == item.item_type {
+                    let moved = left.min(stack.quantity);
+                    stack.quantity -= moved;
+                    left -= moved;
+
+                    if stack.quantity == 0 {
+                        *slot = None;
+                    }
+                }
+            }
         }
+
+        Ok(())
     }
-}
 
-impl Default for InventoryItems
-{
-    fn default() -> Self {
-        Self {
-            items: Vec::<Item>::new()
+    /// Takes `item.quantity` out of one slot, or `None` if the slot no longer holds that much.
+    pub fn take_from_slot(&mut self, slot: usize, item: &Item) -> Option<Item> {
+        let stack = self.slots.get_mut(slot)?.as_mut()?;
+
+        if stack.item_type != item.item_type || stack.quantity < item.quantity {
+            return None;
+        }
+
+        stack.quantity -= item.quantity;
+
+        if stack.quantity == 0 {
+            self.slots[slot] = None;
         }
+
+        Some(item.clone())
+    }
+
+    /// Removes a single `item_type`, e.g. when it is placed or eaten.
+    pub fn tick_or_remove(&mut self, item_type: ItemTypes) -> Result<(), NotEnoughItems>
+    {
+        self.remove_item(&Item { item_type, quantity: 1 })
     }
 }
 
@@ -140,33 +260,34 @@ pub struct DropsItem
     pub item: Item
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+// Sprites and stats of each item are defined in `assets/items.ron`, see `registry`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum ItemTypes
 {
-    WallBlock = 0,
-    TurretBlock = 5,
-    TripMine = 12,
-    Fence = 14,
-    Wheat = 17,
-    WindMill = 15,
-    WoodFence = 20,
+    WallBlock,
+    TurretBlock,
+    TripMine,
+    Fence,
+    Wheat,
+    WindMill,
+    WoodFence,
 
     // From here on forward, items have not been added.
-    Chest = 25,
-    LandingPad = 26,
-    MiningRig = 27,
-    IronIngot = 28,
-    Coal = 29,
-    CraftingTable = 30,
-    Steak = 31
-}
-
-#[allow(dead_code)]
-impl ItemTypes {
-    pub fn sprite_index(&self) -> usize {
-        (*self) as usize
-    }
+    Chest,
+    LandingPad,
+    MiningRig,
+    IronIngot,
+    Coal,
+    CraftingTable,
+    Steak,
+    Wood,
+    Ammo,
+    Seeds,
+    Pistol,
+    Shotgun,
+    Rifle,
+    Shells
 }
 
 #[derive(Component)]
@@ -175,12 +296,50 @@ pub struct InteractableEntity
     pub interact_type: InteractionType
 }
 
+/// Hotbar slots point at slots of the player's `InventoryItems`, `active` is the one in hand.
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Hotbar
+{
+    pub slots: Vec<Option<usize>>,
+    pub active: usize
+}
+
+impl Hotbar
+{
+    /// Binds the hotbar to the first inventory slots.
+    pub fn new(size: usize) -> Self {
+        Hotbar {
+            slots: (0..size).map(Some).collect(),
+            active: 0
+        }
+    }
+
+    /// The inventory slot of the active hotbar slot and the item in it.
+    pub fn selected<'a>(&self, inv_items: &'a InventoryItems) -> Option<(usize, &'a Item)> {
+        let slot = self.slots.get(self.active).copied().flatten()?;
+
+        inv_items.slots.get(slot)?.as_ref().map(|item| (slot, item))
+    }
+
+    pub fn cycle(&mut self, steps: i32) {
+        let len = self.slots.len() as i32;
+        self.active = (self.active as i32 + steps).rem_euclid(len) as usize;
+    }
+}
+
 #[derive(Component)]
 pub struct InventoryItem
 {
-    pub item: Item
+    pub item: Item,
+    pub slot: usize
 }
 
+#[derive(Component)]
+pub struct CraftingMenu;
+
+#[derive(Component)]
+pub struct CraftingRecipe(pub usize);
+
 #[derive(Component)]
 pub struct ExternalInventory
 {
@@ -190,18 +349,63 @@ pub struct ExternalInventory
 #[derive(Component)]
 pub struct MiningRig(pub Timer);
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum OreKind
+{
+    Iron,
+    Coal
+}
+
+// Ore in the ground that a mining rig has to be placed on, see `miningrig`
+#[derive(Component)]
+pub struct Deposit
+{
+    pub ore: OreKind,
+    pub quantity: u32,
+    pub regen_timer: Timer
+}
+
+#[derive(Component)]
+pub struct BlockType(pub ItemTypes);
+
 #[derive(Component)]
 pub struct UiText;
 
+#[derive(Component)]
+pub struct SeedText;
+
 #[derive(Component)]
 pub struct StaticEntity;
 
 #[derive(Component)]
 pub struct Tree;
 
+#[derive(Component)]
+pub struct Ground;
+
 #[derive(Component)]
 pub struct Player;
 
+// The player controlled from this machine, see `network`
+#[derive(Component)]
+pub struct LocalPlayer;
+
+// The host is player 0, clients are numbered as they join
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct PlayerId(pub u8);
+
+// Actions and cursor of a player, from the controls of this machine or sent by a client
+#[derive(Component, Default)]
+pub struct PlayerInput
+{
+    pub actions: Input<Action>,
+    pub cursor: Vec3
+}
+
+// Zombies, blocks and dropped items of the host mirrored on a client, see `network`
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct NetId(pub u64);
+
 #[derive(Component)]
 pub struct Chest;
 
@@ -209,13 +413,47 @@ pub struct Chest;
 pub struct HealthBar;
 
 #[derive(Component)]
-pub struct Bullet;
+pub struct HungerBar;
 
 #[derive(Component)]
-pub struct Magazine(pub u8);
+pub struct NightOverlay;
+
+#[derive(Component)]
+pub struct ClockText;
+
+// Drains over time and is restored by eating, see `player`
+#[derive(Component)]
+pub struct Hunger
+{
+    pub val: f32
+}
+
+#[derive(Component)]
+pub struct Bullet
+{
+    pub damage: f32,
+    // Where it was fired from, bullets disappear after `weapons::BULLET_RANGE`
+    pub origin: Vec3
+}
+
+// Weapon the player is holding, see `weapons`
+#[derive(Component)]
+pub struct Weapon
+{
+    pub stats: weapons::WeaponStats,
+    pub magazine: u8,
+    // Reloading was asked for before the magazine is empty
+    pub reloading: bool,
+    pub fire_timer: Timer,
+    pub reload_timer: Timer
+}
+
+// Rounds left in the magazines of the weapons the player is not holding
+#[derive(Component, Default)]
+pub struct HolsteredAmmo(pub HashMap<ItemTypes, u8>);
 
 #[derive(Component)]
-pub struct ReloadTimer(pub Timer);
+pub struct WeaponText;
 
 #[derive(Component)]
 pub struct TempZombieDead(pub Timer);
@@ -226,6 +464,41 @@ pub struct TempTurretDestroyed(pub Timer);
 #[derive(Component)]
 pub struct Zombie;
 
+// Archetype the zombie was spawned from, see `zombie`
+#[derive(Component)]
+pub struct ZombieArchetype(pub zombie::ArchetypeDef);
+
+// Multipliers on the base stats of a zombie
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct ZombieStats
+{
+    pub health: f32,
+    pub speed: f32,
+    pub damage: f32
+}
+
+impl Default for ZombieStats
+{
+    fn default() -> Self {
+        ZombieStats {
+            health: 1.0,
+            speed: 1.0,
+            damage: 1.0
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SpitTimer(pub Timer);
+
+// Ranged attack of a spitter zombie
+#[derive(Component)]
+pub struct Spit
+{
+    pub damage: f32,
+    pub lifetime: Timer
+}
+
 #[derive(Component)]
 pub struct NewTargetTimer(pub Timer);
 
@@ -241,6 +514,24 @@ pub struct WoodFence;
 #[derive(Component)]
 pub struct WindMill;
 
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum PowerRole {
+    Generator,
+    Consumer
+}
+
+// Block that produces or draws power, see `power`
+#[derive(Component)]
+pub struct PowerNode
+{
+    pub role: PowerRole,
+    pub watts: f32,
+    // Index into `PowerGrid::networks`, None until the grid has been computed
+    pub network: Option<usize>,
+    // Fraction of the demand of the network that is met, between 0 and 1
+    pub satisfaction: f32
+}
+
 #[derive(Component)]
 pub struct WindMillBlade;
 
@@ -253,6 +544,14 @@ pub struct Wall;
 #[derive(Component)]
 pub struct Turret;
 
+// Rounds a turret has left to fire
+#[derive(Component)]
+pub struct AmmoStore
+{
+    pub rounds: u16,
+    pub capacity: u16
+}
+
 #[derive(Component)]
 pub struct TurretShootTimer(pub Timer);
 
@@ -278,4 +577,114 @@ pub struct Inventory;
 pub struct MainCamera;
 
 #[derive(Component)]
-pub struct TextScoreboard;
\ No newline at end of file
+pub struct TextScoreboard;
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn item(item_type: ItemTypes, quantity: u16) -> Item {
+        Item { item_type, quantity }
+    }
+
+    #[test]
+    fn add_tops_up_stacks_before_empty_slots() {
+        let mut inv = InventoryItems::new(3);
+        inv.slots[1] = Some(item(ItemTypes::Coal, 8));
+
+        assert_eq!(inv.add_item(item(ItemTypes::Coal, 5), 10), Ok(()));
+
+        assert_eq!(inv.slots[0], Some(item(ItemTypes::Coal, 3)));
+        assert_eq!(inv.slots[1], Some(item(ItemTypes::Coal, 10)));
+        assert_eq!(inv.slots[2], None);
+    }
+
+    #[test]
+    fn add_returns_what_does_not_fit() {
+        let mut inv = InventoryItems::new(2);
+        inv.slots[0] = Some(item(ItemTypes::Wheat, 1));
+
+        assert!(!inv.can_add(&item(ItemTypes::Coal, 15), 10));
+        assert_eq!(inv.add_item(item(ItemTypes::Coal, 15), 10), Err(item(ItemTypes::Coal, 5)));
+        assert_eq!(inv.quantity(ItemTypes::Coal), 10);
+        assert_eq!(inv.add_item(item(ItemTypes::Coal, 1), 10), Err(item(ItemTypes::Coal, 1)));
+    }
+
+    #[test]
+    fn unstackable_items_take_a_slot_each() {
+        let mut inv = InventoryItems::new(3);
+
+        assert_eq!(inv.add_item(item(ItemTypes::Pistol, 2), 1), Ok(()));
+
+        assert_eq!(inv.slots[0], Some(item(ItemTypes::Pistol, 1)));
+        assert_eq!(inv.slots[1], Some(item(ItemTypes::Pistol, 1)));
+        assert_eq!(inv.item_types(), vec![ItemTypes::Pistol]);
+        assert_eq!(inv.room_for(ItemTypes::Pistol, 1), 1);
+    }
+
+    #[test]
+    fn remove_takes_from_several_stacks() {
+        let mut inv = InventoryItems::new(3);
+        inv.add_item(item(ItemTypes::Ammo, 250), 120).unwrap();
+
+        assert_eq!(inv.remove_item(&item(ItemTypes::Ammo, 20)), Ok(()));
+
+        assert_eq!(inv.slots[0], Some(item(ItemTypes::Ammo, 120)));
+        assert_eq!(inv.slots[1], Some(item(ItemTypes::Ammo, 110)));
+        assert_eq!(inv.slots[2], None);
+        assert_eq!(inv.quantity(ItemTypes::Ammo), 230);
+    }
+
+    #[test]
+    fn failed_remove_leaves_the_inventory_alone() {
+        let mut inv = InventoryItems::new(2);
+        inv.add_item(item(ItemTypes::Seeds, 4), 99).unwrap();
+
+        assert_eq!(
+            inv.remove_item(&item(ItemTypes::Seeds, 5)),
+            Err(NotEnoughItems { item_type: ItemTypes::Seeds, available: 4 }));
+        assert_eq!(inv.slots[0], Some(item(ItemTypes::Seeds, 4)));
+
+        assert!(inv.tick_or_remove(ItemTypes::Wheat).is_err());
+    }
+
+    #[test]
+    fn emptied_slots_are_freed() {
+        let mut inv = InventoryItems::new(1);
+        inv.add_item(item(ItemTypes::Wheat, 1), 99).unwrap();
+
+        assert_eq!(inv.tick_or_remove(ItemTypes::Wheat), Ok(()));
+
+        assert_eq!(inv.slots[0], None);
+        assert!(!inv.has_item(ItemTypes::Wheat));
+    }
+
+    #[test]
+    fn stacks_can_hold_more_than_127() {
+        let mut inv = InventoryItems::new(1);
+
+        assert_eq!(inv.add_item(item(ItemTypes::Ammo, 500), 999), Ok(()));
+        assert_eq!(inv.quantity(ItemTypes::Ammo), 500);
+    }
+
+    #[test]
+    fn rearranged_stacks_have_the_same_contents() {
+        let mut inv = InventoryItems::new(3);
+        inv.slots[0] = Some(item(ItemTypes::Coal, 10));
+        inv.slots[1] = Some(item(ItemTypes::Wheat, 2));
+
+        let mut split = InventoryItems::new(3);
+        split.slots[0] = Some(item(ItemTypes::Wheat, 2));
+        split.slots[1] = Some(item(ItemTypes::Coal, 4));
+        split.slots[2] = Some(item(ItemTypes::Coal, 6));
+
+        assert!(inv.same_contents(&split));
+
+        split.slots[2] = Some(item(ItemTypes::Coal, 7));
+        assert!(!inv.same_contents(&split));
+
+        split.slots[2] = None;
+        assert!(!inv.same_contents(&split));
+        assert!(!inv.same_contents(&InventoryItems::new(4)));
+    }
+}
@@ -1,68 +1,72 @@
 use crate::{prelude::*, utils::entity_destruct};
 
-const ANIMAL_SPEED: f32 = 40.0;
-const ANIMAL_ACC: f32 = 200.0;
+use rand::rngs::StdRng;
+
 const STROLL_TIME: f32 = 1.0;
-const REACT_DISTANCE: f32 = 60.0;
 
 pub struct AnimalsPlugin;
 
 impl Animal
 {
-    pub fn set_random_stroll(&mut self) {
-        let mut rng = thread_rng();
-        let rand_vec = Vec3::new(rng.gen::<f32>()-0.5, rng.gen::<f32>()-0.5, 0.0).normalize();
-        self.stroll_direction = rand_vec;
-    }
-}
-
-impl Default for Animal
-{
-    fn default() -> Self {
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
         let mut animal = Animal {
             stroll_timer: Timer::from_seconds(STROLL_TIME, true),
             stroll_direction: Vec3::NAN
         };
 
-        animal.set_random_stroll();
+        animal.set_random_stroll(rng);
 
         animal
     }
+
+    pub fn set_random_stroll<R: Rng>(&mut self, rng: &mut R) {
+        let rand_vec = Vec3::new(rng.gen::<f32>()-0.5, rng.gen::<f32>()-0.5, 0.0).normalize();
+        self.stroll_direction = rand_vec;
+    }
 }
 
 impl Plugin for AnimalsPlugin
 {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(AppState::InGame)
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
         .with_system(animal_behaviour)
         .with_system(entities::mutual_repulsion::<Animal>));
     }
 }
 
+type Hostile = Or<(With<Zombie>, With<Player>)>;
+
 fn animal_behaviour(
     mut animal_query: Query<(&Transform, &mut Rigidbody, &mut Animal), With<Animal>>,
-    hostile_query: Query<&Transform, Or<(With<Zombie>, With<Player>)>>,
+    hostile_query: Query<&Transform, Hostile>,
     static_objs: Query<&Transform, With<StaticEntity>>,
-    time: Res<Time>
+    mut game_rng: ResMut<GameRng>,
+    clock: Res<Clock>,
+    time: Res<Time>,
+    balance: Res<Balance>
 ) {
+    let animals = &balance.animals;
     let static_vec_trans: Vec<&Transform> = static_objs.iter().collect();
 
     'outer: for (anim_trans, mut anim_rb, mut animal) in animal_query.iter_mut() {
         for hostile_trans in hostile_query.iter() {
             let vec_away: Vec3 = anim_trans.translation - hostile_trans.translation;
-            if vec_away.length() < REACT_DISTANCE {
-                if !is_hindered(&static_vec_trans, &anim_trans, &hostile_trans) {
-                    anim_rb.acc_clamped(vec_away.normalize(), ANIMAL_ACC, ANIMAL_SPEED, &time);
+            if vec_away.length() < animals.react_distance && !is_hindered(&static_vec_trans, &anim_trans, &hostile_trans) {
+                anim_rb.acc_clamped(vec_away.normalize(), animals.acceleration, animals.speed, &time);
 
-                    continue 'outer;
-                }
+                continue 'outer;
             }
         }
 
+        // Animals only flee at night
+        if clock.night {
+            continue;
+        }
+
         if !animal.stroll_timer.tick(time.delta()).just_finished() {
-            anim_rb.acc_clamped(animal.stroll_direction, ANIMAL_ACC, ANIMAL_SPEED/4.0, &time);
+            anim_rb.acc_clamped(animal.stroll_direction, animals.acceleration, animals.speed/4.0, &time);
         } else {
-            animal.set_random_stroll();
+            animal.set_random_stroll(&mut game_rng.gameplay);
         }
     }
 }
@@ -70,10 +74,9 @@ fn animal_behaviour(
 pub fn spawn_animal(
     commands: &mut Commands,
     spawn_pos: Vec3,
-    game_assets: &Res<GameAssets>
-) {
-    let mut rng = rand::thread_rng();
-
+    game_assets: &Res<GameAssets>,
+    rng: &mut StdRng
+) -> Entity {
     (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
@@ -92,9 +95,7 @@ pub fn spawn_animal(
             vy: 0.0,
             friction: true
         })
-        .insert(Animal {
-            ..Default::default()
-        })
+        .insert(Animal::new(rng))
         .insert(BoxCollider {
             size: Vec2::new(10.0, 10.0)
         })
@@ -105,5 +106,6 @@ pub fn spawn_animal(
             }
         })
         .insert(Attackable(TargetPriority::High))
-        .insert(EntityHealth{val: 20.0, func_destruct: entity_destruct});
+        .insert(EntityHealth{val: 20.0, func_destruct: entity_destruct})
+        .id()
 }
\ No newline at end of file
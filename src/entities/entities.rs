@@ -2,7 +2,7 @@ use crate::prelude::*;
 
 const ENTITY_DIST_REPULSION: f32 = 20.0;
 const REPULSION_ACC: f32 = 200.0;
-const DROPPED_ROTSPEED: f32 = 3.14/2.0;
+const DROPPED_ROTSPEED: f32 = std::f32::consts::FRAC_PI_2;
 
 pub struct EntitiesPlugin;
 
@@ -10,7 +10,7 @@ impl Plugin for EntitiesPlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_update(AppState::InGame)
+        app.add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
         .with_system(temp_entity_handler)
         .with_system(temp_turret_handler)
         .with_system(dropped_behaviour)
@@ -66,17 +66,19 @@ impl TempEntity for TempTurretDestroyed
 fn drop_items(
     mut commands: Commands,
     game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
     query: Query<(&EntityHealth, &Transform, &DropsItem), Without<Player>>
 ) {
     for (health, trans, drop_items) in query.iter()
     {
-        let mut cloned_trans = trans.clone();
+        let mut cloned_trans = *trans;
 
         cloned_trans.scale *= 0.5;
 
         if health.val <= 0.0 {
             spawn_dropped(&mut commands,
                 &game_assets,
+                &registry,
                 &cloned_trans,
                 drop_items.item.clone());
         }
@@ -86,25 +88,27 @@ fn drop_items(
 pub fn spawn_dropped(
     commands: &mut Commands,
     game_assets: &Res<GameAssets>,
+    registry: &Res<ItemRegistry>,
     spawn_trans: &Transform,
     item: Item
-) {
+) -> Entity {
     commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: item.item_type.sprite_index(),
+                index: registry.sprite_index(item.item_type),
                 ..Default::default()
             },
             ..Default::default()
         })
         .insert_bundle(TransformBundle{
-            local: spawn_trans.clone(),
+            local: *spawn_trans,
             ..Default::default()
         })
         .insert(CollectableItem {
             item: item.clone()
-        });
+        })
+        .id()
 }
 
 fn entity_health(
@@ -114,7 +118,7 @@ fn entity_health(
 ) {
     for (entity, health, trans) in query.iter() {
         if health.val <= 0.0 {
-            (health.func_destruct)(&mut commands, &entity, &game_assets, &trans);
+            (health.func_destruct)(&mut commands, &entity, &game_assets, trans);
         }
     }
 }
@@ -162,17 +166,17 @@ pub fn temp_turret_handler(
 
 pub fn mutual_repulsion<ENTITYTYPE: Component>(
     mut query: Query<(&Transform, &mut Rigidbody), With<ENTITYTYPE>>,
+    others_query: Query<&Transform, With<ENTITYTYPE>>,
+    hash: Res<SpatialHash>,
     time: Res<Time>  
 ) {
-    let all_pos: Vec<Vec3> = query.iter().map(|q| q.0.translation).collect();
-
     for (ent_trans, mut rb) in query.iter_mut() {
-        for pos in all_pos.iter() {
-            if ent_trans.translation == *pos {
+        for other_trans in hash.within_radius(ent_trans.translation, ENTITY_DIST_REPULSION).filter_map(|e| others_query.get(e).ok()) {
+            if ent_trans.translation == other_trans.translation {
                 continue;
             }
 
-            let vec_from = ent_trans.translation - *pos;
+            let vec_from = ent_trans.translation - other_trans.translation;
 
             if vec_from.length() <= ENTITY_DIST_REPULSION {
                 rb.vx += vec_from.normalize().x*REPULSION_ACC*time.delta_seconds();
@@ -1,15 +1,17 @@
 use bevy::prelude::*;
 
 pub mod animals;
+#[allow(clippy::module_inception)]
 pub mod entities;
 pub mod weapons;
 pub mod zombie;
+pub mod waves;
 
 pub use entities::{
     TempEntity,
 };
 
-pub use zombie::is_hindered;
+pub use zombie::{is_hindered, ZombieArchetypes};
 pub use entities::spawn_dropped;
 
 pub struct EntitiesPlugin;
@@ -20,6 +22,7 @@ impl Plugin for EntitiesPlugin
         app.add_plugin(weapons::WeaponsPlugin)
         .add_plugin(animals::AnimalsPlugin)
         .add_plugin(zombie::ZombiePlugin)
+        .add_plugin(waves::WavePlugin)
         .add_plugin(entities::EntitiesPlugin);
     }
 }
\ No newline at end of file
@@ -0,0 +1,299 @@
+//! The wave director.
+//!
+//! Every wave starts with a build phase without zombies, followed by an attack phase in
+//! which the zombies of the wave are spawned one by one. The wave is cleared once all of
+//! them are dead, or after `max_attack_time` so that a stray zombie can not stall the game.
+//! The waves are defined in `assets/waves.ron`:
+//!
+//! ```text
+//! (
+//!     groups: [
+//!         (
+//!             kind: "normal",
+//!             count: 40,
+//!             directions: [North, East],
+//!             stats: (health: 1.0, speed: 1.0, damage: 1.0),
+//!         ),
+//!     ],
+//! ),
+//! ```
+//!
+//! The `kind` of a group is the name of a zombie archetype in `assets/zombies.ron`. Waves
+//! after the last definition repeat it, with the counts and stats multiplied by `escalation`
+//! once per extra wave.
+
+use std::{f32::consts::PI, fs};
+
+use rand::{rngs::StdRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub const WAVES_PATH: &str = "assets/waves.ron";
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin
+{
+    fn build(&self, app: &mut App) {
+        // Added after the `ZombiePlugin`, which loads the archetypes the groups refer to
+        let waves = read_waves(WAVES_PATH, app.world.resource::<ZombieArchetypes>()).unwrap_or_else(|e| panic!("{}", e));
+
+        app.insert_resource(CurrentWave::new(&waves))
+        .insert_resource(waves)
+        .add_system_set(SystemSet::on_enter(AppState::GameSetup)
+            .with_system(wave_setup))
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(wave_director.before(wave_spawner))
+            .with_system(wave_spawner));
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum SpawnDirection
+{
+    Any,
+    North,
+    East,
+    South,
+    West
+}
+
+impl SpawnDirection
+{
+    pub fn random_angle(&self, rng: &mut StdRng) -> f32 {
+        let center = match self {
+            SpawnDirection::Any => return rng.gen::<f32>() * 2.0 * PI,
+            SpawnDirection::North => PI / 2.0,
+            SpawnDirection::East => 0.0,
+            SpawnDirection::South => 3.0 * PI / 2.0,
+            SpawnDirection::West => PI
+        };
+
+        center + (rng.gen::<f32>() - 0.5) * PI / 2.0
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct GroupDef
+{
+    // Name of the zombie archetype
+    pub kind: String,
+    pub count: u32,
+    pub directions: Vec<SpawnDirection>,
+    pub stats: ZombieStats
+}
+
+#[derive(Deserialize)]
+pub struct WaveDef
+{
+    pub groups: Vec<GroupDef>
+}
+
+#[derive(Deserialize)]
+pub struct Escalation
+{
+    pub count: f32,
+    pub stats: ZombieStats
+}
+
+#[derive(Deserialize)]
+pub struct Waves
+{
+    pub build_time: f32,
+    pub max_attack_time: f32,
+    pub spawn_interval: f32,
+    pub waves: Vec<WaveDef>,
+    pub escalation: Escalation
+}
+
+impl Waves
+{
+    /// The groups of wave `number`, counting from 1.
+    pub fn composition(&self, number: u32) -> Vec<GroupDef> {
+        let index = (number.max(1) as usize - 1).min(self.waves.len() - 1);
+        let extra = (number as usize).saturating_sub(self.waves.len()) as i32;
+
+        self.waves[index].groups
+            .iter()
+            .map(|group| GroupDef {
+                count: (group.count as f32 * self.escalation.count.powi(extra)).ceil() as u32,
+                stats: ZombieStats {
+                    health: group.stats.health * self.escalation.stats.health.powi(extra),
+                    speed: group.stats.speed * self.escalation.stats.speed.powi(extra),
+                    damage: group.stats.damage * self.escalation.stats.damage.powi(extra)
+                },
+                ..group.clone()
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingSpawn
+{
+    pub kind: String,
+    pub direction: SpawnDirection,
+    pub stats: ZombieStats
+}
+
+impl CurrentWave
+{
+    pub fn new(waves: &Waves) -> Self {
+        CurrentWave {
+            number: 1,
+            phase: WavePhase::Build,
+            build_timer: Timer::from_seconds(waves.build_time, false),
+            attack_timer: Timer::from_seconds(waves.max_attack_time, false),
+            spawn_timer: Timer::from_seconds(waves.spawn_interval, true),
+            pending: Vec::new()
+        }
+    }
+}
+
+pub fn read_waves(path: &str, archetypes: &ZombieArchetypes) -> Result<Waves, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let waves: Waves = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    let mut errors: Vec<String> = Vec::new();
+
+    let valid_stats = |stats: &ZombieStats| stats.health > 0.0 && stats.speed > 0.0 && stats.damage > 0.0;
+
+    if waves.build_time < 0.0 {
+        errors.push("build_time can not be negative".to_string());
+    }
+
+    if waves.max_attack_time <= 0.0 {
+        errors.push("max_attack_time has to be positive".to_string());
+    }
+
+    if waves.spawn_interval <= 0.0 {
+        errors.push("spawn_interval has to be positive".to_string());
+    }
+
+    if waves.waves.is_empty() {
+        errors.push("needs at least one wave".to_string());
+    }
+
+    if waves.escalation.count < 1.0 || !valid_stats(&waves.escalation.stats) {
+        errors.push("escalation needs a count of at least 1 and positive stats".to_string());
+    }
+
+    for (i, wave) in waves.waves.iter().enumerate() {
+        let mut error = |msg: &str| errors.push(format!("Wave {}: {}", i + 1, msg));
+
+        if wave.groups.iter().map(|group| group.count).sum::<u32>() == 0 {
+            error("needs at least one zombie");
+        }
+
+        for group in wave.groups.iter() {
+            if !archetypes.contains(&group.kind) {
+                error(&format!("{} is not a zombie archetype", group.kind));
+            }
+
+            if group.directions.is_empty() {
+                error(&format!("{} group needs at least one direction", group.kind));
+            }
+
+            if !valid_stats(&group.stats) {
+                error(&format!("{} group needs positive stats", group.kind));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(waves)
+    } else {
+        Err(format!("Invalid waves in {}:\n{}", path, errors.join("\n")))
+    }
+}
+
+fn wave_setup(
+    mut commands: Commands,
+    waves: Res<Waves>
+) {
+    commands.insert_resource(CurrentWave::new(&waves));
+}
+
+fn wave_director(
+    mut wave: ResMut<CurrentWave>,
+    waves: Res<Waves>,
+    zombie_query: Query<(), With<Zombie>>,
+    mut game_rng: ResMut<GameRng>,
+    time: Res<Time>,
+    mut started_writer: EventWriter<WaveStarted>,
+    mut cleared_writer: EventWriter<WaveCleared>
+) {
+    match wave.phase {
+        WavePhase::Build => {
+            if wave.build_timer.tick(time.delta()).just_finished() {
+                let rng = &mut game_rng.waves;
+                let mut pending: Vec<PendingSpawn> = Vec::new();
+
+                for group in waves.composition(wave.number) {
+                    for i in 0..group.count as usize {
+                        pending.push(PendingSpawn {
+                            kind: group.kind.clone(),
+                            direction: group.directions[i % group.directions.len()],
+                            stats: group.stats
+                        });
+                    }
+                }
+
+                pending.shuffle(rng);
+
+                wave.pending = pending;
+                wave.phase = WavePhase::Attack;
+                wave.attack_timer.reset();
+                wave.spawn_timer.reset();
+
+                println!("Wave {} started!", wave.number);
+                started_writer.send(WaveStarted { number: wave.number });
+            }
+        },
+        WavePhase::Attack => {
+            let timed_out = wave.attack_timer.tick(time.delta()).just_finished();
+
+            if (wave.pending.is_empty() && zombie_query.is_empty()) || timed_out {
+                println!("Wave {} cleared!", wave.number);
+                cleared_writer.send(WaveCleared { number: wave.number });
+
+                wave.number += 1;
+                wave.phase = WavePhase::Build;
+                wave.build_timer.reset();
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn wave_spawner(
+    mut commands: Commands,
+    mut wave: ResMut<CurrentWave>,
+    time: Res<Time>,
+    clock: Res<Clock>,
+    cycle: Res<DayCycle>,
+    game_assets: Res<GameAssets>,
+    archetypes: Res<ZombieArchetypes>,
+    mut game_rng: ResMut<GameRng>,
+    balance: Res<Balance>,
+    network: Res<Network>
+) {
+    // A client gets its zombies from the host
+    if network.is_client() || wave.phase != WavePhase::Attack || wave.pending.is_empty() {
+        return;
+    }
+
+    // Zombies pour in faster at night
+    if wave.spawn_timer.tick(time.delta().mul_f32(cycle.spawn_rate(clock.night))).just_finished() {
+        let rng = &mut game_rng.waves;
+        let spawn = wave.pending.pop().unwrap();
+
+        let angle = spawn.direction.random_angle(rng);
+        let start_dist = balance.waves.start_distance;
+        let start_pos = Vec3::new(angle.cos() * start_dist, angle.sin() * start_dist, 2.0);
+
+        zombie::spawn_zombie_kind(&mut commands, &archetypes, &spawn.kind, start_pos, &game_assets, spawn.stats, rng);
+    }
+}
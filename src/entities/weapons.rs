@@ -1,9 +1,35 @@
-use crate::prelude::*;
+//! Guns of the player and the bullets of the player and the turrets.
+//!
+//! Every weapon is an item with an entry in `assets/weapons.ron`:
+//!
+//! ```text
+//! (
+//!     item: Shotgun,
+//!     ammo: Shells,
+//!     fire_interval: 0.8,
+//!     spread: 0.5,
+//!     pellets: 6,
+//!     damage: 20.0,
+//!     magazine: 6,
+//!     reload_time: 2.5,
+//!     bullet_speed: 400.0,
+//! ),
+//! ```
+//!
+//! The player holds one of the weapons in the inventory and switches to the next one with Q.
+//! Weapons keep the rounds left in their magazine while they are holstered. Reloading, when the
+//! magazine is empty or on R, takes the `ammo` item of the weapon from the inventory, and a
+//! weapon can not fire once both are used up.
 
-pub const BLLT_SPEED: f32 = 500.0;
-pub const BLLT_RANDOM: f32 = 0.1;
-pub const MAGAZINE_SIZE: u8 = 30;
-pub const BLLT_DMG: f32 = 33.0;
+use std::{collections::HashMap, fs};
+
+use serde::Deserialize;
+
+use crate::{prelude::*, utils::destruct_cleanup};
+
+pub const WEAPONS_PATH: &str = "assets/weapons.ron";
+// Distance in pixels a bullet flies before it disappears
+pub const BULLET_RANGE: f32 = 1000.0;
 
 pub struct WeaponsPlugin;
 
@@ -11,23 +37,171 @@ impl Plugin for WeaponsPlugin
 {
     fn build(&self, app: &mut App)
     {
+        let weapons = read_weapons(WEAPONS_PATH).unwrap_or_else(|e| panic!("{}", e));
+
         app
-        .insert_resource(GunTimer(Timer::from_seconds(0.15, true)))
-        .add_system_set(SystemSet::on_update(AppState::InGame)
+        .insert_resource(weapons)
+        .add_system_set(SystemSet::on_enter(AppState::GameSetup)
+            .with_system(weapons_setup))
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
             .with_system(shot_bullets)
-            .with_system(shoot)
-        );
+            .with_system(switch_weapon)
+            .with_system(shoot.after(switch_weapon))
+            .with_system(weapon_text.after(shoot))
+        )
+        .add_system_set(SystemSet::on_enter(AppState::GameDestruct)
+            .with_system(destruct_cleanup::<WeaponText>));
     }
 }
 
+#[derive(Deserialize)]
+struct WeaponsFile
+{
+    weapons: Vec<WeaponStats>
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct WeaponStats
+{
+    pub item: ItemTypes,
+    // Item the magazine is reloaded with
+    pub ammo: ItemTypes,
+    // Seconds between two shots
+    pub fire_interval: f32,
+    // Width in radians of the cone the bullets are spread over
+    pub spread: f32,
+    // Bullets fired per shot
+    pub pellets: u8,
+    // Damage of every bullet
+    pub damage: f32,
+    pub magazine: u8,
+    pub reload_time: f32,
+    pub bullet_speed: f32
+}
+
+pub struct Weapons
+{
+    weapons: HashMap<ItemTypes, WeaponStats>
+}
+
+impl Weapons
+{
+    pub fn get(&self, item_type: ItemTypes) -> Option<&WeaponStats> {
+        self.weapons.get(&item_type)
+    }
+
+    pub fn is_weapon(&self, item_type: ItemTypes) -> bool {
+        self.weapons.contains_key(&item_type)
+    }
+}
+
+pub fn read_weapons(path: &str) -> Result<Weapons, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let file: WeaponsFile = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    let mut errors: Vec<String> = Vec::new();
+    let mut weapons: HashMap<ItemTypes, WeaponStats> = HashMap::new();
+
+    for stats in file.weapons {
+        if weapons.contains_key(&stats.item) {
+            errors.push(format!("{:?} is defined more than once", stats.item));
+            continue;
+        }
+
+        if stats.fire_interval <= 0.0 || stats.reload_time <= 0.0 {
+            errors.push(format!("{:?}: fire_interval and reload_time have to be positive", stats.item));
+        }
+
+        if weapons.contains_key(&stats.ammo) {
+            errors.push(format!("{:?}: a weapon can not be used as ammo", stats.item));
+        }
+
+        if stats.pellets == 0 || stats.magazine == 0 {
+            errors.push(format!("{:?}: pellets and magazine have to be at least 1", stats.item));
+        }
+
+        if stats.spread < 0.0 || stats.damage < 0.0 || stats.bullet_speed <= 0.0 {
+            errors.push(format!("{:?}: spread and damage can not be negative and bullet_speed has to be positive", stats.item));
+        }
+
+        weapons.insert(stats.item, stats);
+    }
+
+    if errors.is_empty() {
+        Ok(Weapons { weapons })
+    } else {
+        Err(format!("Invalid weapons in {}:\n{}", path, errors.join("\n")))
+    }
+}
+
+impl Weapon
+{
+    pub fn new(stats: WeaponStats, magazine: u8) -> Self {
+        let mut fire_timer = Timer::from_seconds(stats.fire_interval, false);
+        // Ready to fire right away
+        fire_timer.tick(fire_timer.duration());
+
+        Weapon {
+            stats,
+            magazine: magazine.min(stats.magazine),
+            reloading: false,
+            fire_timer,
+            reload_timer: Timer::from_seconds(stats.reload_time, false)
+        }
+    }
+
+    pub fn needs_reload(&self) -> bool {
+        self.magazine == 0 || self.reloading
+    }
+
+    /// e.g. "Rifle  12/30  +90", or "Rifle  reloading"
+    pub fn display(&self, registry: &ItemRegistry, inv_items: &InventoryItems) -> String {
+        let name = &registry.get(self.stats.item).name;
+        let spare = inv_items.quantity(self.stats.ammo);
+
+        if self.needs_reload() && spare > 0 {
+            format!("{}  reloading", name)
+        } else if self.magazine == 0 {
+            format!("{}  no {}", name, registry.get(self.stats.ammo).name.to_lowercase())
+        } else {
+            format!("{}  {}/{}  +{}", name, self.magazine, self.stats.magazine, spare)
+        }
+    }
+}
+
+fn weapons_setup(
+    mut commands: Commands,
+    inventory_asset: Res<InventoryAsset>
+) {
+    let text_style = TextStyle {
+        font: inventory_asset.font.clone(),
+        font_size: 16.0,
+        color: Color::WHITE
+    };
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::from_section("", text_style)
+                .with_alignment(TextAlignment::CENTER_LEFT),
+            transform: Transform::from_xyz(-400.0, 252.0, 10.0),
+            ..Default::default()
+        })
+        .insert(WeaponText);
+}
+
+// Bullets pass through the players and their own defenses
+type BulletTarget = (Without<Bullet>, Without<Player>, Without<Turret>, Without<Fence>, Without<Wheat>);
+
 fn shot_bullets(
-    mut query: Query<(Entity, &mut Transform), With<Bullet>>,
+    mut query: Query<(Entity, &mut Transform, &Bullet)>,
     mut commands: Commands,
     mut event_reader: EventReader<CollisionEvent>,
-    mut health_query: Query<(&Transform, &mut EntityHealth), (Without<Bullet>, Without<Player>, Without<Turret>, Without<Fence>, Without<Wheat>)>
+    mut health_query: Query<(&Transform, &mut EntityHealth), BulletTarget>,
+    hash: Res<SpatialHash>
 ) {
-    let bullet_ents: Vec<Entity> = query.iter().map(|(ent, _trans)| ent).collect();
-    
+    let bullet_ents: Vec<Entity> = query.iter().map(|(ent, _trans, _bullet)| ent).collect();
+
     for coll in event_reader.iter() {
         if bullet_ents.contains(&coll.ent_a) {
             commands.entity(coll.ent_a).despawn();
@@ -36,56 +210,132 @@ fn shot_bullets(
 
     // Check if a bullet is near an entity containing the component EntityHealth
     // If so, despawn the bullet and remove health from the entity.
-    'outer: for (b_ent, b_trans) in query.iter() {
-        for (helt_trans, mut helt_health) in health_query.iter_mut() {
+    'outer: for (b_ent, b_trans, bullet) in query.iter() {
+        for entity in hash.within_radius(b_trans.translation, 20.0) {
+            let (helt_trans, mut helt_health) = match health_query.get_mut(entity) {
+                Ok(health) => health,
+                Err(_) => continue
+            };
+
             if (b_trans.translation - helt_trans.translation).length() < 20.0 {
                 commands.entity(b_ent).despawn();
-                helt_health.val -= BLLT_DMG;
+                helt_health.val -= bullet.damage;
                 continue 'outer;
             }
         }
     }
 
-    for (bullet_entity, bullet) in query.iter_mut() {
-        if dist_between(Vec3::new(0.0,0.0,0.0), bullet.translation) > 1000.0 {
+    for (bullet_entity, trans, bullet) in query.iter_mut() {
+        if dist_between(bullet.origin, trans.translation) > BULLET_RANGE {
             commands.entity(bullet_entity).despawn();
             continue;
         }
     }
 }
 
+type PlayerWeapons<'a> = (Entity, &'a PlayerInput, &'a InventoryItems, Option<&'a Weapon>, &'a mut HolsteredAmmo);
+
+/// Equips the next weapon in the inventory on Q. A player without a weapon picks up the
+/// first one in the inventory, and a weapon that leaves the inventory is dropped from the hands.
+fn switch_weapon(
+    mut commands: Commands,
+    mut player_query: Query<PlayerWeapons, With<Player>>,
+    weapons: Res<Weapons>
+) {
+    for (player_entity, input, inv_items, weapon, mut holstered) in player_query.iter_mut() {
+        let owned: Vec<ItemTypes> = inv_items.item_types().into_iter()
+            .filter(|item_type| weapons.is_weapon(*item_type))
+            .collect();
+
+        let held = weapon.map(|weapon| weapon.stats.item);
+
+        let next = match held {
+            Some(item_type) if !owned.contains(&item_type) => owned.first().copied(),
+            Some(item_type) if input.actions.just_pressed(Action::SwitchWeapon) => {
+                let index = owned.iter().position(|owned_type| *owned_type == item_type).unwrap();
+                Some(owned[(index + 1) % owned.len()])
+            },
+            Some(_) => continue,
+            None => owned.first().copied()
+        };
+
+        if next == held {
+            continue;
+        }
+
+        if let Some(weapon) = weapon {
+            holstered.0.insert(weapon.stats.item, weapon.magazine);
+        }
+
+        match next {
+            Some(item_type) => {
+                let stats = *weapons.get(item_type).unwrap();
+                let magazine = holstered.0.remove(&item_type).unwrap_or(stats.magazine);
+
+                commands.entity(player_entity).insert(Weapon::new(stats, magazine));
+            },
+            None => {
+                commands.entity(player_entity).remove::<Weapon>();
+            }
+        }
+    }
+}
+
+/// Fires at the cursor of each player, which is sent along with the actions of a client.
 fn shoot(
     mut commands: Commands,
-    mut gun_timer: ResMut<GunTimer>,
-    mouseloc: Res<MouseLoc>,
-    mut query: Query<(&Transform, &mut ReloadTimer, &mut Magazine), With<Player>>,
-    btn: Res<Input<MouseButton>>,
+    mut query: Query<(&Transform, &PlayerInput, &mut Weapon, &mut InventoryItems), With<Player>>,
+    mut game_rng: ResMut<GameRng>,
     time: Res<Time>
 ) {
+    for (player, input, mut weapon, mut inv_items) in query.iter_mut() {
+        weapon.fire_timer.tick(time.delta());
 
-    if !gun_timer.0.just_finished() {
-        gun_timer.0.tick(time.delta());
-    }
+        if !weapon.needs_reload() {
+            //Spawn bullets while the fire button is held
+            if input.actions.pressed(Action::Fire) && weapon.fire_timer.finished() {
+                let angle = angle_between(player.translation, input.cursor);
+                let stats = weapon.stats;
 
-    let (player, mut reload_timer, mut magazine) = query.single_mut();
-    
-    if magazine.0 != 0 {
-        //Spawn bullet when left mousebutton is clicked
-        if btn.pressed(MouseButton::Left) && gun_timer.0.just_finished(){
-            let angle = angle_between(player.translation, Vec3::new(mouseloc.x, mouseloc.y, 0.0));
+                for _ in 0..stats.pellets {
+                    let rand_angle: f32 = (game_rng.gameplay.gen::<f32>() - 0.5) * stats.spread;
 
-            let player_pos = player.translation;
+                    spawn_bullet(&mut commands, player.translation, angle, rand_angle, stats.bullet_speed, stats.damage);
+                }
 
-            let mut rng = rand::thread_rng();
-            let rand_angle: f32 = (rng.gen::<f32>() - 0.5) * BLLT_RANDOM;
+                weapon.fire_timer.reset();
+                weapon.magazine -= 1;
+            }
+        } else {
+            let spare = inv_items.quantity(weapon.stats.ammo);
 
-            spawn_bullet(&mut commands, player_pos, angle, rand_angle);
+            if spare == 0 {
+                // Keep firing what is left in the magazine
+                weapon.reloading = false;
+                weapon.reload_timer.reset();
+            } else if weapon.reload_timer.tick(time.delta()).just_finished() {
+                let rounds = ((weapon.stats.magazine - weapon.magazine) as u32).min(spare) as u8;
 
-            gun_timer.0.tick(time.delta()); // Resume countdown!
-            magazine.0 -= 1;
+                inv_items.remove_item(&Item { item_type: weapon.stats.ammo, quantity: rounds as u16 }).unwrap();
+                weapon.magazine += rounds;
+                weapon.reloading = false;
+                weapon.reload_timer.reset();
+            }
         }
-    } else if reload_timer.0.tick(time.delta()).just_finished() {
-        magazine.0 = MAGAZINE_SIZE;
+    }
+}
+
+fn weapon_text(
+    mut text_query: Query<&mut Text, With<WeaponText>>,
+    weapon_query: Query<(&Weapon, &InventoryItems), With<LocalPlayer>>,
+    registry: Res<ItemRegistry>
+) {
+    let display = weapon_query.get_single()
+        .map(|(weapon, inv_items)| weapon.display(&registry, inv_items))
+        .unwrap_or_default();
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = display.clone();
     }
 }
 
@@ -93,7 +343,9 @@ pub fn spawn_bullet(
     commands: &mut Commands,
     spawn_at: Vec3,
     angle: f32,
-    rand_angle: f32
+    rand_angle: f32,
+    speed: f32,
+    damage: f32
 ) {
     (*commands)
         .spawn_bundle(SpriteBundle {
@@ -108,14 +360,14 @@ pub fn spawn_bullet(
             local: Transform::from_translation(spawn_at),
             ..Default::default()
         })
-        .insert(Bullet)
+        .insert(Bullet { damage, origin: spawn_at })
         .insert(Rigidbody{
-            vx: (angle + rand_angle).cos()*BLLT_SPEED,
-            vy: (angle + rand_angle).sin()*BLLT_SPEED,
+            vx: (angle + rand_angle).cos()*speed,
+            vy: (angle + rand_angle).sin()*speed,
             friction: false,
         })
         .insert(BoxCollider{
             size: Vec2::new(5.0, 5.0)
         });
-        
-}
\ No newline at end of file
+
+}
@@ -1,113 +1,239 @@
+//! Zombies and their archetypes.
+//!
+//! Every kind of zombie is an archetype with an entry in `assets/zombies.ron`:
+//!
+//! ```text
+//! (
+//!     name: "spitter",
+//!     sprite_index: 4,
+//!     color: (0.5, 1.0, 0.4),
+//!     scale: 1.0,
+//!     collider: (10.0, 10.0),
+//!     health: 18.0,
+//!     speed: 140.0,
+//!     damage: 5.0,
+//!     attack_interval: 0.4,
+//!     special: Spit(range: 160.0, interval: 1.5, damage: 8.0, speed: 220.0),
+//! ),
+//! ```
+//!
+//! Zombies are spawned by the name of their archetype, e.g. from the waves. The `special`
+//! behaviour is one of `None`, `Explode` (blows up like a trip mine next to a block or the
+//! player), `Spit` (spits at the player from a distance) and `TargetStructures` (goes for
+//! the nearest block instead of the player).
+
+use std::{collections::HashMap, fs};
+
+use rand::rngs::StdRng;
+use serde::Deserialize;
+
 use crate::prelude::*;
 
-use std::f32::consts::PI;
+pub const ZOMBIES_PATH: &str = "assets/zombies.ron";
 
 pub struct ZombiePlugin;
 
-const ZOMBIE_ACC: f32 = 600.0;
-const ZOMB_ANGRY_SPEED: f32 = 180.0;
-const ZOMB_IDLE_SPEED: f32 = 30.0;
-
-const ATTACK_DMG: f32 = 10.0;
-const START_DIST: f32 = 900.0;
-const ATTACK_TIME: f32 = 0.3;
 const INIT_TARGET_RAD: f32 = 30.0;
+const SPIT_HIT_DIST: f32 = 10.0;
 
 impl Plugin for ZombiePlugin
 {
     fn build(&self, app: &mut App)
     {
+        let archetypes = read_archetypes(ZOMBIES_PATH).unwrap_or_else(|e| panic!("{}", e));
+
         app
-        .insert_resource(ZombieTimer(Timer::from_seconds(0.3, true)))
-        .insert_resource(ZombieTimeoutTimer(Timer::from_seconds(120.0, true)))
-        .insert_resource(ZombieLevelTimer(Timer::from_seconds(60.0, true)))
-        .add_system_set(SystemSet::on_update(AppState::InGame) 
+        .insert_resource(archetypes)
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
             .with_system(zombie_ai)
-            .with_system(zombie_spawner)
             .with_system(attack_health_entities)
             .with_system(enemy_pathfind)
             .with_system(enemy_entity_pathfind)
+            .with_system(target_structures.after(enemy_entity_pathfind))
             .with_system(entities::mutual_repulsion::<Zombie>)
-            .with_system(random_new_target));
+            .with_system(random_new_target)
+            .with_system(exploder_detonate)
+            .with_system(spitter_attack)
+            .with_system(spit_behaviour));
     }
 }
 
-pub fn zombie_ai(
-    mut query: Query<(&Transform, &mut Rigidbody, &Pathfinder), With<Zombie>>,
-    time: Res<Time>
-) {
-    for (zombie, mut rb, pf) in query.iter_mut() {
-        let dist = zombie.translation.distance(pf.target);
-        let angle = angle_between(zombie.translation, pf.target);
-
-        if dist > 20.0 {
-            rb.vx += ZOMBIE_ACC*angle.cos()*time.delta_seconds();
-            rb.vy += ZOMBIE_ACC*angle.sin()*time.delta_seconds();
-            if pf.target_entity {
-                rb.vx = rb.vx.clamp(-ZOMB_ANGRY_SPEED, ZOMB_ANGRY_SPEED);
-                rb.vy = rb.vy.clamp(-ZOMB_ANGRY_SPEED, ZOMB_ANGRY_SPEED);
-            } else {
-                rb.vx = rb.vx.clamp(-ZOMB_IDLE_SPEED, ZOMB_IDLE_SPEED);
-                rb.vy = rb.vy.clamp(-ZOMB_IDLE_SPEED, ZOMB_IDLE_SPEED);
-            }
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub enum Special
+{
+    None,
+    Explode { trigger_dist: f32 },
+    Spit { range: f32, interval: f32, damage: f32, speed: f32 },
+    TargetStructures
+}
+
+#[derive(Deserialize)]
+struct ZombiesFile
+{
+    zombies: Vec<ArchetypeDef>
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ArchetypeDef
+{
+    pub name: String,
+    pub sprite_index: usize,
+    // Tint of the sprite, to tell archetypes sharing a sprite apart
+    pub color: (f32, f32, f32),
+    pub scale: f32,
+    pub collider: (f32, f32),
+    pub health: f32,
+    // Top speed when going for a target, multiplied by the `ZombieStats` of the zombie
+    pub speed: f32,
+    // Damage of every melee attack
+    pub damage: f32,
+    // Seconds between two melee attacks
+    pub attack_interval: f32,
+    pub special: Special
+}
+
+impl ArchetypeDef
+{
+    // How close the zombie walks up to its target
+    fn stop_dist(&self, targets_entity: bool) -> f32 {
+        match self.special {
+            // Spitters keep their distance from the player
+            Special::Spit { range, .. } if !targets_entity => range * 0.75,
+            _ => 20.0
         }
     }
 }
 
-fn zombie_spawner(
-    mut commands: Commands,
-    mut spawn_timer: ResMut<ZombieTimer>,
-    mut timeout_timer: ResMut<ZombieTimeoutTimer>,
-    mut level_timer: ResMut<ZombieLevelTimer>,
+pub struct ZombieArchetypes
+{
+    archetypes: HashMap<String, ArchetypeDef>
+}
+
+impl ZombieArchetypes
+{
+    pub fn get(&self, name: &str) -> Option<&ArchetypeDef> {
+        self.archetypes.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.archetypes.contains_key(name)
+    }
+}
+
+pub fn read_archetypes(path: &str) -> Result<ZombieArchetypes, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let file: ZombiesFile = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    let mut errors: Vec<String> = Vec::new();
+    let mut archetypes: HashMap<String, ArchetypeDef> = HashMap::new();
+
+    for def in file.zombies {
+        let mut error = |msg: &str| errors.push(format!("{}: {}", def.name, msg));
+
+        if archetypes.contains_key(&def.name) {
+            error("is defined more than once");
+            continue;
+        }
+
+        if def.name.is_empty() {
+            error("name is empty");
+        }
+
+        if def.sprite_index >= crate::SHEET_COLUMNS * crate::SHEET_ROWS {
+            error("sprite_index is outside of Sheet.png");
+        }
+
+        if def.scale <= 0.0 || def.collider.0 <= 0.0 || def.collider.1 <= 0.0 {
+            error("scale and collider have to be positive");
+        }
+
+        if def.health <= 0.0 || def.speed <= 0.0 || def.attack_interval <= 0.0 || def.damage < 0.0 {
+            error("health, speed and attack_interval have to be positive and damage can not be negative");
+        }
+
+        match def.special {
+            Special::Explode { trigger_dist } if trigger_dist <= 0.0 => error("trigger_dist has to be positive"),
+            Special::Spit { range, interval, damage, speed } if range <= 0.0 || interval <= 0.0 || damage < 0.0 || speed <= 0.0 =>
+                error("spit range, interval and speed have to be positive and damage can not be negative"),
+            _ => {}
+        }
+
+        archetypes.insert(def.name.clone(), def);
+    }
+
+    if archetypes.is_empty() {
+        errors.push("needs at least one zombie".to_string());
+    }
+
+    if errors.is_empty() {
+        Ok(ZombieArchetypes { archetypes })
+    } else {
+        Err(format!("Invalid zombies in {}:\n{}", path, errors.join("\n")))
+    }
+}
+
+pub fn zombie_ai(
+    mut query: Query<(&Transform, &mut Rigidbody, &Pathfinder, &ZombieArchetype, &ZombieStats), With<Zombie>>,
     time: Res<Time>,
-    game_assets: Res<GameAssets>
+    balance: Res<Balance>
 ) {
-    if timeout_timer.0.just_finished() {
-        if !level_timer.0.tick(time.delta()).just_finished() {
-            if spawn_timer.0.tick(time.delta()).just_finished() {
-                let mut rng = rand::thread_rng();
-                let angle: f32 = rng.gen::<f32>() * 2.0 * PI;
-
-                let start_pos = Vec3::new(angle.cos() * START_DIST, angle.sin() * START_DIST, 2.0);
-
-                if rng.gen::<f32>() > 0.05 {
-                    spawn_zombie(&mut commands, start_pos, &game_assets);
-                } else {
-                    spawn_chungus_zombie(&mut commands, start_pos, &game_assets);
-                }
-            }
-        } else {
-            timeout_timer.0.tick(time.delta());
+    let acc = balance.zombies.acceleration;
+
+    for (zombie, mut rb, pf, archetype, stats) in query.iter_mut() {
+        let dist = zombie.translation.distance(pf.target);
+        let angle = angle_between(zombie.translation, pf.waypoint.unwrap_or(pf.target));
+
+        if dist > archetype.0.stop_dist(pf.target_entity) {
+            rb.vx += acc*angle.cos()*time.delta_seconds();
+            rb.vy += acc*angle.sin()*time.delta_seconds();
+            let max_speed = if pf.target_entity { archetype.0.speed } else { balance.zombies.idle_speed } * stats.speed;
+
+            rb.vx = rb.vx.clamp(-max_speed, max_speed);
+            rb.vy = rb.vy.clamp(-max_speed, max_speed);
         }
-    } else {
-        timeout_timer.0.tick(time.delta());
     }
 }
 
 fn attack_health_entities(
     mut health_query: Query<(&Transform, &mut EntityHealth), Without<Zombie>>,
-    mut enemy_query: Query<(&Transform, &mut ZombieAttackTimer), With<Zombie>>,
+    mut enemy_query: Query<(&Transform, &mut ZombieAttackTimer, &ZombieArchetype, &ZombieStats), With<Zombie>>,
+    hash: Res<SpatialHash>,
     time: Res<Time>
 ) {
-    for (enm_trans, mut attack_timer) in enemy_query.iter_mut() {
-        for (health_trans, mut ent_health) in health_query.iter_mut() {
-            if (enm_trans.translation - health_trans.translation).length() < 20.0 {
-                if attack_timer.0.tick(time.delta()).just_finished() {
-                    ent_health.val = (ent_health.val - ATTACK_DMG).clamp(0.0, 10000.0);
-                }
+    for (enm_trans, mut attack_timer, archetype, stats) in enemy_query.iter_mut() {
+        for entity in hash.within_radius(enm_trans.translation, 20.0) {
+            let (health_trans, mut ent_health) = match health_query.get_mut(entity) {
+                Ok(health) => health,
+                Err(_) => continue
+            };
+
+            if (enm_trans.translation - health_trans.translation).length() < 20.0 && attack_timer.0.tick(time.delta()).just_finished() {
+                ent_health.val = (ent_health.val - archetype.0.damage*stats.damage).clamp(0.0, 10000.0);
             }
         }
     }
 }
 
+type ChasedPlayer = (With<Player>, Without<StaticEntity>, Without<Pathfinder>);
+
+// Attack the obstacle in the way if there is no path around it
 fn enemy_pathfind(
     mut query: Query<(&Transform, &mut Pathfinder), With<Pathfinder>>,
     static_query: Query<&Transform, (With<StaticEntity>, Without<Pathfinder>)>,
-    player_query: Query<&Transform, (With<Player>, Without<StaticEntity>, Without<Pathfinder>)>
+    player_query: Query<&Transform, ChasedPlayer>,
+    grid: Res<NavGrid>,
+    field: Res<FlowField>
 ) {
-    let player = player_query.single();
+    let players: Vec<Vec3> = player_query.iter().map(|trans| trans.translation).collect();
 
     for (enm_trans, mut enm_pf) in query.iter_mut() {
+        // Zombies after a player walk around obstacles, unless they are walled in
+        if navigation::chases_player(&enm_pf, &players)
+            && field.has_path(&grid, navigation::world_to_cell(enm_trans.translation)) != Some(false) {
+            continue;
+        }
+
         let dist = dist_between(enm_trans.translation, enm_pf.target);
 
         let r1 = (enm_pf.target.x - enm_trans.translation.x)/dist;
@@ -132,40 +258,42 @@ fn enemy_pathfind(
 
             let stat_vec = stat_trans.translation - enm_trans.translation;
 
-            if line_dist < 20.0 && stat_vec.length() < target_dist && stat_vec.dot(target_vec) > 0.0 {
-                if stat_vec.length() < closest_dist {
-                    closest_dist = stat_vec.length();
-                    closest_obj_vec = stat_trans.translation;
-                    target_obj = true;
-                }
+            if line_dist < 20.0 && stat_vec.length() < target_dist && stat_vec.dot(target_vec) > 0.0 && stat_vec.length() < closest_dist {
+                closest_dist = stat_vec.length();
+                closest_obj_vec = stat_trans.translation;
+                target_obj = true;
             }
         }
 
-        if (enm_pf.target - closest_obj_vec).length() > 20.0 && (player.translation - enm_pf.target).length() > 5.0 && target_obj {
+        if (enm_pf.target - closest_obj_vec).length() > 20.0 && target_obj {
             enm_pf.target = closest_obj_vec;
         }
     }
 }
 
+type AttackableFilter = (With<Attackable>, Without<StaticEntity>, Without<Pathfinder>);
+
 fn enemy_entity_pathfind(
-    mut query: Query<(&Transform, &mut Pathfinder), With<Pathfinder>>,
+    mut query: Query<(&Transform, &mut Pathfinder, Option<&ZombieArchetype>), With<Pathfinder>>,
     static_query: Query<&Transform, (With<StaticEntity>, Without<Pathfinder>)>,
-    ent_att: Query<(&Transform, &Attackable), (With<Attackable>, Without<StaticEntity>, Without<Pathfinder>)>
+    ent_att: Query<(&Transform, &Attackable), AttackableFilter>
 ) {
-    for (enm_trans, mut enm_pf) in query.iter_mut() {
+    for (enm_trans, mut enm_pf, archetype) in query.iter_mut() {
+        if archetype.is_some_and(|a| a.0.special == Special::TargetStructures) {
+            continue;
+        }
+
 
         let mut found_attackable: bool = false;
 
         for (ent_att_trans, ent_attackable) in ent_att.iter() {
             let static_vec: Vec<&Transform> = static_query.iter().collect();
 
-            if !is_hindered(&static_vec, &enm_trans, &ent_att_trans) {
-                if ent_attackable.0 as u8 > enm_pf.target_priority as u8 {
-                    enm_pf.target_entity = true;
-                    enm_pf.target = ent_att_trans.translation;
-                    enm_pf.target_priority = ent_attackable.0.clone();
-                    found_attackable = true;
-                }
+            if !is_hindered(&static_vec, &enm_trans, &ent_att_trans) && ent_attackable.0 as u8 > enm_pf.target_priority as u8 {
+                enm_pf.target_entity = true;
+                enm_pf.target = ent_att_trans.translation;
+                enm_pf.target_priority = ent_attackable.0;
+                found_attackable = true;
             }
         }
 
@@ -211,65 +339,50 @@ pub fn is_hindered(
     is_hindered
 }
 
-fn spawn_zombie(
+/// Spawns a zombie of the archetype called `kind`, or nothing if there is no such archetype.
+pub fn spawn_zombie_kind(
     commands: &mut Commands,
+    archetypes: &ZombieArchetypes,
+    kind: &str,
     spawn_pos: Vec3,
-    game_assets: &Res<GameAssets>
-) {
-    let mut rng = rand::thread_rng();
+    game_assets: &Res<GameAssets>,
+    stats: ZombieStats,
+    rng: &mut StdRng
+) -> Option<Entity> {
+    let def = archetypes.get(kind)?;
 
-    (*commands)
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: game_assets.texture_atlas.clone(),
-            sprite: TextureAtlasSprite {
-                index: 4,
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert_bundle(TransformBundle{
-            local: Transform::from_translation(spawn_pos),
-            ..Default::default()
-        })
-        .insert(Rigidbody{
-            vx: 0.0,
-            vy: 0.0,
-            friction: true
-        })
-        .insert(Zombie)
-        .insert(Pathfinder{
-            target: Vec3::new(rng.gen::<f32>()*INIT_TARGET_RAD,rng.gen::<f32>()*INIT_TARGET_RAD,0.0),
-            target_priority: TargetPriority::Low,
-            target_entity: false
-        })
-        .insert(ZombieAttackTimer(Timer::from_seconds(ATTACK_TIME, true)))
-        .insert(BoxCollider {
-            size: Vec2::new(10.0, 10.0)
-        })
-        .insert(NewTargetTimer(Timer::from_seconds(5.0, true)))
-        .insert(EntityHealth{val: 20.0, func_destruct: zombie_destruct});
+    Some(spawn_zombie(commands, def, spawn_pos, game_assets, stats, rng))
 }
 
-fn spawn_chungus_zombie(
+pub fn spawn_zombie(
     commands: &mut Commands,
+    def: &ArchetypeDef,
     spawn_pos: Vec3,
-    game_assets: &Res<GameAssets>
-) {
-    let mut rng = rand::thread_rng();
-
-    (*commands)
+    game_assets: &Res<GameAssets>,
+    stats: ZombieStats,
+    rng: &mut StdRng
+) -> Entity {
+    let func_destruct = match def.special {
+        Special::Explode { .. } => exploder_destruct,
+        _ => zombie_destruct
+    };
+
+    let mut zombie = (*commands)
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_assets.texture_atlas.clone(),
             sprite: TextureAtlasSprite {
-                index: 4,
+                index: def.sprite_index,
+                color: Color::rgb(def.color.0, def.color.1, def.color.2),
                 ..Default::default()
             },
             ..Default::default()
-        })
+        });
+
+    zombie
         .insert_bundle(TransformBundle{
             local: Transform{
                 translation: spawn_pos,
-                scale: Vec3::ONE * 2.0,
+                scale: Vec3::ONE * def.scale,
                 ..Default::default()
             },
             ..Default::default()
@@ -282,15 +395,24 @@ fn spawn_chungus_zombie(
         .insert(Zombie)
         .insert(Pathfinder{
             target: Vec3::new(rng.gen::<f32>()*INIT_TARGET_RAD,rng.gen::<f32>()*INIT_TARGET_RAD,0.0),
+            waypoint: None,
             target_priority: TargetPriority::Low,
             target_entity: false
         })
-        .insert(ZombieAttackTimer(Timer::from_seconds(ATTACK_TIME, true)))
+        .insert(ZombieAttackTimer(Timer::from_seconds(def.attack_interval, true)))
         .insert(BoxCollider {
-            size: Vec2::new(30.0, 30.0)
+            size: Vec2::new(def.collider.0, def.collider.1)
         })
         .insert(NewTargetTimer(Timer::from_seconds(5.0, true)))
-        .insert(EntityHealth{val: 300.0, func_destruct: zombie_destruct});
+        .insert(EntityHealth{val: def.health*stats.health, func_destruct})
+        .insert(ZombieArchetype(def.clone()))
+        .insert(stats);
+
+    if let Special::Spit { interval, .. } = def.special {
+        zombie.insert(SpitTimer(Timer::from_seconds(interval, true)));
+    }
+
+    zombie.id()
 }
 
 fn zombie_destruct(
@@ -305,20 +427,189 @@ fn zombie_destruct(
 }
 
 
+/// The player closest to `pos`, zombies go for whoever is nearest.
+fn nearest_player<'a>(player_query: &'a Query<&Transform, With<Player>>, pos: Vec3) -> Option<&'a Transform> {
+    player_query.iter()
+        .min_by(|a, b| (a.translation - pos).length().total_cmp(&(b.translation - pos).length()))
+}
+
 fn random_new_target(
-    mut query: Query<(&Transform, &mut NewTargetTimer, &mut Pathfinder)>,
+    mut query: Query<(&Transform, &mut NewTargetTimer, &mut Pathfinder, &ZombieArchetype)>,
     player_query: Query<&Transform, With<Player>>,
+    mut game_rng: ResMut<GameRng>,
     time: Res<Time>
 ){
-    let player = player_query.single();
-    let mut rng = rand::thread_rng();
+    let rng = &mut game_rng.gameplay;
+
+    for (trans, mut timer, mut pf, archetype) in query.iter_mut() {
+        if archetype.0.special == Special::TargetStructures {
+            continue;
+        }
+
+        if (trans.translation - pf.target).length() < 20.0 && !pf.target_entity && timer.0.tick(time.delta()).just_finished() {
+            let player = match nearest_player(&player_query, trans.translation) {
+                Some(player) => player,
+                None => continue
+            };
+
+            let new_target = player.translation + Vec3::new(rng.gen::<f32>()*INIT_TARGET_RAD, rng.gen::<f32>()*INIT_TARGET_RAD, 0.0);
+
+            pf.target = new_target;
+        }
+    }
+}
+
+type Structure = (With<BlockType>, With<EntityHealth>, Without<Zombie>);
+
+// Tanks go for the nearest block, and only fall back to the player when there is none
+fn target_structures(
+    mut query: Query<(&Transform, &mut Pathfinder, &ZombieArchetype), With<Zombie>>,
+    block_query: Query<&Transform, Structure>
+) {
+    for (trans, mut pf, archetype) in query.iter_mut() {
+        if archetype.0.special != Special::TargetStructures {
+            continue;
+        }
+
+        let nearest = block_query.iter()
+            .map(|block| block.translation)
+            .min_by(|a, b| (*a - trans.translation).length().total_cmp(&(*b - trans.translation).length()));
+
+        if let Some(target) = nearest {
+            pf.target = target;
+            pf.target_entity = true;
+            pf.target_priority = TargetPriority::High;
+        } else if pf.target_entity {
+            pf.target_entity = false;
+            pf.target_priority = TargetPriority::Low;
+        }
+    }
+}
+
+type ExplosionTrigger = (Or<(With<StaticEntity>, With<Player>)>, Without<Zombie>);
+
+// Exploders blow up once they are next to a block or the player
+fn exploder_detonate(
+    mut zombie_query: Query<(&Transform, &mut EntityHealth, &ZombieArchetype), With<Zombie>>,
+    target_query: Query<&Transform, ExplosionTrigger>,
+    hash: Res<SpatialHash>
+) {
+    for (trans, mut health, archetype) in zombie_query.iter_mut() {
+        let trigger_dist = match archetype.0.special {
+            Special::Explode { trigger_dist } => trigger_dist,
+            _ => continue
+        };
+
+        let triggered = hash.within_radius(trans.translation, trigger_dist)
+            .filter_map(|e| target_query.get(e).ok())
+            .any(|target| (target.translation - trans.translation).length() < trigger_dist);
+
+        if triggered {
+            // The explosion is spawned when the zombie is destroyed, see `exploder_destruct`
+            health.val = 0.0;
+        }
+    }
+}
+
+fn exploder_destruct(
+    commands: &mut Commands,
+    entity: &Entity,
+    game_assets: &Res<GameAssets>,
+    parent_trans: &Transform
+) {
+    commands.entity(*entity).despawn();
+
+    tripmine::spawn_explosion(commands, game_assets, parent_trans);
+}
+
+fn spitter_attack(
+    mut commands: Commands,
+    mut zombie_query: Query<(&Transform, &mut SpitTimer, &ZombieArchetype, &ZombieStats), With<Zombie>>,
+    player_query: Query<&Transform, With<Player>>,
+    static_query: Query<&Transform, (With<StaticEntity>, Without<Pathfinder>)>,
+    time: Res<Time>,
+    balance: Res<Balance>
+) {
+    let static_vec: Vec<&Transform> = static_query.iter().collect();
+
+    for (trans, mut timer, archetype, stats) in zombie_query.iter_mut() {
+        let (range, damage, speed) = match archetype.0.special {
+            Special::Spit { range, damage, speed, .. } => (range, damage, speed),
+            _ => continue
+        };
+
+        let player = match nearest_player(&player_query, trans.translation) {
+            Some(player) => player,
+            None => continue
+        };
+
+        if (player.translation - trans.translation).length() > range {
+            continue;
+        }
+
+        if timer.0.tick(time.delta()).just_finished() && !is_hindered(&static_vec, &trans, &player) {
+            let angle = angle_between(trans.translation, player.translation);
+
+            spawn_spit(&mut commands, trans.translation, angle, speed, damage*stats.damage, balance.zombies.spit_lifetime);
+        }
+    }
+}
+
+fn spawn_spit(
+    commands: &mut Commands,
+    spawn_at: Vec3,
+    angle: f32,
+    speed: f32,
+    damage: f32,
+    lifetime: f32
+) {
+    (*commands)
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite{
+                color: Color::rgb(0.4, 0.9, 0.2),
+                custom_size: Some(Vec2::new(6.0, 6.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert_bundle(TransformBundle{
+            local: Transform::from_translation(spawn_at),
+            ..Default::default()
+        })
+        .insert(Spit { damage, lifetime: Timer::from_seconds(lifetime, false) })
+        .insert(Rigidbody{
+            vx: angle.cos()*speed,
+            vy: angle.sin()*speed,
+            friction: false,
+        });
+}
+
+type SpitTarget<'a> = (&'a Transform, &'a mut EntityHealth);
+
+// Spit hurts the player and blocks, but not other zombies
+fn spit_behaviour(
+    mut commands: Commands,
+    mut spit_query: Query<(Entity, &Transform, &mut Spit)>,
+    mut health_query: Query<SpitTarget, (Without<Zombie>, Without<Spit>)>,
+    hash: Res<SpatialHash>,
+    time: Res<Time>
+) {
+    'outer: for (spit_entity, spit_trans, mut spit) in spit_query.iter_mut() {
+        if spit.lifetime.tick(time.delta()).just_finished() {
+            commands.entity(spit_entity).despawn();
+            continue;
+        }
 
-    for (trans, mut timer, mut pf) in query.iter_mut() {
-        if (trans.translation - pf.target).length() < 20.0 && !pf.target_entity {
-            if timer.0.tick(time.delta()).just_finished() {
-                let new_target = player.translation + Vec3::new(rng.gen::<f32>()*INIT_TARGET_RAD, rng.gen::<f32>()*INIT_TARGET_RAD, 0.0);
+        for entity in hash.within_radius(spit_trans.translation, SPIT_HIT_DIST) {
+            let (health_trans, mut health) = match health_query.get_mut(entity) {
+                Ok(health) => health,
+                Err(_) => continue
+            };
 
-                pf.target = new_target;
+            if (spit_trans.translation - health_trans.translation).length() < SPIT_HIT_DIST {
+                health.val -= spit.damage;
+                commands.entity(spit_entity).despawn();
+                continue 'outer;
             }
         }
     }
@@ -331,7 +622,7 @@ fn spawn_dead(
 )
 {
 
-    let mut cloned = spawn_trans.clone();
+    let mut cloned = *spawn_trans;
     cloned.translation.z -= 1.0;
 
     (*commands)
@@ -7,7 +7,15 @@ impl Plugin for EventsPlugin
     fn build(&self, app: &mut App) {
         app.add_event::<ChestInteractEvent>()
         .add_event::<ChestChangeInventoryEvent>()
-        .add_event::<CollisionEvent>();
+        .add_event::<CraftingInteractEvent>()
+        .add_event::<HarvestInteractEvent>()
+        .add_event::<TurretInteractEvent>()
+        .add_event::<CollisionEvent>()
+        .add_event::<ItemMinedEvent>()
+        .add_event::<WaveStarted>()
+        .add_event::<WaveCleared>()
+        .add_event::<NightFell>()
+        .add_event::<DayBroke>();
     }
 }
 
@@ -16,6 +24,25 @@ pub struct ChestInteractEvent
     pub chest_entity: Entity
 }
 
+pub struct CraftingInteractEvent
+{
+    pub table_entity: Entity
+}
+
+pub struct HarvestInteractEvent
+{
+    pub crop_entity: Entity
+}
+
+pub struct TurretInteractEvent
+{
+    pub turret_entity: Entity,
+    // Player whose ammo refills the turret
+    pub player_entity: Entity,
+    // Switch to the next targeting mode instead of refilling
+    pub cycle_mode: bool
+}
+
 pub struct ChestChangeInventoryEvent
 {
     pub entity: Entity,
@@ -26,4 +53,29 @@ pub struct CollisionEvent
 {
     pub ent_a: Entity,
     pub stat_b: Entity
-}
\ No newline at end of file
+}
+
+pub struct ItemMinedEvent
+{
+    pub item: Item
+}
+
+pub struct WaveStarted
+{
+    pub number: u32
+}
+
+pub struct WaveCleared
+{
+    pub number: u32
+}
+
+pub struct NightFell
+{
+    pub day: u32
+}
+
+pub struct DayBroke
+{
+    pub day: u32
+}
@@ -0,0 +1,157 @@
+//! The controls screen, opened from the main menu.
+//!
+//! Up and down select an action and enter waits for the next key or mouse button to
+//! bind to it. A binding that clashes with another action is refused. Backspace
+//! restores the default binding of the selected action and delete unbinds it. Escape
+//! saves the controls to `controls.ron` and returns to the main menu. These keys are
+//! fixed so that the screen can not be locked out by a bad binding.
+
+use crate::{prelude::*, utils::destruct_cleanup};
+use crate::systems::controls::{write_controls, Binding, CONTROLS_PATH};
+
+pub struct ControlsMenuPlugin;
+
+#[derive(Component)]
+pub struct ControlsText;
+
+#[derive(Default)]
+pub struct Rebinding
+{
+    pub selected: usize,
+    // Waiting for the key or mouse button to bind to the selected action
+    pub waiting: bool,
+    pub message: String
+}
+
+impl Plugin for ControlsMenuPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>()
+        .add_system_set(SystemSet::on_enter(AppState::Controls)
+            .with_system(setup_controls_menu))
+        .add_system_set(SystemSet::on_update(AppState::Controls)
+            .with_system(rebind_input)
+            .with_system(controls_text.after(rebind_input)))
+        .add_system_set(SystemSet::on_exit(AppState::Controls)
+            .with_system(destruct_cleanup::<ControlsText>));
+    }
+}
+
+fn setup_controls_menu(
+    mut commands: Commands,
+    mut rebinding: ResMut<Rebinding>,
+    inv_asset: Res<InventoryAsset>
+) {
+    *rebinding = Rebinding::default();
+
+    let style = TextStyle {
+        font: inv_asset.font.clone(),
+        font_size: 14.0,
+        color: Color::WHITE
+    };
+
+    commands.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: Color::rgb(0.0, 0.0, 0.0).into(),
+        ..Default::default()
+    })
+    .insert(ControlsText)
+    .with_children(|parent| {
+        parent.spawn_bundle(TextBundle {
+            text: Text::from_section("", style),
+            ..Default::default()
+        })
+        .insert(ControlsText);
+    });
+}
+
+fn rebind_input(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut controls: ResMut<Controls>,
+    mut rebinding: ResMut<Rebinding>,
+    mut app_state: ResMut<State<AppState>>
+) {
+    let action = Action::ALL[rebinding.selected];
+
+    if rebinding.waiting {
+        if keys.just_pressed(KeyCode::Escape) {
+            rebinding.waiting = false;
+            rebinding.message = String::new();
+            return;
+        }
+
+        let binding = keys.get_just_pressed().next().map(|key| Binding::Key(*key))
+            .or_else(|| buttons.get_just_pressed().next().map(|button| Binding::Mouse(*button)));
+
+        if let Some(binding) = binding {
+            rebinding.waiting = false;
+            rebinding.message = bind(&mut controls, action, vec![binding]);
+        }
+
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Up) {
+        rebinding.selected = (rebinding.selected + Action::ALL.len() - 1) % Action::ALL.len();
+    } else if keys.just_pressed(KeyCode::Down) {
+        rebinding.selected = (rebinding.selected + 1) % Action::ALL.len();
+    } else if keys.just_pressed(KeyCode::Return) {
+        rebinding.waiting = true;
+        rebinding.message = format!("Press a key or mouse button for {:?}, escape to cancel", action);
+    } else if keys.just_pressed(KeyCode::Back) {
+        rebinding.message = bind(&mut controls, action, action.default_bindings());
+    } else if keys.just_pressed(KeyCode::Delete) {
+        controls.bindings.insert(action, Vec::new());
+        rebinding.message = format!("{:?} is unbound", action);
+    } else if keys.just_pressed(KeyCode::Escape) {
+        match write_controls(CONTROLS_PATH, &controls) {
+            Ok(()) => println!("Saved controls to {}", CONTROLS_PATH),
+            Err(e) => eprintln!("{}", e)
+        }
+
+        app_state.set(AppState::MainMenu).unwrap();
+    }
+}
+
+/// Binds `bindings` to `action` unless one of them clashes with another action.
+fn bind(controls: &mut Controls, action: Action, bindings: Vec<Binding>) -> String {
+    for binding in bindings.iter() {
+        if let Some(other) = controls.conflicts(action, *binding).first() {
+            return format!("{} is already bound to {:?}", binding.display(), other);
+        }
+    }
+
+    controls.bindings.insert(action, bindings);
+
+    format!("{:?} is bound to {}", action, controls.display(action))
+}
+
+fn controls_text(
+    controls: Res<Controls>,
+    rebinding: Res<Rebinding>,
+    mut text_query: Query<&mut Text, With<ControlsText>>
+) {
+    let mut lines = vec![
+        "Controls".to_string(),
+        "Up/Down select, enter rebinds, backspace restores the default, delete unbinds, escape saves".to_string(),
+        String::new()
+    ];
+
+    for (i, action) in Action::ALL.iter().enumerate() {
+        let marker = if i == rebinding.selected { ">" } else { " " };
+        lines.push(format!("{} {:?}: {}", marker, action, controls.display(*action)));
+    }
+
+    lines.push(String::new());
+    lines.push(rebinding.message.clone());
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
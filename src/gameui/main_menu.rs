@@ -9,7 +9,8 @@ impl Plugin for MainMenuPlugin
         .add_system_set(SystemSet::on_enter(AppState::MainMenu)
             .with_system(setup_main_menu))
         .add_system_set(SystemSet::on_update(AppState::MainMenu)
-            .with_system(key_press))
+            .with_system(key_press)
+            .with_system(seed_input))
         .add_system_set(SystemSet::on_exit(AppState::MainMenu)
             .with_system(destruct_cleanup::<UiText>));
             
@@ -23,12 +24,17 @@ impl Plugin for MainMenuPlugin
 
 fn setup_main_menu(
     mut commands: Commands,
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    game_rng: Res<GameRng>,
+    controls: Res<Controls>
 ) {
 
     let font_handle: Handle<Font> = asset_server.load("fonts\\Roboto-Regular.ttf");
     let game_name: &str = "Apocalypse Farmer";
-    let game_start_msg: &str = "Press space to begin game!";
+    let game_start_msg: String = format!("Press {} to begin game!\nPress {} to change the controls",
+        controls.display(Action::StartGame),
+        controls.display(Action::OpenControls));
+    let seed_msg: String = format!("\nSeed: {} (type to change)", game_rng.seed);
 
     commands.spawn_bundle(NodeBundle {
         style: Style {
@@ -46,7 +52,7 @@ fn setup_main_menu(
         parent.spawn_bundle(TextBundle {
             style: Style {
                 // Set height to font size * number of text lines
-                size: Size::new(Val::Auto, Val::Px(62. * 1.)),
+                size: Size::new(Val::Auto, Val::Px(62. * 2.)),
                 // Set left margin to auto to push the text to the right
                 margin: UiRect {
                     left: Val::Auto,
@@ -68,12 +74,21 @@ fn setup_main_menu(
                     },
 
                     TextSection {
-                        value: game_start_msg.to_string(),
+                        value: game_start_msg,
                         style: TextStyle {
                             font: font_handle.clone(),
                             font_size: 12.0,
                             color: Color::rgb(1.0, 1.0, 1.0)
                         }
+                    },
+
+                    TextSection {
+                        value: seed_msg,
+                        style: TextStyle {
+                            font: font_handle.clone(),
+                            font_size: 12.0,
+                            color: Color::rgb(0.6, 0.6, 0.6)
+                        }
                     }
                 ],
                 alignment: TextAlignment {
@@ -82,7 +97,8 @@ fn setup_main_menu(
                 },
             },
             ..Default::default()
-        });
+        })
+        .insert(SeedText);
     });
 
 }
@@ -94,18 +110,69 @@ fn setup_setupgame(
 }
 
 fn key_press(
-    btn: Res<Input<KeyCode>>,
-    mut app_state: ResMut<State<AppState>>
+    actions: Res<Input<Action>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut game_rng: ResMut<GameRng>
 ) {
-    if btn.just_pressed(KeyCode::Space) {
+    if actions.just_pressed(Action::StartGame) {
+        // Start every game from the beginning of the seeded streams
+        *game_rng = GameRng::new(game_rng.seed);
         app_state.set(AppState::GameSetup).unwrap();
-    }    
+    } else if actions.just_pressed(Action::OpenControls) {
+        app_state.set(AppState::Controls).unwrap();
+    }
 }
 
+fn seed_input(
+    btn: Res<Input<KeyCode>>,
+    mut game_rng: ResMut<GameRng>,
+    mut seed_text: Query<&mut Text, With<SeedText>>,
+    network: Res<Network>
+) {
+    // Everyone has to generate the same map as the host
+    if network.is_multiplayer() {
+        return;
+    }
+
+    for key in btn.get_just_pressed() {
+        let seed = match key {
+            KeyCode::Back => game_rng.seed / 10,
+            _ => match key_digit(key) {
+                Some(digit) => game_rng.seed.saturating_mul(10).saturating_add(digit),
+                None => continue
+            }
+        };
+
+        *game_rng = GameRng::new(seed);
+
+        for mut text in seed_text.iter_mut() {
+            text.sections[2].value = format!("\nSeed: {} (type to change)", seed);
+        }
+    }
+}
+
+fn key_digit(key: &KeyCode) -> Option<u64> {
+    match key {
+        KeyCode::Key0 | KeyCode::Numpad0 => Some(0),
+        KeyCode::Key1 | KeyCode::Numpad1 => Some(1),
+        KeyCode::Key2 | KeyCode::Numpad2 => Some(2),
+        KeyCode::Key3 | KeyCode::Numpad3 => Some(3),
+        KeyCode::Key4 | KeyCode::Numpad4 => Some(4),
+        KeyCode::Key5 | KeyCode::Numpad5 => Some(5),
+        KeyCode::Key6 | KeyCode::Numpad6 => Some(6),
+        KeyCode::Key7 | KeyCode::Numpad7 => Some(7),
+        KeyCode::Key8 | KeyCode::Numpad8 => Some(8),
+        KeyCode::Key9 | KeyCode::Numpad9 => Some(9),
+        _ => None
+    }
+}
+
+type GameSprite = Or<(With<TextureAtlasSprite>, With<Sprite>)>;
+
 fn destruct_game(
     mut commands: Commands,
     mut state: ResMut<State<AppState>>,
-    entity_query: Query<Entity, Or<(With<TextureAtlasSprite>, With<Sprite>)>>
+    entity_query: Query<Entity, GameSprite>
 ) {
     for entity in entity_query.iter() {
         commands.entity(entity).despawn();
@@ -1,12 +1,14 @@
 use bevy::prelude::*;
 
 pub mod main_menu;
+pub mod controls_menu;
 
 pub struct GameUiPlugin;
 
 impl Plugin for GameUiPlugin
 {
     fn build(&self, app: &mut App) {
-        app.add_plugin(main_menu::MainMenuPlugin);
+        app.add_plugin(main_menu::MainMenuPlugin)
+        .add_plugin(controls_menu::ControlsMenuPlugin);
     }
 }
\ No newline at end of file
@@ -0,0 +1,238 @@
+//! Support for running the game without a window, used for automated testing and balancing.
+//!
+//! `HeadlessPlugin` replaces the windowed input and time with a fixed timestep and an
+//! `InputScript`. The script is a plain text file with one input event per line:
+//!
+//! ```text
+//! # seconds  action   argument
+//! 0.0        press    D
+//! 1.5        release  D
+//! 2.0        mouse    20 0
+//! 2.0        click    Right
+//! 2.1        unclick  Right
+//! ```
+//!
+//! See `src/bin/headless.rs` for a runner that simulates a survival run from the command line.
+
+use std::time::Duration;
+
+use bevy::{input::mouse::MouseWheel, time::TimePlugin};
+
+use crate::prelude::*;
+
+pub struct HeadlessPlugin
+{
+    pub timestep: f32
+}
+
+impl Default for HeadlessPlugin
+{
+    fn default() -> Self {
+        HeadlessPlugin {
+            timestep: 1.0/60.0
+        }
+    }
+}
+
+impl Plugin for HeadlessPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedStep(Duration::from_secs_f32(self.timestep)))
+        .insert_resource(Time::default())
+        .insert_resource(Input::<KeyCode>::default())
+        .insert_resource(Input::<MouseButton>::default())
+        .add_event::<MouseWheel>()
+        .insert_resource(GameAssets { texture_atlas: Handle::default() })
+        .insert_resource(InventoryAsset { texture: Handle::default(), font: Handle::default() })
+        .insert_resource(SimulationStats::default())
+        .init_resource::<InputScript>()
+        .add_system_to_stage(CoreStage::First, fixed_time_step.exclusive_system().at_start())
+        .insert_resource(Controls::default())
+        .add_system_to_stage(CoreStage::PreUpdate, scripted_input.before(controls::ActionSystem))
+        .add_system_set(SystemSet::on_update(AppState::GameSetup)
+            .with_system(start_game))
+        .add_system_set(SystemSet::on_update(AppState::InGame)
+            .with_system(count_mined));
+    }
+}
+
+/// Builds a windowless game app that starts in `AppState::GameSetup` and moves on to
+/// `AppState::InGame` on its first update.
+pub fn headless_app(headless: HeadlessPlugin, seed: u64) -> App {
+    let mut app = App::new();
+
+    app.add_plugins_with(MinimalPlugins, |group| group.disable::<TimePlugin>())
+    .add_plugin(TransformPlugin)
+    .add_plugin(HierarchyPlugin)
+    .add_plugin(headless)
+    .insert_resource(GameRng::new(seed))
+    .add_plugins_with(GamePlugin { initial_state: AppState::GameSetup }, |group| {
+        group.disable::<GraphicsPlugin>()
+        .disable::<GameUiPlugin>()
+    });
+
+    app
+}
+
+pub struct FixedStep(pub Duration);
+
+#[derive(Default)]
+pub struct SimulationStats
+{
+    pub mined: u32
+}
+
+pub enum ScriptAction
+{
+    Press(KeyCode),
+    Release(KeyCode),
+    Click(MouseButton),
+    Unclick(MouseButton),
+    MouseMove(f32, f32)
+}
+
+pub struct ScriptEvent
+{
+    pub time: f32,
+    pub action: ScriptAction
+}
+
+#[derive(Default)]
+pub struct InputScript
+{
+    pub events: Vec<ScriptEvent>,
+    pub next: usize
+}
+
+impl InputScript
+{
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut events = Vec::new();
+
+        for (line_nr, line) in contents.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let err = || format!("Invalid script line {}: {}", line_nr + 1, line);
+
+            if parts.len() < 3 {
+                return Err(err());
+            }
+
+            let time: f32 = parts[0].parse().map_err(|_| err())?;
+
+            let action = match parts[1] {
+                "press" => ScriptAction::Press(parse_key(parts[2]).ok_or_else(err)?),
+                "release" => ScriptAction::Release(parse_key(parts[2]).ok_or_else(err)?),
+                "click" => ScriptAction::Click(parse_button(parts[2]).ok_or_else(err)?),
+                "unclick" => ScriptAction::Unclick(parse_button(parts[2]).ok_or_else(err)?),
+                "mouse" if parts.len() == 4 => ScriptAction::MouseMove(
+                    parts[2].parse().map_err(|_| err())?,
+                    parts[3].parse().map_err(|_| err())?),
+                _ => return Err(err())
+            };
+
+            events.push(ScriptEvent { time, action });
+        }
+
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        Ok(InputScript { events, next: 0 })
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let key = match name {
+        "W" => KeyCode::W,
+        "A" => KeyCode::A,
+        "S" => KeyCode::S,
+        "D" => KeyCode::D,
+        "R" => KeyCode::R,
+        "Q" => KeyCode::Q,
+        "E" => KeyCode::E,
+        "F" => KeyCode::F,
+        "I" => KeyCode::I,
+        "Space" => KeyCode::Space,
+        "F5" => KeyCode::F5,
+        "F9" => KeyCode::F9,
+        "Key1" | "1" => KeyCode::Key1,
+        "Key2" | "2" => KeyCode::Key2,
+        "Key3" | "3" => KeyCode::Key3,
+        "Key4" | "4" => KeyCode::Key4,
+        "Key5" | "5" => KeyCode::Key5,
+        "Key6" | "6" => KeyCode::Key6,
+        "Key7" | "7" => KeyCode::Key7,
+        "Key8" | "8" => KeyCode::Key8,
+        "Key9" | "9" => KeyCode::Key9,
+        "Key0" | "0" => KeyCode::Key0,
+        _ => return None
+    };
+
+    Some(key)
+}
+
+fn parse_button(name: &str) -> Option<MouseButton> {
+    match name {
+        "Left" => Some(MouseButton::Left),
+        "Right" => Some(MouseButton::Right),
+        "Middle" => Some(MouseButton::Middle),
+        _ => None
+    }
+}
+
+// Replaces `TimePlugin` so that every frame advances the game by exactly one step.
+fn fixed_time_step(
+    mut time: ResMut<Time>,
+    step: Res<FixedStep>
+) {
+    let last_update = time.last_update().unwrap_or_else(|| time.startup());
+    time.update_with_instant(last_update + step.0);
+}
+
+fn scripted_input(
+    mut script: ResMut<InputScript>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut mouse_loc: ResMut<MouseLoc>,
+    time: Res<Time>
+) {
+    // There is no InputPlugin to do this for us
+    keys.clear();
+    buttons.clear();
+
+    let now = time.seconds_since_startup() as f32;
+
+    while script.next < script.events.len() && script.events[script.next].time <= now {
+        match script.events[script.next].action {
+            ScriptAction::Press(key) => keys.press(key),
+            ScriptAction::Release(key) => keys.release(key),
+            ScriptAction::Click(button) => buttons.press(button),
+            ScriptAction::Unclick(button) => buttons.release(button),
+            ScriptAction::MouseMove(x, y) => {
+                mouse_loc.x = x;
+                mouse_loc.y = y;
+            }
+        }
+
+        script.next += 1;
+    }
+}
+
+fn start_game(
+    mut app_state: ResMut<State<AppState>>
+) {
+    app_state.set(AppState::InGame).unwrap();
+}
+
+fn count_mined(
+    mut mined_reader: EventReader<ItemMinedEvent>,
+    mut stats: ResMut<SimulationStats>
+) {
+    for mined in mined_reader.iter() {
+        stats.mined += mined.item.quantity as u32;
+    }
+}
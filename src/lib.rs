@@ -0,0 +1,129 @@
+pub mod player;
+pub mod systems;
+pub mod gameui;
+pub mod entities;
+pub mod blocks;
+pub mod components;
+pub mod resources;
+pub mod utils;
+pub mod events;
+pub mod headless;
+
+pub mod prelude {
+    pub use bevy::prelude::*;
+    pub use rand::*;
+    pub use crate::player::*;
+    pub use crate::gameui::*;
+    pub use crate::systems::*;
+    pub use crate::entities::*;
+    pub use crate::blocks::*;
+    pub use crate::components::*;
+    pub use crate::resources::*;
+    pub use crate::events::*;
+    pub use crate::utils::angle_between;
+    pub use crate::utils::dist_between;
+    pub use crate::utils::my_cursor_system;
+    pub use crate::{GamePlugin, GameStatePlugin, GraphicsPlugin};
+}
+
+use bevy::app::PluginGroupBuilder;
+
+use prelude::*;
+
+pub const SHEET_COLUMNS: usize = 4;
+pub const SHEET_ROWS: usize = 10;
+
+/// All of the game. Sub-plugins can be turned off with `App::add_plugins_with`,
+/// e.g. `GraphicsPlugin` and `GameUiPlugin` when running without a window.
+pub struct GamePlugin
+{
+    pub initial_state: AppState
+}
+
+impl Default for GamePlugin
+{
+    fn default() -> Self {
+        GamePlugin {
+            initial_state: AppState::MainMenu
+        }
+    }
+}
+
+impl PluginGroup for GamePlugin
+{
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group.add(GameStatePlugin { initial_state: self.initial_state.clone() })
+        .add(EventsPlugin)
+        .add(PlayerPlugin)
+        .add(EntitiesPlugin)
+        .add(SystemsPlugin)
+        .add(BlocksPlugin)
+        .add(GraphicsPlugin)
+        .add(GameUiPlugin);
+    }
+}
+
+/// Shared resources, the `AppState` machine and the player controls.
+pub struct GameStatePlugin
+{
+    pub initial_state: AppState
+}
+
+impl Plugin for GameStatePlugin
+{
+    fn build(&self, app: &mut App) {
+        // Keep a seed that was chosen before the plugin was added
+        if !app.world.contains_resource::<GameRng>() {
+            app.insert_resource(GameRng::new(thread_rng().gen::<u32>() as u64));
+        }
+
+        app.insert_resource(MouseLoc{x: 0.0, y: 0.0})
+        .add_state(self.initial_state.clone())
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(utils::keyboard_actions)
+        );
+    }
+}
+
+/// Camera, textures and cursor tracking. Needs a window and the `AssetServer`.
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup)
+        .add_system(my_cursor_system);
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>
+) {
+    commands.spawn_bundle(Camera2dBundle::default())
+    .insert(MainCamera);
+
+    // Get the texture sheet
+    let texture_handle = asset_server.load("Sheet.png");
+    let texture_atlas = TextureAtlas::from_grid(texture_handle,
+    Vec2::new(20.0,20.0), SHEET_COLUMNS, SHEET_ROWS);
+
+    let texture_atlas_handle = (texture_atlases).add(texture_atlas);
+    
+    commands.insert_resource(GameAssets{
+        texture_atlas: texture_atlas_handle.clone()
+    });
+
+    // Get the texture of the inventory
+    let texture_handle_inventory = asset_server.load("inventory.png");
+    let texture_atlas_inventory = TextureAtlas::from_grid(texture_handle_inventory,
+        Vec2::new(108.0, 66.0), 1, 1);
+
+    let inventory_handle = texture_atlases.add(texture_atlas_inventory);
+
+    commands.insert_resource(InventoryAsset {
+        texture: inventory_handle,
+        font: asset_server.load("fonts/Roboto-Regular.ttf")
+    });
+}
@@ -0,0 +1,132 @@
+//! The hotbar at the bottom of the screen.
+//!
+//! Each hotbar slot points at a slot of the player's inventory. The `Hotbar1` to
+//! `Hotbar10` actions pick a slot and the scroll wheel cycles through them. Stacks
+//! dragged from the inventory screen onto the hotbar bind their inventory slot to it.
+
+use bevy::input::mouse::MouseWheel;
+
+use crate::{prelude::*, utils::destruct_cleanup};
+
+// One slot per hotbar action
+pub const HOTBAR_SLOTS: usize = Action::HOTBAR.len();
+const SLOT_STEP: f32 = 24.0;
+const HOTBAR_Y: f32 = -275.0;
+
+const SLOT_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.8);
+const ACTIVE_COLOR: Color = Color::rgba(0.6, 0.6, 0.6, 0.9);
+
+#[derive(Component)]
+pub struct HotbarSlot(pub usize);
+
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin
+{
+    fn build(&self, app: &mut App)
+    {
+        app
+        .add_system_set(SystemSet::on_enter(AppState::GameSetup)
+            .with_system(hotbar_setup))
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(hotbar_select))
+        .add_system(hotbar_display)
+        .add_system_set(SystemSet::on_enter(AppState::GameDestruct)
+            .with_system(destruct_cleanup::<HotbarSlot>));
+    }
+}
+
+fn hotbar_setup(mut commands: Commands) {
+    let offx = -(HOTBAR_SLOTS as f32 - 1.0) / 2.0 * SLOT_STEP;
+
+    for i in 0..HOTBAR_SLOTS {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: SLOT_COLOR,
+                    custom_size: Some(Vec2::splat(SLOT_STEP - 2.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(offx + i as f32 * SLOT_STEP, HOTBAR_Y, 10.0),
+                ..Default::default()
+            })
+            .insert(HotbarSlot(i));
+    }
+}
+
+/// The hotbar slot under `pos`, if any.
+pub fn slot_at(hotbar_slots: &Query<(&HotbarSlot, &GlobalTransform)>, pos: Vec3) -> Option<usize> {
+    hotbar_slots.iter()
+        .find(|(_, glob_trans)| {
+            let offset = pos.truncate() - glob_trans.translation().truncate();
+            offset.x.abs() <= SLOT_STEP / 2.0 && offset.y.abs() <= SLOT_STEP / 2.0
+        })
+        .map(|(slot, _)| slot.0)
+}
+
+fn hotbar_select(
+    mut hotbar_query: Query<(&mut Hotbar, &PlayerInput, Option<&LocalPlayer>), With<Player>>,
+    mut wheel_reader: EventReader<MouseWheel>,
+    state: Res<State<AppState>>
+) {
+    // Scrolling up selects the slot to the left, but not while scrolling in a menu
+    let scroll: f32 = wheel_reader.iter().map(|wheel| wheel.y).sum();
+    let scroll = if state.current() == &AppState::InGame { scroll } else { 0.0 };
+
+    for (mut hotbar, input, local) in hotbar_query.iter_mut() {
+        if let Some(slot) = Action::HOTBAR.iter().position(|action| input.actions.just_pressed(*action)) {
+            if slot < hotbar.slots.len() {
+                hotbar.active = slot;
+            }
+        }
+
+        // The wheel is not an action, so a client sends its whole hotbar to the host instead
+        if scroll != 0.0 && local.is_some() {
+            hotbar.cycle(-scroll.signum() as i32);
+        }
+    }
+}
+
+type HotbarChanged = (With<LocalPlayer>, Or<(Changed<Hotbar>, Changed<InventoryItems>)>);
+
+/// Redraws the hotbar icons whenever the hotbar or the player's inventory changes.
+#[allow(clippy::too_many_arguments)]
+fn hotbar_display(
+    mut commands: Commands,
+    player_query: Query<(&Hotbar, &InventoryItems), With<LocalPlayer>>,
+    changed: Query<(), HotbarChanged>,
+    mut slot_query: Query<(Entity, &HotbarSlot, &mut Sprite)>,
+    new_slots: Query<(), Added<HotbarSlot>>,
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    inv_asset: Res<InventoryAsset>
+) {
+    let (hotbar, inv_items) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return
+    };
+
+    if changed.is_empty() && new_slots.is_empty() {
+        return;
+    }
+
+    let text_style = inventory::quantity_style(&inv_asset);
+
+    for (entity, slot, mut sprite) in slot_query.iter_mut() {
+        sprite.color = if slot.0 == hotbar.active { ACTIVE_COLOR } else { SLOT_COLOR };
+
+        let item = hotbar.slots.get(slot.0)
+            .copied()
+            .flatten()
+            .and_then(|inv_slot| inv_items.slots.get(inv_slot)?.as_ref());
+
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity)
+            .with_children(|parent| {
+                if let Some(item) = item {
+                    inventory::spawn_icon(parent, &game_assets, &registry, &text_style, item,
+                        Transform::from_xyz(0.0, 0.0, 1.0), ());
+                }
+            });
+    }
+}
@@ -1,15 +1,15 @@
 use bevy::prelude::*;
 
+#[allow(clippy::module_inception)]
 pub mod player;
-
-pub use player::MOVESPEED;
-pub use player::PLAYER_ACC;
+pub mod hotbar;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin
 {
     fn build(&self, app: &mut App) {
-        app.add_plugin(player::PlayerPlugin);
+        app.add_plugin(player::PlayerPlugin)
+        .add_plugin(hotbar::HotbarPlugin);
     }
 }
\ No newline at end of file
@@ -2,12 +2,10 @@ use bevy::sprite::Anchor;
 
 use crate::prelude::*;
 
-pub const COLL_DIST: f32 = 10.0;
-pub const MOVESPEED: f32 = 40.0;
-pub const PLAYER_ACC: f32 = 600.0;
-
 pub struct PlayerPlugin;
 
+pub const SPAWN_POS: Vec3 = Vec3::new(0.0, 0.0, 5.0);
+
 impl Plugin for PlayerPlugin
 {
     fn build(&self, app: &mut App)
@@ -15,16 +13,98 @@ impl Plugin for PlayerPlugin
         app
         .add_system_set(SystemSet::on_enter(AppState::GameSetup)
             .with_system(player_setup))
-        .add_system_set(SystemSet::on_update(AppState::InGame)
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
             .with_system(player_health)
+            .with_system(player_hunger)
+            .with_system(eat_food)
             .with_system(collect_items));
     }
 }
 
 fn player_setup(
     mut commands: Commands,
-    game_asset: Res<GameAssets>
+    game_asset: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    balance: Res<Balance>,
+    network: Res<Network>
 ) {
+    let player_entity = spawn_player(&mut commands,
+        &game_asset,
+        &balance,
+        SPAWN_POS,
+        starting_items(&registry));
+
+    commands.entity(player_entity)
+        .insert(LocalPlayer)
+        .insert(network.local_id());
+
+    // SPAWN HEALTBAR
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite{
+                color: Color::rgb(0.0,1.0,0.0),
+                custom_size: Some(Vec2::new(100.0,10.0)),
+                anchor: Anchor::CenterLeft,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert_bundle(TransformBundle{
+            local: Transform::from_xyz(-400.0, 300.0, 10.0),
+            ..Default::default()
+        })
+        .insert(HealthBar);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite{
+                color: Color::rgb(0.8,0.5,0.0),
+                custom_size: Some(Vec2::new(balance.player.max_hunger,10.0)),
+                anchor: Anchor::CenterLeft,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert_bundle(TransformBundle{
+            local: Transform::from_xyz(-400.0, 285.0, 10.0),
+            ..Default::default()
+        })
+        .insert(HungerBar);
+}
+
+/// What every player starts a game with.
+pub fn starting_items(registry: &ItemRegistry) -> InventoryItems {
+    let mut inv_items = InventoryItems::new(inventory::INVENTORY_SLOTS);
+
+    for (item_type, quantity) in [
+        (ItemTypes::WoodFence, 30),
+        (ItemTypes::TurretBlock, 30),
+        (ItemTypes::TripMine, 30),
+        (ItemTypes::Fence, 30),
+        (ItemTypes::WindMill, 30),
+        (ItemTypes::Seeds, 30),
+        (ItemTypes::MiningRig, 2),
+        (ItemTypes::CraftingTable, 2),
+        (ItemTypes::Chest, 2),
+        (ItemTypes::Rifle, 1),
+        (ItemTypes::Pistol, 1),
+        (ItemTypes::Ammo, 120)
+    ] {
+        if let Err(leftover) = inv_items.add_item(Item { item_type, quantity }, registry.max_stack(item_type)) {
+            eprintln!("No room for {} starting {:?}", leftover.quantity, leftover.item_type);
+        }
+    }
+
+    inv_items
+}
+
+pub fn spawn_player(
+    commands: &mut Commands,
+    game_asset: &Res<GameAssets>,
+    balance: &Balance,
+    spawn_pos: Vec3,
+    inv_items: InventoryItems
+) -> Entity {
     commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: game_asset.texture_atlas.clone(),
@@ -35,7 +115,7 @@ fn player_setup(
             ..Default::default()
         })
         .insert_bundle(TransformBundle{
-            local: Transform::from_xyz(0.0, 0.0, 5.0),
+            local: Transform::from_translation(spawn_pos),
             ..Default::default()
         })
         .insert(Player)
@@ -44,99 +124,132 @@ fn player_setup(
             vy: 0.0,
             friction: true
         })
-        .insert(ReloadTimer(Timer::from_seconds(2.0, true)))
-        .insert(Magazine(weapons::MAGAZINE_SIZE))
+        .insert(HolsteredAmmo::default())
         .insert(Attackable(TargetPriority::High))
         .insert(BoxCollider {
             size: Vec2::new(10.0, 10.0)
         })
-        .insert(InventoryItems{
-            items: vec![
-                Item{
-                    quantity: 30,
-                    item_type: ItemTypes::WoodFence
-                },
-                Item{
-                    quantity: 30,
-                    item_type: ItemTypes::TurretBlock
-                },
-                Item{
-                    quantity: 30,
-                    item_type: ItemTypes::TripMine
-                },
-                Item{
-                    quantity: 30,
-                    item_type: ItemTypes::Fence
-                },
-                Item{
-                    quantity: 30,
-                    item_type: ItemTypes::WindMill
-                },
-                Item{
-                    quantity: 30,
-                    item_type: ItemTypes::Wheat
-                },
-                Item{
-                    quantity: 2,
-                    item_type: ItemTypes::MiningRig
-                },
-                Item{
-                    quantity: 2,
-                    item_type: ItemTypes::CraftingTable
-                },
-                Item{
-                    quantity: 2,
-                    item_type: ItemTypes::Chest
-                }
-                ]
-        })
-        .insert(EntityHealth{val: 100.0, func_destruct: player_destruct});
-
-    // SPAWN HEALTBAR
-    commands
-        .spawn_bundle(SpriteBundle {
-            sprite: Sprite{
-                color: Color::rgb(0.0,1.0,0.0),
-                custom_size: Some(Vec2::new(100.0,10.0)),
-                anchor: Anchor::CenterLeft,
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert_bundle(TransformBundle{
-            local: Transform::from_xyz(-400.0, 300.0, 10.0),
-            ..Default::default()
-        })
-        .insert(HealthBar);
+        .insert(inv_items)
+        .insert(Hotbar::new(hotbar::HOTBAR_SLOTS))
+        .insert(EntityHealth{val: balance.player.max_health, func_destruct: player_destruct})
+        .insert(Hunger{val: balance.player.max_hunger})
+        .insert(PlayerInput::default())
+        .id()
 }
 
+type PlayerVitals<'a> = (&'a PlayerId, &'a mut Transform, &'a mut EntityHealth, &'a mut Hunger, Option<&'a LocalPlayer>);
+
+/// Ends the game when the player dies alone. With others connected the host respawns
+/// dead players instead.
 fn player_health(
     mut query: Query<&mut Sprite, With<HealthBar>>,
-    player_query: Query<&EntityHealth, With<Player>>,
-    mut state: ResMut<State<AppState>>
+    mut player_query: Query<PlayerVitals, With<Player>>,
+    mut state: ResMut<State<AppState>>,
+    network: Res<Network>,
+    balance: Res<Balance>
 ) {
+    for (id, mut trans, mut health, mut hunger, local) in player_query.iter_mut() {
+        if local.is_some() {
+            let mut sprite = query.single_mut();
+            sprite.custom_size = Some(Vec2 { x: health.val, y: 10.0 });
 
-    let mut sprite = query.single_mut();
-    let health = player_query.single();
-    sprite.custom_size = Some(Vec2 { x: health.val, y: 10.0 });
+            let fraction = health.val / balance.player.max_health;
+            sprite.color = Color::rgb(1.0 - fraction, fraction, 0.0);
+        }
 
-    sprite.color = Color::rgb(1.0 - health.val/100.0, health.val/100.0, 0.0);
+        if health.val > 0.0 || network.is_client() {
+            continue;
+        }
 
-    if player_query.single().val <= 0.0 {
-        state.set(AppState::GameDestruct).unwrap();
+        if !network.is_multiplayer() {
+            state.set(AppState::GameDestruct).unwrap();
+            return;
+        }
+
+        println!("Player {} died", id.0);
+
+        trans.translation = SPAWN_POS;
+        health.val = balance.player.max_health;
+        hunger.val = balance.player.max_hunger;
+    }
+}
+
+fn player_hunger(
+    mut query: Query<&mut Sprite, With<HungerBar>>,
+    mut player_query: Query<(&mut Hunger, &mut EntityHealth, Option<&LocalPlayer>), With<Player>>,
+    balance: Res<Balance>,
+    time: Res<Time>
+) {
+    let balance = &balance.player;
+
+    for (mut hunger, mut health, local) in player_query.iter_mut() {
+        hunger.val = (hunger.val - balance.hunger_drain*time.delta_seconds()).max(0.0);
+
+        if hunger.val <= 0.0 {
+            health.val -= balance.starvation_damage*time.delta_seconds();
+        } else if hunger.val >= balance.well_fed && health.val > 0.0 {
+            health.val = (health.val + balance.health_regen*time.delta_seconds()).min(balance.max_health);
+        }
+
+        if local.is_some() {
+            let mut sprite = query.single_mut();
+            sprite.custom_size = Some(Vec2 { x: hunger.val, y: 10.0 });
+        }
+    }
+}
+
+fn eat_food(
+    mut player_query: Query<(&PlayerInput, &mut Hunger, &mut InventoryItems), With<Player>>,
+    registry: Res<ItemRegistry>,
+    balance: Res<Balance>
+) {
+    for (input, mut hunger, mut inv_items) in player_query.iter_mut() {
+        if !input.actions.just_pressed(Action::Eat) || hunger.val >= balance.player.max_hunger {
+            continue;
+        }
+
+        // Eat the most nourishing food in the inventory
+        let food = inv_items.items()
+            .filter_map(|item| registry.get(item.item_type).food.map(|food| (item.item_type, food)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        match food {
+            Some((item_type, food)) => {
+                inv_items.tick_or_remove(item_type).unwrap();
+                hunger.val = (hunger.val + food).min(balance.player.max_hunger);
+            },
+            None => println!("There is nothing to eat")
+        }
     }
 }
 
 fn collect_items(
     mut commands: Commands,
     mut player_inv_q: Query<(&Transform, &mut InventoryItems), With<Player>>,
-    dropped_items_q: Query<(Entity, &Transform, &CollectableItem), With<CollectableItem>>
+    mut dropped_items_q: Query<(Entity, &Transform, &mut CollectableItem)>,
+    registry: Res<ItemRegistry>,
+    balance: Res<Balance>,
+    hash: Res<SpatialHash>
 ) {
+    let collect_dist = balance.player.collect_distance;
+
     for (player_trans, mut player_invitems) in player_inv_q.iter_mut() {
-        for (item_entity, item_trans, item_collable) in dropped_items_q.iter() {
-            if (player_trans.translation - item_trans.translation).length() <= COLL_DIST {
-                player_invitems.add_item(item_collable.item.clone());
-                commands.entity(item_entity).despawn();
+        for item_entity in hash.within_radius(player_trans.translation, collect_dist) {
+            let (item_entity, item_trans, mut item_collable) = match dropped_items_q.get_mut(item_entity) {
+                Ok(item) => item,
+                Err(_) => continue
+            };
+
+            if (player_trans.translation - item_trans.translation).length() > collect_dist {
+                continue;
+            }
+
+            let max_stack = registry.max_stack(item_collable.item.item_type);
+
+            // Whatever does not fit stays on the ground
+            match player_invitems.add_item(item_collable.item.clone(), max_stack) {
+                Ok(()) => commands.entity(item_entity).despawn(),
+                Err(leftover) => item_collable.item = leftover
             }
         }
     }
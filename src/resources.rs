@@ -1,3 +1,6 @@
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
 
 pub struct MouseLoc
@@ -18,21 +21,17 @@ pub struct GameAssets
     pub texture_atlas: Handle<TextureAtlas>
 }
 
-pub struct BlockSelection
-{
-    pub block: ItemTypes
-}
-
 pub struct InventoryAsset
 {
-    pub texture: Handle<TextureAtlas>
+    pub texture: Handle<TextureAtlas>,
+    pub font: Handle<Font>
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Item
 {
     pub item_type: ItemTypes,
-    pub quantity: i8
+    pub quantity: u16
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -43,19 +42,64 @@ pub enum AppState {
     InGame,
     Inventory,
     ExternalInventory,
+    Crafting,
+    Controls,
     GameDestruct,
     Paused,
 }
 
-pub struct GunTimer(pub Timer);
+// Separate streams so that e.g. shooting does not change the next wave or the map.
+pub struct GameRng
+{
+    pub seed: u64,
+    pub world: StdRng,
+    pub waves: StdRng,
+    pub gameplay: StdRng
+}
+
+impl GameRng
+{
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            world: StdRng::seed_from_u64(seed),
+            waves: StdRng::seed_from_u64(seed.wrapping_add(1)),
+            gameplay: StdRng::seed_from_u64(seed.wrapping_add(2))
+        }
+    }
+}
 
-pub struct ZombieTimer(pub Timer);
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WavePhase
+{
+    Build,
+    Attack
+}
 
-pub struct ZombieTimeoutTimer(pub Timer);
+/// The wave that is being built for or fought, numbered from 1.
+pub struct CurrentWave
+{
+    pub number: u32,
+    pub phase: WavePhase,
+    pub build_timer: Timer,
+    pub attack_timer: Timer,
+    pub spawn_timer: Timer,
+    pub pending: Vec<waves::PendingSpawn>
+}
 
-pub struct ZombieLevelTimer(pub Timer);
+/// Time of day, see `daynight`.
+pub struct Clock
+{
+    pub day: u32,
+    // Hours since midnight
+    pub hour: f32,
+    pub night: bool
+}
 
 pub enum InteractionType
 {
-    ChestOpen
+    ChestOpen,
+    CraftingOpen,
+    Harvest,
+    Turret
 }
\ No newline at end of file
@@ -0,0 +1,282 @@
+//! Gameplay tuning loaded from `assets/balance.ron`.
+//!
+//! Systems read the values from the `Balance` resource every frame, so edits to the
+//! file take effect while the game runs. The file is checked for changes once per
+//! `RELOAD_INTERVAL`. An invalid edit is reported and the previous values are kept.
+//! Values that are copied into an entity when it is spawned, like the starting ammo of
+//! a turret, only apply to entities spawned after the reload. The ammo capacity of the
+//! turrets is the exception, existing turrets follow it and drop any rounds above it.
+//!
+//! Distances are in pixels, speeds in pixels per second and times in seconds.
+
+use std::fs;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+pub const BALANCE_PATH: &str = "assets/balance.ron";
+
+const RELOAD_INTERVAL: f32 = 1.0;
+
+#[derive(Deserialize)]
+pub struct Balance
+{
+    pub player: PlayerBalance,
+    pub zombies: ZombieBalance,
+    pub waves: WaveBalance,
+    pub tripmine: TripMineBalance,
+    pub turret: TurretBalance,
+    pub fence: FenceBalance,
+    pub wheat: WheatBalance,
+    pub power: PowerBalance,
+    pub mining: MiningBalance,
+    pub animals: AnimalBalance
+}
+
+#[derive(Deserialize)]
+pub struct PlayerBalance
+{
+    pub move_speed: f32,
+    pub acceleration: f32,
+    pub max_health: f32,
+    pub max_hunger: f32,
+    // Hunger lost per second
+    pub hunger_drain: f32,
+    // Health lost per second while starving
+    pub starvation_damage: f32,
+    // Health regenerates while the hunger is at least `well_fed`
+    pub well_fed: f32,
+    pub health_regen: f32,
+    pub collect_distance: f32,
+    pub interaction_distance: f32,
+    pub build_distance: f32
+}
+
+#[derive(Deserialize)]
+pub struct ZombieBalance
+{
+    pub acceleration: f32,
+    // Speed while wandering without a target, the archetypes set the speed when chasing
+    pub idle_speed: f32,
+    pub spit_lifetime: f32
+}
+
+#[derive(Deserialize)]
+pub struct WaveBalance
+{
+    // Distance from the base at which zombies spawn
+    pub start_distance: f32
+}
+
+#[derive(Deserialize)]
+pub struct TripMineBalance
+{
+    pub trigger_distance: f32,
+    pub blast_radius: f32,
+    // Damage per second to everything within the blast radius
+    pub explosion_damage: f32,
+    pub explosion_time: f32
+}
+
+#[derive(Deserialize)]
+pub struct TurretBalance
+{
+    pub ammo_capacity: u16,
+    pub start_ammo: u16,
+    pub range: f32,
+    pub bullet_speed: f32,
+    pub bullet_damage: f32,
+    // Radians between the widest shots
+    pub spread: f32
+}
+
+#[derive(Deserialize)]
+pub struct FenceBalance
+{
+    // Top speed of anything walking through a fence
+    pub slow_speed: f32
+}
+
+#[derive(Deserialize)]
+pub struct WheatBalance
+{
+    // Seconds per growth state
+    pub grow_time: f32,
+    pub harvest_wheat: u16,
+    pub harvest_seeds: u16,
+    pub withered_seeds: u16
+}
+
+#[derive(Deserialize)]
+pub struct PowerBalance
+{
+    pub radius: f32
+}
+
+#[derive(Deserialize)]
+pub struct MiningBalance
+{
+    // Seconds a rig at full power takes to mine one ore
+    pub iron_mine_time: f32,
+    pub coal_mine_time: f32,
+    // Ore a full deposit holds
+    pub iron_capacity: u32,
+    pub coal_capacity: u32,
+    // Seconds for a deposit to regrow one ore
+    pub deposit_regen_time: f32
+}
+
+impl MiningBalance
+{
+    pub fn mine_time(&self, ore: OreKind) -> f32 {
+        match ore {
+            OreKind::Iron => self.iron_mine_time,
+            OreKind::Coal => self.coal_mine_time
+        }
+    }
+
+    pub fn capacity(&self, ore: OreKind) -> u32 {
+        match ore {
+            OreKind::Iron => self.iron_capacity,
+            OreKind::Coal => self.coal_capacity
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AnimalBalance
+{
+    pub speed: f32,
+    pub acceleration: f32,
+    // Animals flee from zombies and the player closer than this
+    pub react_distance: f32
+}
+
+pub fn read_balance(path: &str) -> Result<Balance, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let balance: Balance = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    let mut errors: Vec<String> = Vec::new();
+
+    let positive = [
+        ("player.move_speed", balance.player.move_speed),
+        ("player.acceleration", balance.player.acceleration),
+        ("player.max_health", balance.player.max_health),
+        ("player.max_hunger", balance.player.max_hunger),
+        ("player.collect_distance", balance.player.collect_distance),
+        ("player.interaction_distance", balance.player.interaction_distance),
+        ("player.build_distance", balance.player.build_distance),
+        ("zombies.acceleration", balance.zombies.acceleration),
+        ("zombies.spit_lifetime", balance.zombies.spit_lifetime),
+        ("waves.start_distance", balance.waves.start_distance),
+        ("tripmine.trigger_distance", balance.tripmine.trigger_distance),
+        ("tripmine.explosion_time", balance.tripmine.explosion_time),
+        ("turret.range", balance.turret.range),
+        ("turret.bullet_speed", balance.turret.bullet_speed),
+        ("wheat.grow_time", balance.wheat.grow_time),
+        ("mining.iron_mine_time", balance.mining.iron_mine_time),
+        ("mining.coal_mine_time", balance.mining.coal_mine_time),
+        ("mining.deposit_regen_time", balance.mining.deposit_regen_time),
+        ("animals.acceleration", balance.animals.acceleration)
+    ];
+
+    for (name, value) in positive {
+        if value <= 0.0 {
+            errors.push(format!("{} has to be positive", name));
+        }
+    }
+
+    let not_negative = [
+        ("player.hunger_drain", balance.player.hunger_drain),
+        ("player.starvation_damage", balance.player.starvation_damage),
+        ("player.health_regen", balance.player.health_regen),
+        ("zombies.idle_speed", balance.zombies.idle_speed),
+        ("tripmine.blast_radius", balance.tripmine.blast_radius),
+        ("tripmine.explosion_damage", balance.tripmine.explosion_damage),
+        ("turret.bullet_damage", balance.turret.bullet_damage),
+        ("turret.spread", balance.turret.spread),
+        ("fence.slow_speed", balance.fence.slow_speed),
+        ("power.radius", balance.power.radius),
+        ("animals.speed", balance.animals.speed),
+        ("animals.react_distance", balance.animals.react_distance)
+    ];
+
+    for (name, value) in not_negative {
+        if value < 0.0 {
+            errors.push(format!("{} can not be negative", name));
+        }
+    }
+
+    if !(0.0..=balance.player.max_hunger).contains(&balance.player.well_fed) {
+        errors.push("player.well_fed has to be between 0 and player.max_hunger".to_string());
+    }
+
+    if balance.turret.start_ammo > balance.turret.ammo_capacity {
+        errors.push("turret.start_ammo can not be more than turret.ammo_capacity".to_string());
+    }
+
+    if balance.mining.iron_capacity == 0 || balance.mining.coal_capacity == 0 {
+        errors.push("mining.iron_capacity and mining.coal_capacity have to be at least 1".to_string());
+    }
+
+    if errors.is_empty() {
+        Ok(balance)
+    } else {
+        Err(format!("Invalid balance in {}:\n{}", path, errors.join("\n")))
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+pub struct BalanceWatcher
+{
+    pub modified: Option<SystemTime>,
+    pub timer: Timer
+}
+
+pub struct BalancePlugin;
+
+impl Plugin for BalancePlugin
+{
+    fn build(&self, app: &mut App) {
+        let balance = read_balance(BALANCE_PATH).unwrap_or_else(|e| panic!("{}", e));
+
+        app.insert_resource(balance)
+        .insert_resource(BalanceWatcher {
+            modified: modified(BALANCE_PATH),
+            timer: Timer::from_seconds(RELOAD_INTERVAL, true)
+        })
+        .add_system(reload_balance);
+    }
+}
+
+fn reload_balance(
+    mut balance: ResMut<Balance>,
+    mut watcher: ResMut<BalanceWatcher>,
+    time: Res<Time>
+) {
+    if !watcher.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let modified = modified(BALANCE_PATH);
+
+    if modified == watcher.modified {
+        return;
+    }
+
+    watcher.modified = modified;
+
+    match read_balance(BALANCE_PATH) {
+        Ok(new_balance) => {
+            *balance = new_balance;
+            eprintln!("Reloaded {}", BALANCE_PATH);
+        },
+        Err(e) => eprintln!("{}\nKeeping the previous balance", e)
+    }
+}
@@ -0,0 +1,313 @@
+//! Input actions and their rebindable key and mouse bindings.
+//!
+//! Gameplay systems read `Input<Action>` instead of raw `KeyCode`s and `MouseButton`s.
+//! It is updated at the start of every frame from the bindings in `Controls`, which are
+//! loaded from the user config at `controls.ron` and changed on the controls screen.
+//! Actions that are missing in the file keep their default bindings:
+//!
+//! ```text
+//! (
+//!     bindings: {
+//!         MoveUp: [Key(Up)],
+//!         Fire: [Mouse(Left), Key(Space)],
+//!     },
+//! )
+//! ```
+//!
+//! Two actions can only share a binding if they are never used at the same time.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use bevy::input::InputSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub const CONTROLS_PATH: &str = "controls.ron";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action
+{
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Build,
+    Interact,
+    OpenInventory,
+    Reload,
+    Eat,
+    SwitchWeapon,
+    // Cycles what the closest turret shoots at
+    TurretMode,
+    Hotbar1,
+    Hotbar2,
+    Hotbar3,
+    Hotbar4,
+    Hotbar5,
+    Hotbar6,
+    Hotbar7,
+    Hotbar8,
+    Hotbar9,
+    Hotbar10,
+    DragStack,
+    QuickMove,
+    SplitStack,
+    Craft,
+    QuickSave,
+    QuickLoad,
+    StartGame,
+    OpenControls
+}
+
+/// Where an action is used. Actions of different contexts can share a binding.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Context
+{
+    Game,
+    // The inventory and crafting screens
+    Menus,
+    MainMenu
+}
+
+impl Action
+{
+    pub const ALL: [Action; 30] = [
+        Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight,
+        Action::Fire, Action::Build, Action::Interact, Action::OpenInventory,
+        Action::Reload, Action::Eat, Action::SwitchWeapon, Action::TurretMode,
+        Action::Hotbar1, Action::Hotbar2, Action::Hotbar3, Action::Hotbar4, Action::Hotbar5,
+        Action::Hotbar6, Action::Hotbar7, Action::Hotbar8, Action::Hotbar9, Action::Hotbar10,
+        Action::DragStack, Action::QuickMove, Action::SplitStack, Action::Craft,
+        Action::QuickSave, Action::QuickLoad, Action::StartGame, Action::OpenControls
+    ];
+
+    pub const HOTBAR: [Action; 10] = [
+        Action::Hotbar1, Action::Hotbar2, Action::Hotbar3, Action::Hotbar4, Action::Hotbar5,
+        Action::Hotbar6, Action::Hotbar7, Action::Hotbar8, Action::Hotbar9, Action::Hotbar10
+    ];
+
+    pub fn contexts(self) -> &'static [Context] {
+        match self {
+            // Also close the inventory and crafting screens again
+            Action::Interact | Action::OpenInventory => &[Context::Game, Context::Menus],
+            Action::DragStack | Action::QuickMove | Action::SplitStack | Action::Craft => &[Context::Menus],
+            Action::StartGame | Action::OpenControls => &[Context::MainMenu],
+            _ => &[Context::Game]
+        }
+    }
+
+    pub fn shares_context(self, other: Action) -> bool {
+        self.contexts().iter().any(|context| other.contexts().contains(context))
+    }
+
+    pub fn default_bindings(self) -> Vec<Binding> {
+        use Binding::{Key, Mouse};
+
+        match self {
+            Action::MoveUp => vec![Key(KeyCode::W)],
+            Action::MoveDown => vec![Key(KeyCode::S)],
+            Action::MoveLeft => vec![Key(KeyCode::A)],
+            Action::MoveRight => vec![Key(KeyCode::D)],
+            Action::Fire => vec![Mouse(MouseButton::Left)],
+            Action::Build => vec![Mouse(MouseButton::Right)],
+            Action::Interact => vec![Key(KeyCode::E)],
+            Action::OpenInventory => vec![Key(KeyCode::I)],
+            Action::Reload => vec![Key(KeyCode::R)],
+            Action::Eat => vec![Key(KeyCode::F)],
+            Action::SwitchWeapon => vec![Key(KeyCode::Q)],
+            Action::TurretMode => vec![Key(KeyCode::T)],
+            Action::Hotbar1 => vec![Key(KeyCode::Key1)],
+            Action::Hotbar2 => vec![Key(KeyCode::Key2)],
+            Action::Hotbar3 => vec![Key(KeyCode::Key3)],
+            Action::Hotbar4 => vec![Key(KeyCode::Key4)],
+            Action::Hotbar5 => vec![Key(KeyCode::Key5)],
+            Action::Hotbar6 => vec![Key(KeyCode::Key6)],
+            Action::Hotbar7 => vec![Key(KeyCode::Key7)],
+            Action::Hotbar8 => vec![Key(KeyCode::Key8)],
+            Action::Hotbar9 => vec![Key(KeyCode::Key9)],
+            Action::Hotbar10 => vec![Key(KeyCode::Key0)],
+            Action::DragStack => vec![Mouse(MouseButton::Left)],
+            Action::QuickMove => vec![Key(KeyCode::LShift), Key(KeyCode::RShift)],
+            Action::SplitStack => vec![Key(KeyCode::LControl), Key(KeyCode::RControl)],
+            Action::Craft => vec![Mouse(MouseButton::Right)],
+            Action::QuickSave => vec![Key(KeyCode::F5)],
+            Action::QuickLoad => vec![Key(KeyCode::F9)],
+            Action::StartGame => vec![Key(KeyCode::Space)],
+            Action::OpenControls => vec![Key(KeyCode::C)]
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding
+{
+    Key(KeyCode),
+    Mouse(MouseButton)
+}
+
+impl Binding
+{
+    pub fn pressed(&self, keys: &Input<KeyCode>, buttons: &Input<MouseButton>) -> bool {
+        match self {
+            Binding::Key(key) => keys.pressed(*key),
+            Binding::Mouse(button) => buttons.pressed(*button)
+        }
+    }
+
+    /// e.g. "E" or "Mouse Left"
+    pub fn display(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button)
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Controls
+{
+    pub bindings: BTreeMap<Action, Vec<Binding>>
+}
+
+impl Default for Controls
+{
+    fn default() -> Self {
+        Controls {
+            bindings: Action::ALL.iter().map(|action| (*action, action.default_bindings())).collect()
+        }
+    }
+}
+
+impl Controls
+{
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    /// e.g. "Space", "LShift / RShift" or "unbound"
+    pub fn display(&self, action: Action) -> String {
+        let bindings = self.bindings(action);
+
+        if bindings.is_empty() {
+            return "unbound".to_string();
+        }
+
+        bindings.iter().map(|binding| binding.display()).collect::<Vec<String>>().join(" / ")
+    }
+
+    /// The other actions that `binding` would clash with if it was bound to `action`.
+    pub fn conflicts(&self, action: Action, binding: Binding) -> Vec<Action> {
+        self.bindings.iter()
+            .filter(|(other, bindings)| **other != action && action.shares_context(**other) && bindings.contains(&binding))
+            .map(|(other, _)| *other)
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors: Vec<String> = Vec::new();
+
+        for (action, bindings) in self.bindings.iter() {
+            for binding in bindings {
+                // Every clash is found from both sides, only report it once
+                for other in self.conflicts(*action, *binding).into_iter().filter(|other| other > action) {
+                    errors.push(format!("{} is bound to both {:?} and {:?}", binding.display(), action, other));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+pub fn read_controls(path: &str) -> Result<Controls, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let file: Controls = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    let mut controls = Controls::default();
+    controls.bindings.extend(file.bindings);
+
+    controls.validate().map_err(|e| format!("Invalid controls in {}:\n{}", path, e))?;
+
+    Ok(controls)
+}
+
+pub fn write_controls(path: &str, controls: &Controls) -> Result<(), String> {
+    let serialized = ron::ser::to_string_pretty(controls, ron::ser::PrettyConfig::default())
+        .map_err(|e| format!("Could not serialize controls: {}", e))?;
+
+    fs::write(path, serialized).map_err(|e| format!("Could not write {}: {}", path, e))
+}
+
+/// Runs before all gameplay systems, after the raw input was updated.
+#[derive(SystemLabel, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ActionSystem;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin
+{
+    fn build(&self, app: &mut App) {
+        // Keep controls that were chosen before the plugin was added
+        if !app.world.contains_resource::<Controls>() {
+            let controls = if fs::metadata(CONTROLS_PATH).is_ok() {
+                read_controls(CONTROLS_PATH).unwrap_or_else(|e| {
+                    eprintln!("{}\nUsing the default controls", e);
+                    Controls::default()
+                })
+            } else {
+                Controls::default()
+            };
+
+            app.insert_resource(controls);
+        }
+
+        app.init_resource::<Input<Action>>()
+        .add_system_to_stage(CoreStage::PreUpdate, update_actions.label(ActionSystem).after(InputSystem))
+        .add_system_to_stage(CoreStage::PreUpdate, local_player_input.after(ActionSystem));
+    }
+}
+
+fn update_actions(
+    controls: Res<Controls>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut actions: ResMut<Input<Action>>
+) {
+    actions.clear();
+
+    for action in Action::ALL {
+        let pressed = controls.bindings(action).iter().any(|binding| binding.pressed(&keys, &buttons));
+
+        if pressed {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+}
+
+/// Drives the player of this machine with the local controls. The player stands still
+/// while a menu is open, the menus read `Input<Action>` themselves.
+fn local_player_input(
+    actions: Res<Input<Action>>,
+    mouseloc: Res<MouseLoc>,
+    state: Res<State<AppState>>,
+    mut player_query: Query<&mut PlayerInput, With<LocalPlayer>>
+) {
+    for mut input in player_query.iter_mut() {
+        input.actions = if state.current() == &AppState::InGame {
+            actions.clone()
+        } else {
+            Input::default()
+        };
+        input.cursor = mouseloc.get_vec3();
+    }
+}
@@ -0,0 +1,248 @@
+//! Day and night.
+//!
+//! The `Clock` resource advances by a full day every `day_length` seconds. Between `dusk`
+//! and `dawn` it is night: the world darkens, zombies of a wave spawn faster, windmills
+//! produce less and animals stop wandering. `NightFell` and `DayBroke` are sent when the
+//! night starts and ends. The cycle is defined in `assets/daynight.ron`, with the times of
+//! day in hours:
+//!
+//! ```text
+//! (
+//!     day_length: 600.0,
+//!     start_hour: 8.0,
+//!     dawn: 6.0,
+//!     dusk: 20.0,
+//!     twilight: 1.0,
+//!     max_darkness: 0.7,
+//!     night_spawn_rate: 2.0,
+//!     night_generation: 0.5,
+//! )
+//! ```
+
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::{prelude::*, utils::destruct_cleanup};
+
+pub const DAYNIGHT_PATH: &str = "assets/daynight.ron";
+
+const HOURS_PER_DAY: f32 = 24.0;
+const OVERLAY_SIZE: f32 = 4000.0;
+
+pub struct DayNightPlugin;
+
+impl Plugin for DayNightPlugin
+{
+    fn build(&self, app: &mut App) {
+        let cycle = read_day_cycle(DAYNIGHT_PATH).unwrap_or_else(|e| panic!("{}", e));
+
+        app.insert_resource(Clock::new(&cycle))
+        .insert_resource(cycle)
+        .add_system_set(SystemSet::on_enter(AppState::GameSetup)
+            .with_system(daynight_setup))
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(advance_clock)
+            .with_system(night_overlay.after(advance_clock))
+            .with_system(clock_text.after(advance_clock)))
+        .add_system_set(SystemSet::on_enter(AppState::GameDestruct)
+            .with_system(destruct_cleanup::<ClockText>));
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DayCycle
+{
+    // Seconds of a full day and night
+    pub day_length: f32,
+    pub start_hour: f32,
+    pub dawn: f32,
+    pub dusk: f32,
+    // Hours it takes to get fully dark after dusk, and fully light before dawn
+    pub twilight: f32,
+    pub max_darkness: f32,
+    pub night_spawn_rate: f32,
+    pub night_generation: f32
+}
+
+impl DayCycle
+{
+    pub fn is_night(&self, hour: f32) -> bool {
+        (hour - self.dusk).rem_euclid(HOURS_PER_DAY) < self.night_hours()
+    }
+
+    /// Opacity of the night overlay, from 0 during the day up to `max_darkness`.
+    pub fn darkness(&self, hour: f32) -> f32 {
+        if !self.is_night(hour) {
+            return 0.0;
+        }
+
+        let since_dusk = (hour - self.dusk).rem_euclid(HOURS_PER_DAY);
+        let until_dawn = self.night_hours() - since_dusk;
+
+        self.max_darkness * (since_dusk.min(until_dawn) / self.twilight).min(1.0)
+    }
+
+    /// Multiplier of the spawn rate of wave zombies.
+    pub fn spawn_rate(&self, night: bool) -> f32 {
+        if night { self.night_spawn_rate } else { 1.0 }
+    }
+
+    /// Multiplier of the power produced by windmills.
+    pub fn generation(&self, night: bool) -> f32 {
+        if night { self.night_generation } else { 1.0 }
+    }
+
+    fn night_hours(&self) -> f32 {
+        (self.dawn - self.dusk).rem_euclid(HOURS_PER_DAY)
+    }
+}
+
+impl Clock
+{
+    pub fn new(cycle: &DayCycle) -> Self {
+        Clock {
+            day: 1,
+            hour: cycle.start_hour,
+            night: cycle.is_night(cycle.start_hour)
+        }
+    }
+
+    /// e.g. "Day 2  21:30"
+    pub fn display(&self) -> String {
+        let minutes = (self.hour * 60.0) as u32;
+
+        format!("Day {}  {:02}:{:02}", self.day, minutes / 60, minutes % 60)
+    }
+}
+
+pub fn read_day_cycle(path: &str) -> Result<DayCycle, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let cycle: DayCycle = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    let mut errors: Vec<String> = Vec::new();
+    let valid_hour = |hour: f32| (0.0..HOURS_PER_DAY).contains(&hour);
+
+    if cycle.day_length <= 0.0 {
+        errors.push("day_length has to be positive".to_string());
+    }
+
+    if !valid_hour(cycle.start_hour) || !valid_hour(cycle.dawn) || !valid_hour(cycle.dusk) {
+        errors.push("start_hour, dawn and dusk have to be between 0 and 24".to_string());
+    }
+
+    if cycle.dawn == cycle.dusk {
+        errors.push("dawn and dusk can not be at the same hour".to_string());
+    }
+
+    if cycle.twilight <= 0.0 || cycle.twilight * 2.0 > cycle.night_hours() {
+        errors.push("twilight has to be positive and at most half of the night".to_string());
+    }
+
+    if !(0.0..=1.0).contains(&cycle.max_darkness) {
+        errors.push("max_darkness has to be between 0 and 1".to_string());
+    }
+
+    if cycle.night_spawn_rate <= 0.0 || cycle.night_generation < 0.0 {
+        errors.push("night_spawn_rate has to be positive and night_generation can not be negative".to_string());
+    }
+
+    if errors.is_empty() {
+        Ok(cycle)
+    } else {
+        Err(format!("Invalid day cycle in {}:\n{}", path, errors.join("\n")))
+    }
+}
+
+fn daynight_setup(
+    mut commands: Commands,
+    cycle: Res<DayCycle>,
+    inventory_asset: Res<InventoryAsset>
+) {
+    commands.insert_resource(Clock::new(&cycle));
+
+    // Between the world and the health bars
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite{
+                color: Color::rgba(0.0, 0.0, 0.05, 0.0),
+                custom_size: Some(Vec2::splat(OVERLAY_SIZE)),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert_bundle(TransformBundle{
+            local: Transform::from_xyz(0.0, 0.0, 9.0),
+            ..Default::default()
+        })
+        .insert(NightOverlay);
+
+    let text_style = TextStyle {
+        font: inventory_asset.font.clone(),
+        font_size: 16.0,
+        color: Color::WHITE
+    };
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::from_section("", text_style)
+                .with_alignment(TextAlignment::CENTER_LEFT),
+            transform: Transform::from_xyz(-400.0, 268.0, 10.0),
+            ..Default::default()
+        })
+        .insert(ClockText);
+}
+
+fn advance_clock(
+    mut clock: ResMut<Clock>,
+    cycle: Res<DayCycle>,
+    mut night_writer: EventWriter<NightFell>,
+    mut day_writer: EventWriter<DayBroke>,
+    time: Res<Time>
+) {
+    clock.hour += HOURS_PER_DAY * time.delta_seconds() / cycle.day_length;
+
+    if clock.hour >= HOURS_PER_DAY {
+        clock.hour -= HOURS_PER_DAY;
+        clock.day += 1;
+    }
+
+    let night = cycle.is_night(clock.hour);
+
+    if night != clock.night {
+        clock.night = night;
+
+        if night {
+            println!("Night falls on day {}", clock.day);
+            night_writer.send(NightFell { day: clock.day });
+        } else {
+            println!("Day {} breaks", clock.day);
+            day_writer.send(DayBroke { day: clock.day });
+        }
+    }
+}
+
+// The overlay only covers the screen, so it moves along with the camera
+fn night_overlay(
+    mut overlay_query: Query<(&mut Sprite, &mut Transform), With<NightOverlay>>,
+    camera_query: Query<&Transform, (With<MainCamera>, Without<NightOverlay>)>,
+    clock: Res<Clock>,
+    cycle: Res<DayCycle>
+) {
+    let center = camera_query.get_single().map_or(Vec3::ZERO, |trans| trans.translation);
+
+    for (mut sprite, mut trans) in overlay_query.iter_mut() {
+        sprite.color.set_a(cycle.darkness(clock.hour));
+        trans.translation = center.truncate().extend(trans.translation.z);
+    }
+}
+
+fn clock_text(
+    mut text_query: Query<&mut Text, With<ClockText>>,
+    clock: Res<Clock>
+) {
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = clock.display();
+    }
+}
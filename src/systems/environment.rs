@@ -1,7 +1,46 @@
+//! The endless world around the player.
+//!
+//! The world is split into chunks of `CHUNK_TILES` by `CHUNK_TILES` tiles, which are
+//! generated as they come within `LOAD_RADIUS` chunks of the player or the camera, and
+//! despawned again beyond `UNLOAD_RADIUS`. What is in a chunk only depends on the seed of
+//! the game: value noise decides where the forests, the ore deposits and the meadows with
+//! animals are.
+//!
+//! A chunk that the player changed, e.g. by building on it or cutting its trees, is kept in
+//! `WorldChunks` when it is unloaded and restored instead of generated when it comes back.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::ecs::system::SystemState;
+use rand::rngs::StdRng;
+
 use crate::{prelude::*, utils::entity_destruct};
 
-const NUM_TREES: u8 = 50;
-const NUM_ANIMALS: u8 = 25;
+pub const CHUNK_TILES: i32 = 16;
+pub const TILE_SIZE: f32 = 20.0;
+
+const LOAD_RADIUS: i32 = 2;
+// Larger than LOAD_RADIUS, so that walking along a chunk border does not reload chunks
+const UNLOAD_RADIUS: i32 = 3;
+
+const TREE_HEALTH: f32 = 200.0;
+// Width in tiles of the forests
+const FOREST_SCALE: f32 = 12.0;
+// Forest noise above which trees grow, and how fast they get denser above it
+const FOREST_LEVEL: f32 = 0.45;
+const FOREST_DENSITY: f32 = 0.4;
+const MAX_ANIMALS_PER_CHUNK: u32 = 3;
+// Width in tiles of the ore seams, and the noise above which a tile is a deposit
+const ORE_SCALE: f32 = 5.0;
+const ORE_LEVEL: f32 = 0.85;
+// A deposit of each ore just outside of the base, for the mining rigs the player starts with
+const START_DEPOSITS: [((i32, i32), OreKind); 2] = [((5, -4), OreKind::Iron), ((-5, -4), OreKind::Coal)];
+// Tiles around the origin that are kept free for the player and the first turret
+const CLEARING_TILES: i32 = 8;
+
+const GRASS_INDICES: [usize; 4] = [2, 3, 6, 7];
+
+pub type Chunk = (i32, i32);
 
 pub struct EnvironmentPlugin;
 
@@ -9,67 +48,319 @@ impl Plugin for EnvironmentPlugin
 {
     fn build(&self, app: &mut App)
     {
-        app.add_system_set(SystemSet::on_enter(AppState::GameSetup).with_system(spawn_environment));
+        app.insert_resource(WorldChunks::new(0))
+        .add_system_set(SystemSet::on_enter(AppState::GameSetup)
+            .with_system(environment_setup))
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(stream_chunks.exclusive_system()));
+    }
+}
+
+/// The chunks that are loaded, and the snapshots of the changed chunks that are not.
+pub struct WorldChunks
+{
+    pub seed: u64,
+    pub loaded: HashSet<Chunk>,
+    pub modified: HashMap<Chunk, save::ChunkSave>
+}
+
+impl WorldChunks
+{
+    pub fn new(seed: u64) -> Self {
+        WorldChunks {
+            seed,
+            loaded: HashSet::new(),
+            modified: HashMap::new()
+        }
+    }
+
+    /// Keeps the snapshot of a chunk, unless the chunk is as it was generated.
+    pub fn store(&mut self, chunk: Chunk, save: save::ChunkSave, mining: &balance::MiningBalance) {
+        let generated = generate_chunk(self.seed, chunk);
+
+        let pristine = save.blocks.is_empty()
+            && save.dropped.is_empty()
+            && save.trees.len() == generated.trees.len()
+            && save.trees.iter().all(|tree| tree.health >= TREE_HEALTH)
+            && save.deposits.len() == generated.deposits.len()
+            && save.deposits.iter().all(|deposit| deposit.quantity >= mining.capacity(deposit.ore))
+            // An animal that wandered off would otherwise be spawned again in its home chunk
+            && save.animals.len() == generated.animals.len();
+
+        if pristine {
+            self.modified.remove(&chunk);
+        } else {
+            self.modified.insert(chunk, save);
+        }
+    }
+}
+
+/// Contents of a chunk as it is generated from the seed.
+pub struct ChunkContent
+{
+    pub ground: Vec<(Vec3, usize)>,
+    pub trees: Vec<Vec3>,
+    pub deposits: Vec<(Vec3, OreKind)>,
+    pub animals: Vec<Vec3>,
+    // Continues where the generation stopped, for the looks of the animals
+    pub rng: StdRng
+}
+
+pub fn chunk_of(pos: Vec3) -> Chunk {
+    let tile = ((pos.x / TILE_SIZE).round() as i32, (pos.y / TILE_SIZE).round() as i32);
+
+    (tile.0.div_euclid(CHUNK_TILES), tile.1.div_euclid(CHUNK_TILES))
+}
+
+// Deterministic hash of a point on the lattice
+fn hash(seed: u64, x: i32, y: i32) -> u64 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    h ^= h >> 33;
+
+    h
+}
+
+// Between 0 and 1
+fn lattice(seed: u64, x: i32, y: i32) -> f32 {
+    (hash(seed, x, y) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Smooth value noise between 0 and 1, with features about `scale` tiles wide.
+pub fn value_noise(seed: u64, x: f32, y: f32, scale: f32) -> f32 {
+    let (x, y) = (x / scale, y / scale);
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sy) = (smooth(x - x0 as f32), smooth(y - y0 as f32));
+
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let bottom = lerp(lattice(seed, x0, y0), lattice(seed, x0 + 1, y0), sx);
+    let top = lerp(lattice(seed, x0, y0 + 1), lattice(seed, x0 + 1, y0 + 1), sx);
+
+    lerp(bottom, top, sy)
+}
+
+pub fn generate_chunk(seed: u64, chunk: Chunk) -> ChunkContent {
+    let mut rng = StdRng::seed_from_u64(hash(seed, chunk.0, chunk.1));
+    // A separate noise field, so that forests do not follow the hash of the chunk seeds
+    let forest_seed = seed.wrapping_add(1);
+    let iron_seed = seed.wrapping_add(2);
+    let coal_seed = seed.wrapping_add(3);
+
+    let mut content = ChunkContent {
+        ground: Vec::new(),
+        trees: Vec::new(),
+        deposits: Vec::new(),
+        animals: Vec::new(),
+        rng: StdRng::seed_from_u64(0)
+    };
+
+    let mut meadow = 0.0;
+
+    for tx in 0..CHUNK_TILES {
+        for ty in 0..CHUNK_TILES {
+            let tile = (chunk.0 * CHUNK_TILES + tx, chunk.1 * CHUNK_TILES + ty);
+            let pos = Vec3::new(tile.0 as f32 * TILE_SIZE, tile.1 as f32 * TILE_SIZE, 0.0);
+
+            content.ground.push((pos, GRASS_INDICES[rng.gen_range(0..GRASS_INDICES.len())]));
+
+            let forest = value_noise(forest_seed, tile.0 as f32, tile.1 as f32, FOREST_SCALE);
+            let tree_chance = (forest - FOREST_LEVEL).max(0.0) * FOREST_DENSITY;
+
+            meadow += 1.0 - forest;
+
+            let in_clearing = tile.0.abs() <= CLEARING_TILES && tile.1.abs() <= CLEARING_TILES;
+
+            let ore = if in_clearing {
+                START_DEPOSITS.iter().find(|(start, _)| *start == tile).map(|(_, ore)| *ore)
+            } else if value_noise(iron_seed, tile.0 as f32, tile.1 as f32, ORE_SCALE) > ORE_LEVEL {
+                Some(OreKind::Iron)
+            } else if value_noise(coal_seed, tile.0 as f32, tile.1 as f32, ORE_SCALE) > ORE_LEVEL {
+                Some(OreKind::Coal)
+            } else {
+                None
+            };
+
+            // Trees do not grow on deposits
+            if let Some(ore) = ore {
+                content.deposits.push((pos, ore));
+            } else if rng.gen::<f32>() < tree_chance && !in_clearing {
+                content.trees.push(pos + Vec3::Z * 3.0);
+            }
+        }
     }
+
+    // Animals graze where there are few trees
+    let meadow = meadow / (CHUNK_TILES * CHUNK_TILES) as f32;
+    let animals = (meadow * (MAX_ANIMALS_PER_CHUNK + 1) as f32 * rng.gen::<f32>()) as u32;
+
+    for _ in 0..animals.min(MAX_ANIMALS_PER_CHUNK) {
+        let tile = (chunk.0 * CHUNK_TILES + rng.gen_range(0..CHUNK_TILES), chunk.1 * CHUNK_TILES + rng.gen_range(0..CHUNK_TILES));
+
+        content.animals.push(Vec3::new(tile.0 as f32 * TILE_SIZE, tile.1 as f32 * TILE_SIZE, 3.0));
+    }
+
+    content.rng = rng;
+
+    content
 }
 
-fn spawn_environment(
+fn environment_setup(
     mut commands: Commands,
-    game_assets: Res<GameAssets>
+    game_rng: Res<GameRng>
 ) {
-    // Spawn grassy background!
-    let grass_indeces = [2,3,6,7];
-    let mut rng = rand::thread_rng();
+    // Chunks are loaded around the player once the game runs
+    commands.insert_resource(WorldChunks::new(game_rng.seed));
+}
 
-    let lower: i8 = -35;
-    let upper: i8 = 35;
+/// Loads the chunks around the player and the camera, and unloads those far away from both.
+fn stream_chunks(world: &mut World) {
+    let focus: Vec<Chunk> = world
+        .query_filtered::<&Transform, Or<(With<Player>, With<MainCamera>)>>()
+        .iter(world)
+        .map(|trans| chunk_of(trans.translation))
+        .collect();
 
-    for x in lower..upper
-    {
-        for y in lower..upper {
-
-            commands
-            .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: game_assets.texture_atlas.clone(),
-                sprite: TextureAtlasSprite {
-                    index: grass_indeces[rng.gen_range(0..4)],
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert_bundle(TransformBundle{
-                local: Transform::from_xyz(20.0 * (x as f32), 20.0 * (y as f32), 0.0),
-                ..Default::default()
-            });
+    let within = |chunk: &Chunk, radius: i32| focus.iter()
+        .any(|center| (chunk.0 - center.0).abs() <= radius && (chunk.1 - center.1).abs() <= radius);
+
+    let chunks = world.resource::<WorldChunks>();
+
+    let mut unload: Vec<Chunk> = chunks.loaded.iter()
+        .filter(|chunk| !within(chunk, UNLOAD_RADIUS))
+        .copied()
+        .collect();
 
+    let mut load: Vec<Chunk> = focus.iter()
+        .flat_map(|center| (-LOAD_RADIUS..=LOAD_RADIUS)
+            .flat_map(move |x| (-LOAD_RADIUS..=LOAD_RADIUS).map(move |y| (center.0 + x, center.1 + y))))
+        .filter(|chunk| !chunks.loaded.contains(chunk))
+        .collect();
+
+    // In a fixed order, so that a seeded run spawns the same entities every time
+    unload.sort();
+    load.sort();
+    load.dedup();
+
+    for chunk in unload {
+        unload_chunk(world, chunk);
+    }
+
+    for chunk in load {
+        load_chunk(world, chunk);
+    }
+}
+
+/// Keeps a snapshot of a loaded chunk if it was changed and despawns its entities.
+pub fn unload_chunk(world: &mut World, chunk: Chunk) {
+    let save = save::snapshot_chunk(world, chunk);
+
+    store_chunk(world, chunk, save);
+    world.resource_mut::<WorldChunks>().loaded.remove(&chunk);
+
+    let mut query = world.query_filtered::<(Entity, &Transform), Or<(
+        With<Ground>,
+        With<Tree>,
+        With<Animal>,
+        With<Deposit>,
+        With<BlockType>,
+        With<CollectableItem>
+    )>>();
+
+    let entities: Vec<Entity> = query.iter(world)
+        .filter(|(_, trans)| chunk_of(trans.translation) == chunk)
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in entities {
+        // Children (e.g. windmill blades) may already be gone with their parent.
+        if world.get_entity(entity).is_some() {
+            despawn_with_children_recursive(world, entity);
         }
     }
+}
 
-    // Spawn trees
+/// Keeps the snapshot of a chunk, see `WorldChunks::store`.
+pub fn store_chunk(world: &mut World, chunk: Chunk, save: save::ChunkSave) {
+    world.resource_scope(|world, mut chunks: Mut<WorldChunks>| {
+        chunks.store(chunk, save, &world.resource::<Balance>().mining);
+    });
+}
 
-    for _ in 0..NUM_TREES{
-        let xtile = rng.gen_range(-20..20);
-        let ytile = rng.gen_range(-20..20);
+type ChunkParams<'w, 's> = (Commands<'w, 's>, Res<'w, GameAssets>, Res<'w, ItemRegistry>, Res<'w, Balance>);
 
-        spawn_tree(&mut commands, Vec3::new((xtile as f32)*20.0, (ytile as f32)*20.0, 3.0), &game_assets);
+/// Spawns a chunk, from its snapshot if it was changed before and from the seed otherwise.
+pub fn load_chunk(world: &mut World, chunk: Chunk) {
+    let mut chunks = world.resource_mut::<WorldChunks>();
+    chunks.loaded.insert(chunk);
+
+    let mut content = generate_chunk(chunks.seed, chunk);
+    let snapshot = chunks.modified.get(&chunk).cloned();
+
+    let mut system_state: SystemState<ChunkParams> = SystemState::new(world);
+    let (mut commands, game_assets, registry, balance) = system_state.get_mut(world);
+
+    for (pos, sprite) in content.ground.iter() {
+        spawn_ground(&mut commands, *pos, *sprite, &game_assets);
     }
 
-    // Spawn animals
+    if snapshot.is_none() {
+        for pos in content.trees.iter() {
+            spawn_tree(&mut commands, *pos, &game_assets);
+        }
 
-    for _ in 0..NUM_ANIMALS {
-        let xtile = rng.gen_range(-20..20);
-        let ytile = rng.gen_range(-20..20);
+        for (pos, ore) in content.deposits.iter() {
+            miningrig::spawn_deposit(&mut commands, *pos, *ore, balance.mining.capacity(*ore), &game_assets, &registry, &balance);
+        }
 
-        animals::spawn_animal(&mut commands, Vec3::new((xtile as f32)*20.0, (ytile as f32)*20.0, 3.0), &game_assets);
+        for pos in content.animals.iter() {
+            animals::spawn_animal(&mut commands, *pos, &game_assets, &mut content.rng);
+        }
     }
 
+    system_state.apply(world);
+
+    if let Some(snapshot) = snapshot {
+        save::restore_chunk(world, &snapshot);
+    }
 }
 
-fn spawn_tree(
+pub fn spawn_ground(
+    commands: &mut Commands,
+    spawn_pos: Vec3,
+    sprite_index: usize,
+    game_assets: &Res<GameAssets>
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: game_assets.texture_atlas.clone(),
+            sprite: TextureAtlasSprite {
+                index: sprite_index,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert_bundle(TransformBundle{
+            local: Transform::from_translation(spawn_pos),
+            ..Default::default()
+        })
+        .insert(Ground)
+        .id()
+}
+
+pub fn spawn_tree(
     commands: &mut Commands,
     spawn_pos: Vec3,
     game_asset: &Res<GameAssets>
-) {
+) -> Entity {
 
     (*commands)
         .spawn_bundle(SpriteSheetBundle {
@@ -85,16 +376,18 @@ fn spawn_tree(
             local: Transform::from_translation(spawn_pos),
             ..Default::default()
         })
+        .insert(Tree)
         .insert(StaticEntity)
         .insert(Attackable(TargetPriority::Low))
-        .insert(EntityHealth{val: 200.0, func_destruct: entity_destruct})
+        .insert(EntityHealth{val: TREE_HEALTH, func_destruct: entity_destruct})
         .insert(DropsItem{
             item: Item {
-                item_type: ItemTypes::WallBlock,
+                item_type: ItemTypes::Wood,
                 quantity: 6
             }
         })
         .insert(BoxCollider {
             size: Vec2::new(20.0, 20.0)
-        });
-}
\ No newline at end of file
+        })
+        .id()
+}
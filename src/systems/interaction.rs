@@ -2,12 +2,12 @@ use crate::prelude::*;
 
 pub struct InteractionPlugin;
 
-pub const INTERACTION_DISTANCE: f32 = 60.0;
-
 impl Plugin for InteractionPlugin
 {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(chest_open));
+        app.add_system_set(SystemSet::on_update(AppState::InGame)
+            .with_system(chest_open)
+            .with_system(crafting_open));
     }
 }
 
@@ -15,22 +15,34 @@ fn chest_open(
     mut chest_event: EventReader<ChestInteractEvent>,
     mut commands: Commands,
     mut state: ResMut<State<AppState>>,
-    chest_entities: Query<(Entity, &InventoryItems)>
+    chest_entities: Query<Entity, With<InventoryItems>>,
+    network: Res<Network>
 ) {
     for cevent in chest_event.iter() {
-        let res = chest_entities.iter().find(|p| p.0 == cevent.chest_entity);
+        // The contents of chests are not sent to clients
+        if network.is_client() {
+            println!("Only the host can open chests");
+            break;
+        }
 
-        if res.is_some() {
-            let (entity_origin, inv_items) = res.unwrap();
 
+        if let Ok(entity_origin) = chest_entities.get(cevent.chest_entity) {
             commands
             .spawn()
-            .insert(ExternalInventory {entity_origin: entity_origin})
-            .insert(inv_items.clone());
+            .insert(ExternalInventory {entity_origin});
 
             state.set(AppState::ExternalInventory).unwrap();
 
             break;
         }
     }
-}
\ No newline at end of file
+}
+
+fn crafting_open(
+    mut crafting_event: EventReader<CraftingInteractEvent>,
+    mut state: ResMut<State<AppState>>
+) {
+    if crafting_event.iter().next().is_some() {
+        state.set(AppState::Crafting).unwrap();
+    }
+}
@@ -1,11 +1,37 @@
+//! Inventory frames for the player and for opened chests.
+//!
+//! Every frame shows the `InventoryItems` of its owner and is rebuilt when those
+//! change. Stacks are dragged between slots with `Action::DragStack`, holding
+//! `SplitStack` picks up half of the stack. Clicking while holding `QuickMove` moves
+//! the whole stack to the other open inventory. A dragged stack stays in its slot until it is dropped,
+//! so closing the inventory mid-drag loses nothing. Dropping a player stack on the
+//! hotbar binds its slot to the hotbar.
+
 use bevy::sprite::Anchor;
 
 use crate::{prelude::*, utils::destruct_cleanup};
 
 pub struct InventoryPlugin;
 
-#[derive(Component, Clone)]
-pub struct InvName(pub String);
+/// Slots of the player and chest inventories, one per cell of the 5x3 inventory frame.
+pub const INVENTORY_SLOTS: usize = 15;
+const SLOT_COLUMNS: usize = 5;
+const SLOT_STEP: f32 = 21.0;
+
+/// The entity whose `InventoryItems` an inventory frame shows.
+#[derive(Component, Clone, Copy)]
+pub struct InvOwner(pub Entity);
+
+#[derive(Component)]
+pub struct DragIcon;
+
+/// A stack picked up with the mouse. It is only taken out of its slot once it is dropped.
+pub struct DraggedStack
+{
+    pub owner: Entity,
+    pub slot: usize,
+    pub item: Item
+}
 
 impl Plugin for InventoryPlugin
 {
@@ -16,73 +42,67 @@ impl Plugin for InventoryPlugin
             .with_system(player_inventory_init));
 
         app.add_system_set(SystemSet::on_update(AppState::Inventory)
+            .with_system(refresh_inventory)
+            .with_system(inventory_mouse)
+            .with_system(drag_icon_follow)
             .with_system(inventory_handler));
 
         app.add_system_set(SystemSet::on_exit(AppState::Inventory)
-            .with_system(destruct_cleanup::<Inventory>));
+            .with_system(destruct_cleanup::<Inventory>)
+            .with_system(drag_cleanup));
 
         // External inventory
         app.add_system_set(SystemSet::on_enter(AppState::ExternalInventory)
             .with_system(external_inventory_init));
 
         app.add_system_set(SystemSet::on_update(AppState::ExternalInventory)
-            .with_system(change_inventory)
+            .with_system(refresh_inventory)
+            .with_system(inventory_mouse)
+            .with_system(drag_icon_follow)
             .with_system(inventory_handler));
 
         app.add_system_set(SystemSet::on_exit(AppState::ExternalInventory)
             .with_system(destruct_cleanup::<Inventory>)
-            .with_system(destruct_cleanup::<ExternalInventory>));
+            .with_system(destruct_cleanup::<ExternalInventory>)
+            .with_system(drag_cleanup));
     }
 }
 
 pub fn player_inventory_init(
     mut commands: Commands,
-    inv_items_query: Query<&InventoryItems, With<Player>>,
-    game_assets: Res<GameAssets>,
+    player_query: Query<Entity, With<LocalPlayer>>,
     inv_texture: Res<InventoryAsset>
 ) {
-    // Spawn all inventory items
     inventory_spawn(&mut commands,
-        &inv_items_query.single(),
-        &game_assets,
+        player_query.single(),
         &inv_texture,
-        Vec3::new(0.0, 0.0, 30.0),
-        InvName(String::from("Player")));
+        Vec3::new(0.0, 0.0, 30.0));
 }
 
 pub fn external_inventory_init(
     mut commands: Commands,
-    player_inv_items_query: Query<&InventoryItems, With<Player>>,
-    external_inv_items_query: Query<&InventoryItems, With<ExternalInventory>>,
-    game_assets: Res<GameAssets>,
+    player_query: Query<Entity, With<LocalPlayer>>,
+    external_inv_query: Query<&ExternalInventory>,
     inv_texture: Res<InventoryAsset>
 ) {
-    // Spawn all inventory items
     inventory_spawn(&mut commands,
-        &player_inv_items_query.single(),
-        &game_assets,
+        player_query.single(),
         &inv_texture,
-        Vec3::new(0.0, 55.0, 30.0),
-        InvName(String::from("Player")));
-    
-    // Spawn all external inventory items
+        Vec3::new(0.0, 55.0, 30.0));
+
     inventory_spawn(&mut commands,
-        &external_inv_items_query.single(),
-        &game_assets,
+        external_inv_query.single().entity_origin,
         &inv_texture,
-        Vec3::new(0.0, -55.0, 30.0),
-        InvName(String::from("External")));
+        Vec3::new(0.0, -55.0, 30.0));
 }
 
+/// Spawns an empty frame, `refresh_inventory` fills in the items.
 pub fn inventory_spawn(
     commands: &mut Commands,
-    inv_items: &InventoryItems,
-    game_assets: &Res<GameAssets>,
+    owner: Entity,
     inv_texture: &Res<InventoryAsset>,
-    spawn_pos: Vec3,
-    inv_name: InvName
+    spawn_pos: Vec3
 ) {
-    // Spawn all inventory items
     commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: inv_texture.texture.clone(),
@@ -98,89 +118,292 @@ pub fn inventory_spawn(
             ..Default::default()
         })
         .insert(Inventory)
-        .insert(inv_name.clone())
-        .add_children(|parent| {
-            // Start from top left
-            let xstep: f32 = 21.0;
-            let ystep: f32 = 21.0;
-
-            let offy: f32 = xstep;
-            let offx: f32 = -2.0*ystep;
-
-            for i in 0..inv_items.items.len() {
-                parent.spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: game_assets.texture_atlas.clone(),
-                    sprite: TextureAtlasSprite {
-                        index: inv_items.items[i].item_type as usize,
-                        anchor: Anchor::Center,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert_bundle(TransformBundle{
-                    local: Transform::from_xyz(((i%5) as f32)*xstep + offx, offy - f32::floor((i as f32)/5.0) * ystep, 30.0),
-                    ..Default::default()
-                })
-                .insert(InventoryItem{item: inv_items.items[i].clone()});
-            }
-        });
+        .insert(InvOwner(owner));
 }
 
-pub fn change_inventory(
-    mouse: Res<Input<MouseButton>>,
-    mouse_loc: Res<MouseLoc>,
-    inv_item_query: Query<(&Parent, &GlobalTransform, &InventoryItem), (With<InventoryItem>, Without<Player>)>,
-    inventory_frame_query: Query<(Entity, &InvName), With<Inventory>>,
-    mut all_inventory_entities: Query<(Entity, &mut InventoryItems), (Without<Inventory>, Without<Player>, Without<ExternalInventory>)>,
-    mut player_inv: Query<(Entity, &mut InventoryItems), With<Player>>,
-    mut state: ResMut<State<AppState>>,
-    external_inv: Query<&ExternalInventory, (With<ExternalInventory>, Without<Player>)>
+/// Offset of a slot from the center of its frame, starting from the top left.
+fn slot_offset(slot: usize) -> Vec2 {
+    let col = (slot % SLOT_COLUMNS) as f32;
+    let row = (slot / SLOT_COLUMNS) as f32;
+
+    Vec2::new(col * SLOT_STEP - 2.0 * SLOT_STEP, SLOT_STEP - row * SLOT_STEP)
+}
+
+/// The frame owner and slot under `pos`, if any.
+fn slot_at(frames: &Query<(&InvOwner, &GlobalTransform)>, pos: Vec3) -> Option<(Entity, usize)> {
+    frames.iter().find_map(|(owner, glob_trans)| {
+        (0..INVENTORY_SLOTS)
+            .find(|slot| {
+                let offset = pos.truncate() - glob_trans.translation().truncate() - slot_offset(*slot);
+                offset.x.abs() <= SLOT_STEP / 2.0 && offset.y.abs() <= SLOT_STEP / 2.0
+            })
+            .map(|slot| (owner.0, slot))
+    })
+}
+
+pub fn spawn_icon(
+    parent: &mut ChildBuilder,
+    game_assets: &GameAssets,
+    registry: &ItemRegistry,
+    text_style: &TextStyle,
+    item: &Item,
+    transform: Transform,
+    marker: impl Bundle
 ) {
-    if !player_inv.is_empty() && !external_inv.is_empty() && !inventory_frame_query.is_empty() {
-        let player_invframe_entity = inventory_frame_query.iter().find(|p| p.1.0 == "Player").unwrap().0;
-        let external_invframe_entity = inventory_frame_query.iter().find(|p| p.1.0 == "External").unwrap().0;
-        
-        let mut player_invitems = player_inv.single_mut().1;
-        let external_inventory = external_inv.single();
-
-        let external_origin_entity = external_inventory.entity_origin;
-        let mut external_origin_invitems = all_inventory_entities.iter_mut()
-            .find(|p| p.0.eq(&external_origin_entity))
-            .unwrap().1;
-
-        if mouse.just_pressed(MouseButton::Right) {
-            for (parent_entity, glob_trans, invitem) in inv_item_query.iter() {
-
-                let mut length = glob_trans.translation() - mouse_loc.get_vec3();
-                length.z = 0.0;
-
-                if length.length() < 10.0 {
-                    if parent_entity.get().eq(&player_invframe_entity)  {
-                            println!("Removing from player inventory!");
-
-                            external_origin_invitems.add_item(invitem.item.clone());
-                            player_invitems.remove_item(invitem.item.clone());
-                    } else if parent_entity.get().eq(&external_invframe_entity) {
-                            println!("Removing from external inventory!");
-
-                            external_origin_invitems.remove_item(invitem.item.clone());
-                            player_invitems.add_item(invitem.item.clone());
+    parent.spawn_bundle(SpriteSheetBundle {
+        texture_atlas: game_assets.texture_atlas.clone(),
+        sprite: TextureAtlasSprite {
+            index: registry.sprite_index(item.item_type),
+            anchor: Anchor::Center,
+            ..Default::default()
+        },
+        transform,
+        ..Default::default()
+    })
+    .insert_bundle(marker)
+    .with_children(|icon| {
+        if item.quantity > 1 {
+            icon.spawn_bundle(Text2dBundle {
+                text: Text::from_section(item.quantity.to_string(), text_style.clone())
+                    .with_alignment(TextAlignment::BOTTOM_RIGHT),
+                transform: Transform::from_xyz(10.0, -10.0, 1.0),
+                ..Default::default()
+            });
+        }
+    });
+}
+
+pub fn quantity_style(inv_asset: &InventoryAsset) -> TextStyle {
+    TextStyle {
+        font: inv_asset.font.clone(),
+        font_size: 8.0,
+        color: Color::WHITE
+    }
+}
+
+/// Rebuilds the item icons of new frames and of frames whose owner's items changed.
+#[allow(clippy::too_many_arguments)]
+pub fn refresh_inventory(
+    mut commands: Commands,
+    frames: Query<(Entity, &InvOwner)>,
+    new_frames: Query<(), Added<InvOwner>>,
+    inventories: Query<&InventoryItems>,
+    changed: Query<(), Changed<InventoryItems>>,
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    inv_asset: Res<InventoryAsset>
+) {
+    let text_style = quantity_style(&inv_asset);
+
+    for (frame, owner) in frames.iter() {
+        if new_frames.get(frame).is_err() && changed.get(owner.0).is_err() {
+            continue;
+        }
+
+        let inv_items = match inventories.get(owner.0) {
+            Ok(inv_items) => inv_items,
+            Err(_) => continue
+        };
+
+        commands.entity(frame).despawn_descendants();
+        commands.entity(frame)
+            .with_children(|parent| {
+                for (slot, item) in inv_items.slots.iter().enumerate() {
+                    if let Some(item) = item {
+                        let offset = slot_offset(slot);
+
+                        spawn_icon(parent, &game_assets, &registry, &text_style, item,
+                            Transform::from_xyz(offset.x, offset.y, 1.0),
+                            (InventoryItem{item: item.clone(), slot},));
                     }
+                }
+            });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn inventory_mouse(
+    mut commands: Commands,
+    actions: Res<Input<Action>>,
+    mouse_loc: Res<MouseLoc>,
+    frames: Query<(&InvOwner, &GlobalTransform)>,
+    mut inventories: Query<&mut InventoryItems>,
+    hotbar_slots: Query<(&hotbar::HotbarSlot, &GlobalTransform)>,
+    mut hotbars: Query<&mut Hotbar>,
+    drag_icons: Query<Entity, With<DragIcon>>,
+    dragged: Option<Res<DraggedStack>>,
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    inv_asset: Res<InventoryAsset>
+) {
+    let pos = mouse_loc.get_vec3();
 
-                    break;
+    if actions.just_released(Action::DragStack) {
+        if let Some(dragged) = dragged {
+            for icon in drag_icons.iter() {
+                commands.entity(icon).despawn_recursive();
+            }
+
+            commands.remove_resource::<DraggedStack>();
+
+            if let Some((owner, slot)) = slot_at(&frames, pos) {
+                if (owner, slot) != (dragged.owner, dragged.slot) {
+                    drop_stack(&mut inventories, &dragged, owner, slot, registry.max_stack(dragged.item.item_type));
+                }
+            } else if let Some(hotbar_slot) = hotbar::slot_at(&hotbar_slots, pos) {
+                // Bind the inventory slot to the hotbar, the stack itself stays where it is
+                if let Ok(mut hotbar) = hotbars.get_mut(dragged.owner) {
+                    hotbar.slots[hotbar_slot] = Some(dragged.slot);
                 }
             }
         }
+
+        return;
+    }
+
+    if !actions.just_pressed(Action::DragStack) || dragged.is_some() {
+        return;
+    }
+
+    let (owner, slot) = match slot_at(&frames, pos) {
+        Some(found) => found,
+        None => return
+    };
+
+    let stack = match inventories.get(owner).ok().and_then(|inv_items| inv_items.slots[slot].clone()) {
+        Some(stack) => stack,
+        None => return
+    };
+
+    if actions.pressed(Action::QuickMove) {
+        if let Some((other, _)) = frames.iter().find(|(frame_owner, _)| frame_owner.0 != owner) {
+            quick_move(&mut inventories, owner, slot, other.0, registry.max_stack(stack.item_type));
+        }
+
+        return;
+    }
+
+    let quantity = if actions.pressed(Action::SplitStack) {
+        stack.quantity.div_ceil(2)
     } else {
-        println!("It is empty!");
+        stack.quantity
+    };
+
+    let item = Item { item_type: stack.item_type, quantity };
+    let text_style = quantity_style(&inv_asset);
+
+    commands
+        .spawn_bundle(TransformBundle {
+            local: Transform::from_xyz(pos.x, pos.y, 40.0),
+            ..Default::default()
+        })
+        .insert_bundle(VisibilityBundle::default())
+        .insert(DragIcon)
+        .insert(Inventory)
+        .with_children(|parent| {
+            spawn_icon(parent, &game_assets, &registry, &text_style, &item, Transform::default(), ());
+        });
+
+    commands.insert_resource(DraggedStack { owner, slot, item });
+}
+
+/// Puts items back into the slot they were taken from.
+fn return_to_slot(inv_items: &mut InventoryItems, slot: usize, item: Item) {
+    match &mut inv_items.slots[slot] {
+        Some(stack) => stack.quantity += item.quantity,
+        empty => *empty = Some(item)
+    }
+}
+
+/// Drops a dragged stack on a slot. It fills an empty slot, tops up a stack of the same
+/// item or swaps with a different item if the whole stack was dragged. Whatever is
+/// left goes back to where the stack came from.
+fn drop_stack(
+    inventories: &mut Query<&mut InventoryItems>,
+    dragged: &DraggedStack,
+    owner: Entity,
+    slot: usize,
+    max_stack: u16
+) {
+    let (taken, whole_stack) = match inventories.get_mut(dragged.owner) {
+        Ok(mut from) => match from.take_from_slot(dragged.slot, &dragged.item) {
+            Some(taken) => (taken, from.slots[dragged.slot].is_none()),
+            None => return
+        },
+        Err(_) => return
+    };
+
+    let returned = match inventories.get_mut(owner) {
+        Ok(mut to) => match &mut to.slots[slot] {
+            Some(stack) if stack.item_type == taken.item_type => {
+                let moved = taken.quantity.min(max_stack.saturating_sub(stack.quantity));
+                stack.quantity += moved;
+
+                (moved < taken.quantity).then(|| Item { item_type: taken.item_type, quantity: taken.quantity - moved })
+            },
+            Some(stack) if whole_stack => Some(std::mem::replace(stack, taken)),
+            Some(_) => Some(taken),
+            empty => {
+                *empty = Some(taken);
+                None
+            }
+        },
+        Err(_) => Some(taken)
+    };
+
+    if let Some(returned) = returned {
+        if let Ok(mut from) = inventories.get_mut(dragged.owner) {
+            return_to_slot(&mut from, dragged.slot, returned);
+        }
     }
 }
 
+/// Moves a whole stack into another inventory, whatever does not fit stays in its slot.
+fn quick_move(
+    inventories: &mut Query<&mut InventoryItems>,
+    owner: Entity,
+    slot: usize,
+    other: Entity,
+    max_stack: u16
+) {
+    let stack = match inventories.get_mut(owner).ok().and_then(|mut from| from.slots[slot].take()) {
+        Some(stack) => stack,
+        None => return
+    };
+
+    let leftover = match inventories.get_mut(other) {
+        Ok(mut to) => to.add_item(stack, max_stack).err(),
+        Err(_) => Some(stack)
+    };
+
+    if let Some(leftover) = leftover {
+        println!("The inventory is full!");
+
+        if let Ok(mut from) = inventories.get_mut(owner) {
+            return_to_slot(&mut from, slot, leftover);
+        }
+    }
+}
+
+pub fn drag_icon_follow(
+    mouse_loc: Res<MouseLoc>,
+    mut drag_icons: Query<&mut Transform, With<DragIcon>>
+) {
+    for mut trans in drag_icons.iter_mut() {
+        trans.translation.x = mouse_loc.x;
+        trans.translation.y = mouse_loc.y;
+    }
+}
+
+fn drag_cleanup(mut commands: Commands) {
+    commands.remove_resource::<DraggedStack>();
+}
+
 pub fn inventory_handler(
-    mut keys: ResMut<Input<KeyCode>>,
+    mut actions: ResMut<Input<Action>>,
     mut state: ResMut<State<AppState>>
 ) {
-    if keys.clear_just_pressed(KeyCode::I) {
+    if actions.clear_just_pressed(Action::OpenInventory) {
         state.set(AppState::InGame).unwrap();
     }
-}
\ No newline at end of file
+}
@@ -4,15 +4,43 @@ pub mod inventory;
 pub mod environment;
 pub mod physics;
 pub mod interaction;
+pub mod save;
+pub mod registry;
+pub mod navigation;
+pub mod spatial;
+pub mod power;
+pub mod daynight;
+pub mod controls;
+pub mod balance;
+pub mod network;
+
+pub use registry::ItemRegistry;
+pub use navigation::{NavGrid, FlowField};
+pub use spatial::SpatialHash;
+pub use power::PowerGrid;
+pub use daynight::DayCycle;
+pub use environment::WorldChunks;
+pub use controls::{Action, Controls};
+pub use balance::Balance;
+pub use network::Network;
 
 pub struct SystemsPlugin;
 
 impl Plugin for SystemsPlugin
 {
     fn build(&self, app: &mut App) {
-        app.add_plugin(inventory::InventoryPlugin)
+        app.add_plugin(controls::ControlsPlugin)
+        .add_plugin(balance::BalancePlugin)
+        .add_plugin(registry::RegistryPlugin)
+        .add_plugin(inventory::InventoryPlugin)
         .add_plugin(environment::EnvironmentPlugin)
+        .add_plugin(spatial::SpatialPlugin)
         .add_plugin(physics::PhysicsPlugin)
-        .add_plugin(interaction::InteractionPlugin);
+        .add_plugin(navigation::NavigationPlugin)
+        .add_plugin(power::PowerPlugin)
+        .add_plugin(daynight::DayNightPlugin)
+        .add_plugin(interaction::InteractionPlugin)
+        .add_plugin(save::SavePlugin)
+        .add_plugin(network::NetworkPlugin);
     }
 }
\ No newline at end of file
@@ -0,0 +1,383 @@
+//! Navigation of zombies around obstacles.
+//!
+//! `NavGrid` keeps track of the cells of the 20px build grid that are blocked by a
+//! `StaticEntity` with a `BoxCollider`, and is updated as blocks are placed or destroyed.
+//! `FlowField` holds the walking distance from every cell around the player to the
+//! nearest player, so that every zombie can follow it downhill without a search of its own.
+
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap}};
+
+use crate::prelude::*;
+
+pub const CELL_SIZE: f32 = 20.0;
+
+// Number of cells from the player to the edge of the flow field
+const FIELD_RADIUS: i32 = 64;
+const ORTHOGONAL_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+const UNREACHABLE: u32 = u32::MAX;
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1),
+    (1, 1), (1, -1), (-1, 1), (-1, -1)
+];
+
+pub type Cell = (i32, i32);
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavGrid>()
+        .init_resource::<FlowField>()
+        .add_system_set(SystemSet::on_enter(AppState::GameSetup)
+            .with_system(navigation_setup))
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(update_nav_grid)
+            .with_system(update_flow_field.after(update_nav_grid))
+            .with_system(follow_flow_field.after(update_flow_field)));
+    }
+}
+
+pub fn world_to_cell(pos: Vec3) -> Cell {
+    ((pos.x / CELL_SIZE).round() as i32, (pos.y / CELL_SIZE).round() as i32)
+}
+
+pub fn cell_to_world(cell: Cell) -> Vec3 {
+    Vec3::new(cell.0 as f32 * CELL_SIZE, cell.1 as f32 * CELL_SIZE, 0.0)
+}
+
+#[derive(Default)]
+pub struct NavGrid
+{
+    // Number of colliders covering each blocked cell
+    blocked: HashMap<Cell, u32>,
+    entity_cells: HashMap<Entity, Vec<Cell>>,
+    pub changed: bool
+}
+
+impl NavGrid
+{
+    pub fn is_blocked(&self, cell: Cell) -> bool {
+        self.blocked.contains_key(&cell)
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3, size: Vec2) {
+        // Cells whose center lies inside of the collider
+        let half = size / 2.0 - Vec2::splat(0.1);
+        let min = ((pos.x - half.x) / CELL_SIZE).ceil() as i32;
+        let max = ((pos.x + half.x) / CELL_SIZE).floor() as i32;
+        let min_y = ((pos.y - half.y) / CELL_SIZE).ceil() as i32;
+        let max_y = ((pos.y + half.y) / CELL_SIZE).floor() as i32;
+
+        let mut cells = Vec::new();
+
+        for x in min..=max {
+            for y in min_y..=max_y {
+                *self.blocked.entry((x, y)).or_insert(0) += 1;
+                cells.push((x, y));
+            }
+        }
+
+        self.entity_cells.insert(entity, cells);
+        self.changed = true;
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let cells = match self.entity_cells.remove(&entity) {
+            Some(cells) => cells,
+            None => return
+        };
+
+        for cell in cells {
+            if let Some(count) = self.blocked.get_mut(&cell) {
+                *count -= 1;
+
+                if *count == 0 {
+                    self.blocked.remove(&cell);
+                }
+            }
+        }
+
+        self.changed = true;
+    }
+
+    fn can_step(&self, from: Cell, offset: (i32, i32)) -> bool {
+        let to = (from.0 + offset.0, from.1 + offset.1);
+
+        if self.is_blocked(to) {
+            return false;
+        }
+
+        // Do not cut corners of obstacles when moving diagonally
+        offset.0 == 0 || offset.1 == 0
+            || (!self.is_blocked((from.0 + offset.0, from.1)) && !self.is_blocked((from.0, from.1 + offset.1)))
+    }
+}
+
+/// Walking distances to the nearest player, for the cells within `FIELD_RADIUS` of the
+/// player of this machine.
+pub struct FlowField
+{
+    pub origin: Cell,
+    // Cells of the players the distances were computed to
+    pub sources: Vec<Cell>,
+    distances: Vec<u32>
+}
+
+impl Default for FlowField
+{
+    fn default() -> Self {
+        let size = (2 * FIELD_RADIUS + 1) as usize;
+
+        FlowField {
+            origin: (0, 0),
+            sources: Vec::new(),
+            distances: vec![UNREACHABLE; size * size]
+        }
+    }
+}
+
+impl FlowField
+{
+    fn index(&self, cell: Cell) -> Option<usize> {
+        let x = cell.0 - self.origin.0 + FIELD_RADIUS;
+        let y = cell.1 - self.origin.1 + FIELD_RADIUS;
+        let size = 2 * FIELD_RADIUS + 1;
+
+        if x < 0 || y < 0 || x >= size || y >= size {
+            return None;
+        }
+
+        Some((y * size + x) as usize)
+    }
+
+    fn distance(&self, cell: Cell) -> u32 {
+        self.index(cell).map_or(UNREACHABLE, |i| self.distances[i])
+    }
+
+    pub fn compute(&mut self, grid: &NavGrid, origin: Cell, sources: &[Cell]) {
+        self.origin = origin;
+        self.sources = sources.to_vec();
+        self.distances.iter_mut().for_each(|d| *d = UNREACHABLE);
+
+        let mut queue = BinaryHeap::new();
+
+        for source in sources {
+            if let Some(i) = self.index(*source) {
+                self.distances[i] = 0;
+                queue.push(Reverse((0, *source)));
+            }
+        }
+
+        while let Some(Reverse((dist, cell))) = queue.pop() {
+            if dist > self.distance(cell) {
+                continue;
+            }
+
+            for offset in NEIGHBOURS {
+                if !grid.can_step(cell, offset) {
+                    continue;
+                }
+
+                let next = (cell.0 + offset.0, cell.1 + offset.1);
+                let cost = if offset.0 == 0 || offset.1 == 0 { ORTHOGONAL_COST } else { DIAGONAL_COST };
+
+                if let Some(i) = self.index(next) {
+                    if dist + cost < self.distances[i] {
+                        self.distances[i] = dist + cost;
+                        queue.push(Reverse((dist + cost, next)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The neighbouring cell that is closest to a player, if `cell` is in the field and
+    /// has a path to a player.
+    pub fn next_cell(&self, grid: &NavGrid, cell: Cell) -> Option<Cell> {
+        let mut best = (self.distance(cell), None);
+
+        for offset in NEIGHBOURS {
+            let next = (cell.0 + offset.0, cell.1 + offset.1);
+            let dist = self.distance(next);
+
+            // A zombie pushed into a blocked cell may step out of it in any direction
+            if dist < best.0 && (grid.is_blocked(cell) || grid.can_step(cell, offset)) {
+                best = (dist, Some(next));
+            }
+        }
+
+        best.1
+    }
+
+    /// Walking distance in pixels from `pos` to the nearest player, if there is a known path.
+    pub fn walking_distance(&self, pos: Vec3) -> Option<f32> {
+        let dist = self.distance(world_to_cell(pos));
+
+        if dist == UNREACHABLE {
+            None
+        } else {
+            Some(dist as f32 / ORTHOGONAL_COST as f32 * CELL_SIZE)
+        }
+    }
+
+    /// `None` if `cell` is outside of the field, otherwise whether it has a path to a player.
+    pub fn has_path(&self, grid: &NavGrid, cell: Cell) -> Option<bool> {
+        self.index(cell)?;
+
+        Some(self.distance(cell) != UNREACHABLE || self.next_cell(grid, cell).is_some())
+    }
+}
+
+fn navigation_setup(
+    mut commands: Commands
+) {
+    // Entities of the last game may have been removed while the grid was not updated
+    commands.insert_resource(NavGrid::default());
+    commands.insert_resource(FlowField::default());
+}
+
+fn update_nav_grid(
+    mut grid: ResMut<NavGrid>,
+    added_query: Query<(Entity, &Transform, &BoxCollider), Added<StaticEntity>>,
+    removed: RemovedComponents<StaticEntity>
+) {
+    for entity in removed.iter() {
+        grid.remove(entity);
+    }
+
+    for (entity, trans, collider) in added_query.iter() {
+        grid.insert(entity, trans.translation, collider.size);
+    }
+}
+
+fn update_flow_field(
+    mut grid: ResMut<NavGrid>,
+    mut field: ResMut<FlowField>,
+    player_query: Query<(&Transform, Option<&LocalPlayer>), With<Player>>
+) {
+    let sources: Vec<Cell> = player_query.iter()
+        .map(|(trans, _)| world_to_cell(trans.translation))
+        .collect();
+
+    // The field is centered on the player of this machine, zombies far from it walk straight
+    let origin = match player_query.iter().find(|(_, local)| local.is_some()) {
+        Some((trans, _)) => world_to_cell(trans.translation),
+        None => return
+    };
+
+    if grid.changed || origin != field.origin || sources != field.sources {
+        field.compute(&grid, origin, &sources);
+        grid.changed = false;
+    }
+}
+
+/// Whether a zombie is after one of the players, and so can use the flow field, which only
+/// leads to the players. Zombies without a target entity roam around the nearest player.
+pub fn chases_player(pf: &Pathfinder, players: &[Vec3]) -> bool {
+    !pf.target_entity || players.iter().any(|player| (*player - pf.target).length() <= 5.0)
+}
+
+fn follow_flow_field(
+    mut zombie_query: Query<(&Transform, &mut Pathfinder), With<Zombie>>,
+    static_query: Query<&Transform, (With<StaticEntity>, Without<Pathfinder>)>,
+    player_query: Query<&Transform, (With<Player>, Without<Pathfinder>)>,
+    grid: Res<NavGrid>,
+    field: Res<FlowField>
+) {
+    let players: Vec<Vec3> = player_query.iter().map(|trans| trans.translation).collect();
+    let static_vec: Vec<&Transform> = static_query.iter().collect();
+
+    for (trans, mut pf) in zombie_query.iter_mut() {
+        let target_trans = Transform::from_translation(pf.target);
+
+        pf.waypoint = if chases_player(&pf, &players)
+            && zombie::is_hindered(&static_vec, &trans, &&target_trans) {
+            field.next_cell(&grid, world_to_cell(trans.translation)).map(cell_to_world)
+        } else {
+            None
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const BLOCK: Vec2 = Vec2::new(CELL_SIZE, CELL_SIZE);
+
+    fn grid_with(cells: &[Cell]) -> NavGrid {
+        let mut grid = NavGrid::default();
+
+        for (i, cell) in cells.iter().enumerate() {
+            grid.insert(Entity::from_raw(i as u32), cell_to_world(*cell), BLOCK);
+        }
+
+        grid
+    }
+
+    #[test]
+    fn overlapping_colliders_are_counted() {
+        let mut grid = NavGrid::default();
+        let block = Entity::from_raw(0);
+        let wide = Entity::from_raw(1);
+
+        grid.insert(block, Vec3::ZERO, BLOCK);
+        grid.insert(wide, Vec3::new(10.0, 0.0, 0.0), Vec2::new(2.0 * CELL_SIZE, CELL_SIZE));
+        assert!(grid.is_blocked((0, 0)));
+        assert!(grid.is_blocked((1, 0)));
+        assert!(!grid.is_blocked((2, 0)));
+
+        grid.remove(block);
+        assert!(grid.is_blocked((0, 0)), "the wide collider still covers the cell");
+
+        grid.remove(wide);
+        grid.remove(wide);
+        assert!(!grid.is_blocked((0, 0)));
+        assert!(!grid.is_blocked((1, 0)));
+    }
+
+    #[test]
+    fn diagonal_steps_do_not_cut_corners() {
+        let grid = grid_with(&[(1, 0)]);
+
+        assert!(!grid.can_step((0, 0), (1, 0)));
+        assert!(!grid.can_step((0, 0), (1, 1)));
+        assert!(!grid.can_step((0, 0), (1, -1)));
+        assert!(grid.can_step((0, 0), (0, 1)));
+        assert!(grid.can_step((0, 0), (-1, 1)));
+    }
+
+    #[test]
+    fn flow_field_leads_around_a_wall() {
+        let wall: Vec<Cell> = (-3..=3).map(|y| (2, y)).collect();
+        let grid = grid_with(&wall);
+
+        let mut field = FlowField::default();
+        field.compute(&grid, (0, 0), &[(0, 0)]);
+
+        // Four cells straight through the wall, but the way around is longer
+        let dist = field.walking_distance(cell_to_world((4, 0))).unwrap();
+        assert!(dist > 4.0 * CELL_SIZE, "{}", dist);
+
+        let next = field.next_cell(&grid, (3, 0)).unwrap();
+        assert!(!grid.is_blocked(next));
+        assert_eq!(field.has_path(&grid, (4, 0)), Some(true));
+    }
+
+    #[test]
+    fn walled_in_cells_have_no_path() {
+        let ring: Vec<Cell> = NEIGHBOURS.iter().map(|(x, y)| (10 + x, 10 + y)).collect();
+        let grid = grid_with(&ring);
+
+        let mut field = FlowField::default();
+        field.compute(&grid, (0, 0), &[(0, 0)]);
+
+        assert_eq!(field.walking_distance(cell_to_world((10, 10))), None);
+        assert_eq!(field.has_path(&grid, (10, 10)), Some(false));
+        assert_eq!(field.has_path(&grid, (FIELD_RADIUS + 1, 0)), None);
+    }
+}
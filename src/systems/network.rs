@@ -0,0 +1,1107 @@
+//! LAN co-op for up to `MAX_PLAYERS` players, with the host as the authority.
+//!
+//! Start the host with `cargo run -- --host` and let the others join it with
+//! `cargo run -- --join 192.168.1.20`, or `--join 127.0.0.1` on the same machine.
+//! Both take an optional port, `DEFAULT_PORT` otherwise. A client takes over the seed of
+//! the host, so both generate the same map, and starts its game from the main menu.
+//!
+//! Only the host runs the waves and decides what happens to zombies, blocks and items.
+//! Every frame a client sends its actions and cursor, which drive its player on the host,
+//! and the host sends back a `Snapshot` of the players, zombies, blocks and dropped items
+//! `SNAPSHOT_RATE` times a second. A client moves its own player ahead of the snapshots
+//! and is only corrected when it drifts more than `CORRECTION_DIST` away.
+//!
+//! Clients can rearrange their inventory and hotbar, craft, harvest and refill turrets,
+//! which the host repeats for them. Chests, dropping stacks and saving are left to the
+//! host. Trees, deposits and animals are generated from the seed on every machine and
+//! are not kept in sync. The world goes on while a player has the inventory or crafting
+//! menu open, only that player stands still, see `gameplay_running`.
+//!
+//! Messages are RON, one per line, over TCP.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::ecs::schedule::ShouldRun;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::prelude::*;
+use crate::blocks::craftingtable::Recipes;
+
+pub const DEFAULT_PORT: u16 = 7777;
+pub const MAX_PLAYERS: usize = 4;
+pub const HOST_ID: PlayerId = PlayerId(0);
+
+const SNAPSHOT_RATE: f32 = 20.0;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// Distance in pixels the own player of a client may be off before it is moved back
+const CORRECTION_DIST: f32 = 30.0;
+// Snapshots are skipped for a client that has this many bytes left to receive
+const MAX_PENDING: usize = 1 << 20;
+// Longest message in bytes, a connection that sends a longer one is dropped
+const MAX_MESSAGE: usize = 1 << 22;
+
+#[derive(Serialize, Deserialize)]
+pub enum ClientMessage
+{
+    Input { pressed: Vec<Action>, cursor: Vec3 },
+    Hotbar(Hotbar),
+    // The same items in a different order, e.g. after dragging stacks around
+    Arrange(Vec<Option<Item>>),
+    // Index into `Recipes`
+    Craft(usize),
+    Interact(NetId),
+    // Next targeting mode of a turret
+    TurretMode(NetId)
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum ServerMessage
+{
+    Welcome { id: PlayerId, seed: u64 },
+    Full,
+    Snapshot(Snapshot)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot
+{
+    pub day: u32,
+    pub hour: f32,
+    pub players: Vec<PlayerState>,
+    pub zombies: Vec<ZombieState>,
+    pub blocks: Vec<BlockState>,
+    pub dropped: Vec<DroppedState>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerState
+{
+    pub id: PlayerId,
+    pub pos: Vec3,
+    pub health: f32,
+    pub hunger: f32,
+    pub items: Vec<Option<Item>>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ZombieState
+{
+    pub id: NetId,
+    // Name of the zombie archetype
+    pub kind: String,
+    pub stats: ZombieStats,
+    pub pos: Vec3,
+    pub health: f32
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlockState
+{
+    pub id: NetId,
+    pub block: ItemTypes,
+    pub pos: Vec3,
+    pub health: Option<f32>,
+    pub wheat_state: Option<u8>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DroppedState
+{
+    pub id: NetId,
+    pub pos: Vec3,
+    pub scale: Vec3,
+    pub item: Item
+}
+
+/// A TCP stream that is never waited on. Messages are queued by `send` and written out
+/// by `flush` as far as the socket takes them.
+pub struct Connection
+{
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>
+}
+
+impl Connection
+{
+    pub fn new(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nonblocking(true)
+            .and_then(|_| stream.set_nodelay(true))
+            .map_err(|e| format!("Could not set up the connection: {}", e))?;
+
+        Ok(Connection {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new()
+        })
+    }
+
+    pub fn send<T: Serialize>(&mut self, message: &T) {
+        // The messages only hold plain data, and RON escapes line breaks in strings
+        let line = ron::to_string(message).unwrap();
+
+        self.outgoing.extend_from_slice(line.as_bytes());
+        self.outgoing.push(b'\n');
+    }
+
+    /// Bytes that were queued but not sent yet.
+    pub fn pending(&self) -> usize {
+        self.outgoing.len()
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err("Connection closed".to_string()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(format!("Could not send: {}", e))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every complete message that arrived since the last call.
+    pub fn receive<T: DeserializeOwned>(&mut self) -> Result<Vec<T>, String> {
+        let mut buffer = [0; 4096];
+        let mut closed = false;
+
+        // Whatever does not fit is left in the socket until the next call
+        while self.incoming.len() < MAX_MESSAGE {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                },
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(format!("Could not receive: {}", e))
+            }
+        }
+
+        let mut messages = Vec::new();
+
+        while let Some(end) = self.incoming.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            let text = String::from_utf8_lossy(&line[..end]);
+
+            messages.push(ron::from_str(&text).map_err(|e| format!("Invalid message: {}", e))?);
+        }
+
+        if self.incoming.len() >= MAX_MESSAGE {
+            return Err(format!("Message longer than {} bytes", MAX_MESSAGE));
+        }
+
+        // The last messages before the other side hung up still count
+        if closed && messages.is_empty() {
+            return Err("Connection closed".to_string());
+        }
+
+        Ok(messages)
+    }
+}
+
+pub struct RemoteClient
+{
+    pub id: PlayerId,
+    pub connection: Connection,
+    // Spawned once the client sends its first input in a game
+    pub entity: Option<Entity>
+}
+
+pub struct Server
+{
+    pub listener: TcpListener,
+    pub clients: Vec<RemoteClient>,
+    pub snapshot_timer: Timer
+}
+
+pub struct Client
+{
+    pub id: PlayerId,
+    pub connection: Connection,
+    // Only the newest snapshot is applied
+    pub latest: Option<Snapshot>,
+    // Items of the own player in the last snapshot
+    pub host_items: Vec<Option<Item>>
+}
+
+#[derive(Default)]
+pub enum Network
+{
+    #[default]
+    Offline,
+    Host(Server),
+    Client(Client)
+}
+
+impl Network
+{
+    pub fn is_client(&self) -> bool {
+        matches!(self, Network::Client(_))
+    }
+
+    /// Whether other players are connected, or this is one of them.
+    pub fn is_multiplayer(&self) -> bool {
+        match self {
+            Network::Offline => false,
+            Network::Host(server) => !server.clients.is_empty(),
+            Network::Client(_) => true
+        }
+    }
+
+    /// Id of the player controlled from this machine.
+    pub fn local_id(&self) -> PlayerId {
+        match self {
+            Network::Client(client) => client.id,
+            _ => HOST_ID
+        }
+    }
+
+    /// Lets the host repeat something the player of this client did, otherwise does nothing.
+    pub fn send_to_host(&mut self, message: ClientMessage) {
+        if let Network::Client(client) = self {
+            client.connection.send(&message);
+        }
+    }
+}
+
+/// Run criterion of the gameplay systems. Alone, the game is paused by any menu. With
+/// other players the inventory and crafting menus only pause the input of the own player.
+pub fn gameplay_running(
+    state: Res<State<AppState>>,
+    network: Res<Network>
+) -> ShouldRun {
+    match state.current() {
+        AppState::InGame => ShouldRun::Yes,
+        AppState::Inventory | AppState::ExternalInventory | AppState::Crafting if network.is_multiplayer() => ShouldRun::Yes,
+        _ => ShouldRun::No
+    }
+}
+
+/// e.g. "192.168.1.20" or "192.168.1.20:7000"
+fn with_default_port(addr: &str) -> String {
+    if addr.contains(':') {
+        addr.to_string()
+    } else {
+        format!("{}:{}", addr, DEFAULT_PORT)
+    }
+}
+
+pub fn host(addr: &str) -> Result<Network, String> {
+    let addr = with_default_port(addr);
+
+    let listener = TcpListener::bind(&addr).map_err(|e| format!("Could not host on {}: {}", addr, e))?;
+    listener.set_nonblocking(true).map_err(|e| format!("Could not host on {}: {}", addr, e))?;
+
+    eprintln!("Hosting on {}", addr);
+
+    Ok(Network::Host(Server {
+        listener,
+        clients: Vec::new(),
+        snapshot_timer: Timer::from_seconds(1.0 / SNAPSHOT_RATE, true)
+    }))
+}
+
+/// Connects to the host at `addr` and waits until it lets the player in.
+/// Returns the connection and the seed of the host.
+pub fn join(addr: &str) -> Result<(Network, u64), String> {
+    let addr = with_default_port(addr);
+
+    let socket_addr = addr.to_socket_addrs().ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("Unknown address {}", addr))?;
+
+    let stream = TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Could not connect to {}: {}", addr, e))?;
+
+    let mut connection = Connection::new(stream)?;
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+
+    while Instant::now() < deadline {
+        for message in connection.receive::<ServerMessage>()? {
+            match message {
+                ServerMessage::Welcome { id, seed } => {
+                    eprintln!("Joined {} as player {}", addr, id.0);
+
+                    return Ok((Network::Client(Client { id, connection, latest: None, host_items: Vec::new() }), seed));
+                },
+                ServerMessage::Full => return Err(format!("{} already has {} players", addr, MAX_PLAYERS)),
+                ServerMessage::Snapshot(_) => {}
+            }
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Err(format!("{} did not answer", addr))
+}
+
+/// `--host [address]` or `--join address`, with the seed of the host when joining.
+pub fn from_args(args: impl Iterator<Item = String>) -> Result<(Network, Option<u64>), String> {
+    let args: Vec<String> = args.collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        [] => Ok((Network::Offline, None)),
+        ["--host"] => host("0.0.0.0").map(|network| (network, None)),
+        ["--host", addr] => host(addr).map(|network| (network, None)),
+        ["--join", addr] => join(addr).map(|(network, seed)| (network, Some(seed))),
+        _ => Err("Usage: my-game [--host [address]] [--join address]".to_string())
+    }
+}
+
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin
+{
+    fn build(&self, app: &mut App) {
+        // Keep the role that was chosen before the plugin was added, see `main`
+        app.init_resource::<Network>()
+        // Received before the gameplay systems, so that every press is seen once
+        .add_system_to_stage(CoreStage::PreUpdate, accept_clients.after(controls::ActionSystem))
+        .add_system_to_stage(CoreStage::PreUpdate, receive_from_clients.after(accept_clients))
+        .add_system_to_stage(CoreStage::PreUpdate, receive_from_host.after(controls::ActionSystem))
+        .add_system_to_stage(CoreStage::PreUpdate, apply_snapshot.after(receive_from_host))
+        // Sent after them, with everything that changed in this frame
+        .add_system_to_stage(CoreStage::PostUpdate, send_snapshots)
+        .add_system_to_stage(CoreStage::PostUpdate, send_to_host);
+    }
+}
+
+fn accept_clients(
+    mut network: ResMut<Network>,
+    game_rng: Res<GameRng>
+) {
+    let server = match &mut *network {
+        Network::Host(server) => server,
+        _ => return
+    };
+
+    loop {
+        let (stream, addr) = match server.listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                eprintln!("Could not accept a player: {}", e);
+                break;
+            }
+        };
+
+        let mut connection = match Connection::new(stream) {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+
+        let free_id = (1..MAX_PLAYERS as u8).map(PlayerId)
+            .find(|id| server.clients.iter().all(|client| client.id != *id));
+
+        match free_id {
+            Some(id) => {
+                connection.send(&ServerMessage::Welcome { id, seed: game_rng.seed });
+                server.clients.push(RemoteClient { id, connection, entity: None });
+
+                eprintln!("Player {} joined from {}", id.0, addr);
+            },
+            None => {
+                connection.send(&ServerMessage::Full);
+                connection.flush().ok();
+
+                eprintln!("Turned {} away, the game is full", addr);
+            }
+        }
+    }
+}
+
+/// Applies what the clients sent to their players, and spawns the players of clients
+/// that just started their game.
+#[allow(clippy::too_many_arguments)]
+fn receive_from_clients(
+    mut commands: Commands,
+    mut network: ResMut<Network>,
+    mut player_query: Query<(&Transform, &mut PlayerInput, &mut InventoryItems, &mut Hotbar), With<Player>>,
+    local_query: Query<(), With<LocalPlayer>>,
+    interactable_query: Query<(&Transform, &InteractableEntity), Without<Player>>,
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    recipes: Res<Recipes>,
+    balance: Res<Balance>,
+    mut harvest_writer: EventWriter<HarvestInteractEvent>,
+    mut turret_writer: EventWriter<TurretInteractEvent>
+) {
+    let server = match &mut *network {
+        Network::Host(server) => server,
+        _ => return
+    };
+
+    // Players only exist while the host is in a game
+    let in_game = !local_query.is_empty();
+    let mut left: Vec<usize> = Vec::new();
+
+    for (index, client) in server.clients.iter_mut().enumerate() {
+        let received = client.connection.flush()
+            .and_then(|_| client.connection.receive::<ClientMessage>());
+
+        let messages = match received {
+            Ok(messages) => messages,
+            Err(e) => {
+                eprintln!("Player {} left: {}", client.id.0, e);
+                left.push(index);
+                continue;
+            }
+        };
+
+        if !in_game {
+            continue;
+        }
+
+        let entity = match client.entity.filter(|entity| player_query.contains(*entity)) {
+            Some(entity) => entity,
+            None => {
+                // The player of the last game was despawned with it
+                let entity = player::spawn_player(&mut commands,
+                    &game_assets,
+                    &balance,
+                    player::SPAWN_POS,
+                    player::starting_items(&registry));
+
+                commands.entity(entity).insert(client.id);
+                client.entity = Some(entity);
+
+                // The player can be controlled from the next frame on
+                continue;
+            }
+        };
+
+        let (player_trans, mut input, mut inv_items, mut hotbar) = player_query.get_mut(entity).unwrap();
+        let player_pos = player_trans.translation;
+
+        // Clients are a little ahead of their player on the host, see `CORRECTION_DIST`
+        let in_reach = |trans: &Transform| trans.translation.distance(player_pos) < balance.player.interaction_distance + CORRECTION_DIST;
+
+        // Like `Input<Action>`, a press is only just pressed in the frame it arrived
+        input.actions.clear();
+
+        for message in messages {
+            match message {
+                ClientMessage::Input { pressed, cursor } => {
+                    for action in Action::ALL {
+                        if pressed.contains(&action) {
+                            input.actions.press(action);
+                        } else {
+                            input.actions.release(action);
+                        }
+                    }
+
+                    input.cursor = cursor;
+                },
+                ClientMessage::Hotbar(new_hotbar) => {
+                    if new_hotbar.slots.len() == hotbar.slots.len() && new_hotbar.active < hotbar.slots.len() {
+                        *hotbar = new_hotbar;
+                    }
+                },
+                ClientMessage::Arrange(slots) => {
+                    let arranged = InventoryItems { slots };
+
+                    // Anything else, e.g. an item collected in the meantime, is corrected by the next snapshot
+                    if arranged.same_contents(&inv_items) {
+                        *inv_items = arranged;
+                    }
+                },
+                ClientMessage::Craft(index) => {
+                    // Like the crafting menu, crafting needs a table within reach
+                    let at_table = interactable_query.iter()
+                        .any(|(trans, interactable)| matches!(interactable.interact_type, InteractionType::CraftingOpen) && in_reach(trans));
+
+                    if let Some(recipe) = recipes.recipes.get(index).filter(|_| at_table) {
+                        recipe.craft(&mut inv_items, &registry);
+                    }
+                },
+                ClientMessage::Interact(id) => {
+                    let target = Entity::from_bits(id.0);
+
+                    // The same checks as for the own player, see `utils::keyboard_actions`
+                    match interactable_query.get(target) {
+                        Ok((trans, interactable)) if in_reach(trans) => match interactable.interact_type {
+                            InteractionType::Harvest => harvest_writer.send(HarvestInteractEvent { crop_entity: target }),
+                            InteractionType::Turret => turret_writer.send(TurretInteractEvent { turret_entity: target, player_entity: entity, cycle_mode: false }),
+                            // Chests stay with the host, and crafting needs no event
+                            InteractionType::ChestOpen | InteractionType::CraftingOpen => {}
+                        },
+                        _ => {}
+                    }
+                },
+                ClientMessage::TurretMode(id) => {
+                    let target = Entity::from_bits(id.0);
+
+                    match interactable_query.get(target) {
+                        Ok((trans, interactable)) if matches!(interactable.interact_type, InteractionType::Turret) && in_reach(trans) => {
+                            turret_writer.send(TurretInteractEvent { turret_entity: target, player_entity: entity, cycle_mode: true });
+                        },
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    for index in left.into_iter().rev() {
+        let client = server.clients.remove(index);
+
+        if let Some(entity) = client.entity.filter(|entity| player_query.contains(*entity)) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+type SentBlock<'a> = (Entity, &'a Transform, &'a BlockType, Option<&'a EntityHealth>, Option<&'a Wheat>);
+
+#[allow(clippy::too_many_arguments)]
+fn send_snapshots(
+    mut network: ResMut<Network>,
+    time: Res<Time>,
+    clock: Res<Clock>,
+    local_query: Query<(), With<LocalPlayer>>,
+    player_query: Query<(&PlayerId, &Transform, &EntityHealth, &Hunger, &InventoryItems), With<Player>>,
+    zombie_query: Query<(Entity, &Transform, &EntityHealth, &ZombieArchetype, &ZombieStats), With<Zombie>>,
+    block_query: Query<SentBlock>,
+    dropped_query: Query<(Entity, &Transform, &CollectableItem)>
+) {
+    let server = match &mut *network {
+        Network::Host(server) if !server.clients.is_empty() => server,
+        _ => return
+    };
+
+    if local_query.is_empty() || !server.snapshot_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // The host's own entity ids stay the same for as long as the entities exist
+    let net_id = |entity: Entity| NetId(entity.to_bits());
+
+    let snapshot = ServerMessage::Snapshot(Snapshot {
+        day: clock.day,
+        hour: clock.hour,
+        players: player_query.iter()
+            .map(|(id, trans, health, hunger, inv_items)| PlayerState {
+                id: *id,
+                pos: trans.translation,
+                health: health.val,
+                hunger: hunger.val,
+                items: inv_items.slots.clone()
+            })
+            .collect(),
+        zombies: zombie_query.iter()
+            .map(|(entity, trans, health, archetype, stats)| ZombieState {
+                id: net_id(entity),
+                kind: archetype.0.name.clone(),
+                stats: *stats,
+                pos: trans.translation,
+                health: health.val
+            })
+            .collect(),
+        blocks: block_query.iter()
+            .map(|(entity, trans, block, health, wheat)| BlockState {
+                id: net_id(entity),
+                block: block.0,
+                pos: trans.translation,
+                health: health.map(|h| h.val),
+                wheat_state: wheat.map(|w| w.state)
+            })
+            .collect(),
+        dropped: dropped_query.iter()
+            .map(|(entity, trans, collectable)| DroppedState {
+                id: net_id(entity),
+                pos: trans.translation,
+                scale: trans.scale,
+                item: collectable.item.clone()
+            })
+            .collect()
+    });
+
+    for client in server.clients.iter_mut() {
+        if client.connection.pending() < MAX_PENDING {
+            client.connection.send(&snapshot);
+        }
+    }
+}
+
+fn receive_from_host(
+    mut network: ResMut<Network>,
+    mut state: ResMut<State<AppState>>,
+    local_query: Query<(), With<LocalPlayer>>
+) {
+    let client = match &mut *network {
+        Network::Client(client) => client,
+        _ => return
+    };
+
+    let received = client.connection.flush()
+        .and_then(|_| client.connection.receive::<ServerMessage>());
+
+    match received {
+        Ok(messages) => {
+            for message in messages {
+                if let ServerMessage::Snapshot(snapshot) = message {
+                    client.latest = Some(snapshot);
+                }
+            }
+        },
+        Err(e) => {
+            eprintln!("Lost the connection to the host: {}", e);
+            *network = Network::Offline;
+
+            if !local_query.is_empty() && state.current() != &AppState::GameDestruct {
+                state.set(AppState::GameDestruct).unwrap();
+            }
+        }
+    }
+}
+
+type SyncedPlayer<'a> = (Entity, &'a PlayerId, &'a mut Transform, &'a mut EntityHealth, &'a mut Hunger, &'a mut InventoryItems, Option<&'a LocalPlayer>);
+type SyncedZombie<'a> = (Entity, Option<&'a NetId>, &'a mut Transform, &'a mut EntityHealth);
+type SyncedBlock<'a> = (Entity, Option<&'a NetId>, Option<&'a mut EntityHealth>, Option<&'a mut Wheat>);
+type SyncedDropped<'a> = (Entity, Option<&'a NetId>, &'a mut Transform);
+type BlockFilter = (With<BlockType>, Without<Zombie>, Without<Player>);
+type DroppedFilter = (With<CollectableItem>, Without<Zombie>, Without<Player>);
+
+/// Mirrors the newest snapshot of the host. Entities the host does not know about,
+/// e.g. the turret every game starts with, are removed.
+#[allow(clippy::too_many_arguments)]
+fn apply_snapshot(
+    mut commands: Commands,
+    mut network: ResMut<Network>,
+    mut clock: ResMut<Clock>,
+    mut player_query: Query<SyncedPlayer, With<Player>>,
+    mut zombie_query: Query<SyncedZombie, (With<Zombie>, Without<Player>)>,
+    mut block_query: Query<SyncedBlock, BlockFilter>,
+    mut dropped_query: Query<SyncedDropped, DroppedFilter>,
+    game_assets: Res<GameAssets>,
+    registry: Res<ItemRegistry>,
+    balance: Res<Balance>,
+    archetypes: Res<ZombieArchetypes>,
+    mut game_rng: ResMut<GameRng>
+) {
+    let client = match &mut *network {
+        Network::Client(client) => client,
+        _ => return
+    };
+
+    // Nothing is mirrored outside of a game
+    if !player_query.iter().any(|(.., local)| local.is_some()) {
+        return;
+    }
+
+    let snapshot = match client.latest.take() {
+        Some(snapshot) => snapshot,
+        None => return
+    };
+
+    clock.day = snapshot.day;
+    clock.hour = snapshot.hour;
+
+    let mut seen: HashSet<PlayerId> = HashSet::new();
+
+    for (entity, id, mut trans, mut health, mut hunger, mut inv_items, local) in player_query.iter_mut() {
+        let state = match snapshot.players.iter().find(|state| state.id == *id) {
+            Some(state) => state,
+            None => {
+                // The own player waits for the host to spawn it, the others left
+                if local.is_none() {
+                    commands.entity(entity).despawn_recursive();
+                }
+
+                continue;
+            }
+        };
+
+        seen.insert(*id);
+
+        if local.is_none() || trans.translation.distance(state.pos) > CORRECTION_DIST {
+            trans.translation = state.pos;
+        }
+
+        health.val = state.health;
+        hunger.val = state.hunger;
+
+        if inv_items.slots != state.items {
+            inv_items.slots = state.items.clone();
+        }
+
+        if local.is_some() {
+            client.host_items = state.items.clone();
+        }
+    }
+
+    for state in snapshot.players.iter().filter(|state| !seen.contains(&state.id)) {
+        let entity = player::spawn_player(&mut commands,
+            &game_assets,
+            &balance,
+            state.pos,
+            InventoryItems { slots: state.items.clone() });
+
+        commands.entity(entity).insert(state.id);
+    }
+
+    let zombies: HashMap<NetId, &ZombieState> = snapshot.zombies.iter().map(|state| (state.id, state)).collect();
+    let mut seen: HashSet<NetId> = HashSet::new();
+
+    for (entity, id, mut trans, mut health) in zombie_query.iter_mut() {
+        match id.and_then(|id| zombies.get(id)) {
+            Some(state) => {
+                trans.translation = state.pos;
+                health.val = state.health;
+                seen.insert(state.id);
+            },
+            None => commands.entity(entity).despawn_recursive()
+        }
+    }
+
+    for state in snapshot.zombies.iter().filter(|state| !seen.contains(&state.id)) {
+        let spawned = zombie::spawn_zombie_kind(&mut commands, &archetypes, &state.kind, state.pos, &game_assets, state.stats, &mut game_rng.gameplay);
+
+        if let Some(entity) = spawned {
+            commands.entity(entity).insert(state.id);
+        }
+    }
+
+    let blocks: HashMap<NetId, &BlockState> = snapshot.blocks.iter().map(|state| (state.id, state)).collect();
+    let mut seen: HashSet<NetId> = HashSet::new();
+
+    for (entity, id, health, wheat) in block_query.iter_mut() {
+        let state = match id.and_then(|id| blocks.get(id)) {
+            Some(state) => state,
+            None => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+
+        if let (Some(mut health), Some(val)) = (health, state.health) {
+            health.val = val;
+        }
+
+        if let (Some(mut wheat), Some(wheat_state)) = (wheat, state.wheat_state) {
+            if wheat.state != wheat_state {
+                wheat.state = wheat_state;
+            }
+        }
+
+        seen.insert(state.id);
+    }
+
+    for state in snapshot.blocks.iter().filter(|state| !seen.contains(&state.id)) {
+        let spawn_trans = Transform::from_translation(state.pos);
+
+        if let Some(entity) = construct::spawn_block(&mut commands, &game_assets, &registry, &balance, state.block, &spawn_trans) {
+            commands.entity(entity).insert(state.id);
+        }
+    }
+
+    let dropped: HashMap<NetId, &DroppedState> = snapshot.dropped.iter().map(|state| (state.id, state)).collect();
+    let mut seen: HashSet<NetId> = HashSet::new();
+
+    for (entity, id, mut trans) in dropped_query.iter_mut() {
+        match id.and_then(|id| dropped.get(id)) {
+            Some(state) => {
+                trans.translation = state.pos;
+                seen.insert(state.id);
+            },
+            None => commands.entity(entity).despawn_recursive()
+        }
+    }
+
+    for state in snapshot.dropped.iter().filter(|state| !seen.contains(&state.id)) {
+        let spawn_trans = Transform::from_translation(state.pos).with_scale(state.scale);
+        let entity = spawn_dropped(&mut commands, &game_assets, &registry, &spawn_trans, state.item.clone());
+
+        commands.entity(entity).insert(state.id);
+    }
+}
+
+/// Sends the actions of the own player, and whatever it changed on its own that the
+/// host has to repeat.
+fn send_to_host(
+    mut network: ResMut<Network>,
+    player_query: Query<(&PlayerInput, &InventoryItems, &Hotbar, ChangeTrackers<Hotbar>), With<LocalPlayer>>,
+    net_query: Query<&NetId>,
+    mut harvest_reader: EventReader<HarvestInteractEvent>,
+    mut turret_reader: EventReader<TurretInteractEvent>
+) {
+    let client = match &mut *network {
+        Network::Client(client) => client,
+        _ => return
+    };
+
+    let (input, inv_items, hotbar, hotbar_tracker) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return
+    };
+
+    // Nothing is pressed while a menu is open, see `controls::local_player_input`
+    let pressed = Action::ALL.into_iter().filter(|action| input.actions.pressed(*action)).collect();
+
+    client.connection.send(&ClientMessage::Input { pressed, cursor: input.cursor });
+
+    if hotbar_tracker.is_changed() {
+        client.connection.send(&ClientMessage::Hotbar(hotbar.clone()));
+    }
+
+    // Sent until the host agrees, or its next snapshot undoes the change
+    if inv_items.slots != client.host_items && inv_items.same_contents(&InventoryItems { slots: client.host_items.clone() }) {
+        client.connection.send(&ClientMessage::Arrange(inv_items.slots.clone()));
+    }
+
+    let crops = harvest_reader.iter().map(|event| (event.crop_entity, false));
+    let turrets = turret_reader.iter().map(|event| (event.turret_entity, event.cycle_mode));
+
+    for (target, cycle_mode) in crops.chain(turrets) {
+        if let Ok(id) = net_query.get(target) {
+            if cycle_mode {
+                client.connection.send(&ClientMessage::TurretMode(*id));
+            } else {
+                client.connection.send(&ClientMessage::Interact(*id));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::mpsc;
+
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::headless::{headless_app, HeadlessPlugin};
+
+    // Whole games run in these tests, which is slow in debug builds
+    const GAME_TIMEOUT: Duration = Duration::from_secs(30);
+
+    type BuildParams<'w, 's> = (Commands<'w, 's>, Res<'w, GameAssets>, Res<'w, ItemRegistry>, Res<'w, Balance>);
+
+    fn connect(listener: &TcpListener) -> (Connection, Connection) {
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        (Connection::new(client).unwrap(), Connection::new(server).unwrap())
+    }
+
+    fn receive_all<T: DeserializeOwned>(connection: &mut Connection, count: usize) -> Vec<T> {
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut messages = Vec::new();
+
+        while messages.len() < count && Instant::now() < deadline {
+            connection.flush().unwrap();
+            messages.extend(connection.receive::<T>().unwrap());
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        messages
+    }
+
+    #[test]
+    fn two_clients_on_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let (mut first, mut first_host) = connect(&listener);
+        let (mut second, mut second_host) = connect(&listener);
+
+        first.send(&ClientMessage::Input { pressed: vec![Action::MoveUp, Action::Fire], cursor: Vec3::new(20.0, -5.0, 0.0) });
+        first.send(&ClientMessage::Craft(3));
+        second.send(&ClientMessage::Interact(NetId(42)));
+        first.flush().unwrap();
+        second.flush().unwrap();
+
+        let from_first: Vec<ClientMessage> = receive_all(&mut first_host, 2);
+        let from_second: Vec<ClientMessage> = receive_all(&mut second_host, 1);
+
+        assert!(matches!(&from_first[0], ClientMessage::Input { pressed, cursor }
+            if pressed == &vec![Action::MoveUp, Action::Fire] && *cursor == Vec3::new(20.0, -5.0, 0.0)));
+        assert!(matches!(from_first[1], ClientMessage::Craft(3)));
+        assert!(matches!(from_second[..], [ClientMessage::Interact(NetId(42))]));
+
+        second_host.send(&ServerMessage::Welcome { id: PlayerId(2), seed: 7 });
+        second_host.flush().unwrap();
+
+        let welcome: Vec<ServerMessage> = receive_all(&mut second, 1);
+
+        assert!(matches!(welcome[..], [ServerMessage::Welcome { id: PlayerId(2), seed: 7 }]));
+    }
+
+    #[test]
+    fn endless_message_is_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (mut client, mut host) = connect(&listener);
+
+        // One line that never ends
+        client.outgoing = vec![b'x'; MAX_MESSAGE + 1];
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut result = host.receive::<ClientMessage>();
+
+        while result.is_ok() && Instant::now() < deadline {
+            client.flush().unwrap();
+            thread::sleep(Duration::from_millis(1));
+            result = host.receive::<ClientMessage>();
+        }
+
+        assert!(result.is_err());
+        assert!(host.incoming.len() <= MAX_MESSAGE + 4096);
+    }
+
+    #[test]
+    fn hanging_up_is_noticed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (client, mut host) = connect(&listener);
+
+        drop(client);
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut result = host.receive::<ClientMessage>();
+
+        while result.is_ok() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+            result = host.receive::<ClientMessage>();
+        }
+
+        assert!(result.is_err());
+    }
+
+    fn game(network: Network, seed: u64) -> App {
+        let mut app = headless_app(HeadlessPlugin::default(), seed);
+        app.insert_resource(network);
+
+        app
+    }
+
+    /// Updates the host and the clients in turn until `done` holds.
+    fn run_until(host: &mut App, clients: &mut [App], mut done: impl FnMut(&mut App, &mut [App]) -> bool) {
+        let deadline = Instant::now() + GAME_TIMEOUT;
+
+        while !done(host, clients) {
+            assert!(Instant::now() < deadline, "Timed out");
+
+            host.update();
+
+            for client in clients.iter_mut() {
+                client.update();
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn player_ids(world: &mut World) -> Vec<u8> {
+        let mut ids: Vec<u8> = world.query_filtered::<&PlayerId, With<Player>>()
+            .iter(world)
+            .map(|id| id.0)
+            .collect();
+        ids.sort();
+
+        ids
+    }
+
+    fn player_state(world: &mut World, id: PlayerId) -> Option<(f32, u32)> {
+        world.query::<(&PlayerId, &EntityHealth, &InventoryItems)>()
+            .iter(world)
+            .find(|(player_id, ..)| **player_id == id)
+            .map(|(_, health, inv_items)| (health.val, inv_items.quantity(ItemTypes::Wood)))
+    }
+
+    fn has_wall_at(world: &mut World, pos: Vec3) -> bool {
+        world.query_filtered::<(&Transform, &BlockType), With<NetId>>()
+            .iter(world)
+            .any(|(trans, block)| block.0 == ItemTypes::WallBlock && trans.translation.truncate() == pos.truncate())
+    }
+
+    #[test]
+    fn host_and_two_clients() {
+        let network = host("127.0.0.1:0").unwrap();
+        let addr = match &network {
+            Network::Host(server) => server.listener.local_addr().unwrap().to_string(),
+            _ => unreachable!()
+        };
+
+        let mut host_app = game(network, 7);
+        run_until(&mut host_app, &mut [], |host, _| *host.world.resource::<State<AppState>>().current() == AppState::InGame);
+
+        // Joining waits for the welcome, which the host only sends while it runs
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..2 {
+            let sender = sender.clone();
+            let addr = addr.clone();
+            thread::spawn(move || sender.send(join(&addr)).unwrap());
+        }
+
+        let mut clients: Vec<App> = Vec::new();
+
+        run_until(&mut host_app, &mut [], |_, _| {
+            if let Ok(joined) = receiver.try_recv() {
+                let (network, seed) = joined.unwrap();
+                assert_eq!(seed, 7);
+                clients.push(game(network, seed));
+            }
+
+            clients.len() == 2
+        });
+
+        // Every machine sees the players of the others
+        run_until(&mut host_app, &mut clients, |host, clients| {
+            player_ids(&mut host.world) == [0, 1, 2]
+                && clients.iter_mut().all(|client| player_ids(&mut client.world) == [0, 1, 2])
+        });
+
+        let wall_pos = Vec3::new(200.0, 200.0, 1.0);
+
+        {
+            let world = &mut host_app.world;
+            let mut state: SystemState<BuildParams> = SystemState::new(world);
+            let (mut commands, game_assets, registry, balance) = state.get_mut(world);
+
+            construct::spawn_block(&mut commands, &game_assets, &registry, &balance, ItemTypes::WallBlock, &Transform::from_translation(wall_pos));
+            state.apply(world);
+
+            let max_stack = world.resource::<ItemRegistry>().max_stack(ItemTypes::Wood);
+
+            for (id, mut health, mut hunger, mut inv_items) in world.query::<(&PlayerId, &mut EntityHealth, &mut Hunger, &mut InventoryItems)>().iter_mut(world) {
+                if *id == PlayerId(1) {
+                    // Hungry enough not to heal, but not starving
+                    health.val = 33.0;
+                    hunger.val = 10.0;
+                    inv_items.add_item(Item { item_type: ItemTypes::Wood, quantity: 5 }, max_stack).unwrap();
+                }
+            }
+        }
+
+        let expected = player_state(&mut host_app.world, PlayerId(1)).unwrap();
+        assert_eq!(expected.0, 33.0);
+
+        run_until(&mut host_app, &mut clients, |_, clients| {
+            clients.iter_mut().all(|client| {
+                has_wall_at(&mut client.world, wall_pos)
+                    && player_state(&mut client.world, PlayerId(1)) == Some(expected)
+            })
+        });
+
+        // Including the client that plays player 1 itself
+        let local_ids: Vec<PlayerId> = clients.iter_mut()
+            .map(|client| *client.world.query_filtered::<&PlayerId, With<LocalPlayer>>().single(&client.world))
+            .collect();
+        assert!(local_ids.contains(&PlayerId(1)));
+    }
+}
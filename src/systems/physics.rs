@@ -9,7 +9,7 @@ impl Plugin for PhysicsPlugin
 {
     fn build(&self, app: &mut App) {
         app
-        .add_system_set(SystemSet::on_update(AppState::InGame)
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
             .with_system(apply_velocity)
             .with_system(entity_collision));
     }
@@ -59,7 +59,8 @@ pub fn apply_velocity(
 pub fn entity_collision(
     mut entity_query: Query<(Entity, &mut Transform, &mut Rigidbody, &BoxCollider), Without<StaticEntity>>,
     mut event_writer: EventWriter<CollisionEvent>,
-    static_query: Query<(Entity, &Transform, &BoxCollider), With<StaticEntity>>
+    static_query: Query<(Entity, &Transform, &BoxCollider), With<StaticEntity>>,
+    hash: Res<SpatialHash>
 ) {
 
     for (
@@ -74,7 +75,7 @@ pub fn entity_collision(
             stat_entity,
             stat_trans,
             stat_collider
-        ) in static_query.iter() {
+        ) in hash.overlapping(ent_trans.translation, ent_sprite_size).filter_map(|e| static_query.get(e).ok()) {
             let stat_sprite_size = stat_collider.size;
 
             let diff_x = stat_trans.translation.x - ent_trans.translation.x;
@@ -0,0 +1,141 @@
+//! Power networks of generators and consumers.
+//!
+//! Blocks with a `PowerNode` are connected when they are within the balance `power.radius` of each
+//! other, and every connected group forms a `PowerNetwork`. When the consumers of a network
+//! draw more than its generators produce, all of them brown out and only run at the
+//! fraction of the demand that is met. Windmills produce less at night, see `daynight`.
+
+use std::collections::HashMap;
+
+use crate::prelude::*;
+
+// Consumers are drawn darker the less power they get
+const UNPOWERED_BRIGHTNESS: f32 = 0.4;
+
+pub struct PowerPlugin;
+
+impl Plugin for PowerPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PowerGrid>()
+        .add_system_set(SystemSet::on_enter(AppState::GameSetup)
+            .with_system(power_setup))
+        .add_system_set(SystemSet::new().with_run_criteria(network::gameplay_running)
+            .with_system(update_power_grid)
+            .with_system(power_indicator.after(update_power_grid)));
+    }
+}
+
+#[derive(Default)]
+pub struct PowerNetwork
+{
+    pub supply: f32,
+    pub demand: f32,
+    pub nodes: Vec<Entity>
+}
+
+impl PowerNetwork
+{
+    /// Fraction of the demand that is met, between 0 and 1.
+    pub fn satisfaction(&self) -> f32 {
+        if self.demand <= 0.0 {
+            1.0
+        } else {
+            (self.supply / self.demand).min(1.0)
+        }
+    }
+
+    pub fn is_brownout(&self) -> bool {
+        self.demand > self.supply
+    }
+}
+
+#[derive(Default)]
+pub struct PowerGrid
+{
+    pub networks: Vec<PowerNetwork>
+}
+
+fn power_setup(
+    mut commands: Commands
+) {
+    commands.insert_resource(PowerGrid::default());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_power_grid(
+    mut grid: ResMut<PowerGrid>,
+    mut node_query: Query<(Entity, &Transform, &mut PowerNode)>,
+    removed: RemovedComponents<PowerNode>,
+    mut night_reader: EventReader<NightFell>,
+    mut day_reader: EventReader<DayBroke>,
+    clock: Res<Clock>,
+    cycle: Res<DayCycle>,
+    balance: Res<Balance>
+) {
+    // Networks only change when power blocks are placed, which have no network yet, or destroyed,
+    // when the power radius is rebalanced, and the supply changes between day and night
+    let time_changed = night_reader.iter().count() + day_reader.iter().count() > 0;
+
+    if node_query.iter().all(|(_, _, node)| node.network.is_some()) && removed.iter().next().is_none()
+        && !time_changed && !balance.is_changed() {
+        return;
+    }
+
+    let nodes: Vec<(Entity, Vec3)> = node_query.iter().map(|(entity, trans, _)| (entity, trans.translation)).collect();
+    let mut network_of: HashMap<Entity, usize> = HashMap::new();
+
+    grid.networks.clear();
+
+    // Flood fill from every node that is not part of a network yet
+    for &(start, start_pos) in nodes.iter() {
+        if network_of.contains_key(&start) {
+            continue;
+        }
+
+        let network = grid.networks.len();
+        let mut stack = vec![(start, start_pos)];
+        let mut members = Vec::new();
+        network_of.insert(start, network);
+
+        while let Some((entity, pos)) = stack.pop() {
+            members.push(entity);
+
+            for &(other, other_pos) in nodes.iter() {
+                if !network_of.contains_key(&other) && (pos - other_pos).length() <= balance.power.radius {
+                    network_of.insert(other, network);
+                    stack.push((other, other_pos));
+                }
+            }
+        }
+
+        grid.networks.push(PowerNetwork { nodes: members, ..Default::default() });
+    }
+
+    for (entity, _, node) in node_query.iter() {
+        let network = &mut grid.networks[network_of[&entity]];
+
+        match node.role {
+            PowerRole::Generator => network.supply += node.watts * cycle.generation(clock.night),
+            PowerRole::Consumer => network.demand += node.watts
+        }
+    }
+
+    for (entity, _, mut node) in node_query.iter_mut() {
+        let network = network_of[&entity];
+
+        node.network = Some(network);
+        node.satisfaction = grid.networks[network].satisfaction();
+    }
+}
+
+fn power_indicator(
+    mut consumer_query: Query<(&PowerNode, &mut TextureAtlasSprite), Changed<PowerNode>>
+) {
+    for (node, mut sprite) in consumer_query.iter_mut() {
+        if node.role == PowerRole::Consumer {
+            let brightness = UNPOWERED_BRIGHTNESS + (1.0 - UNPOWERED_BRIGHTNESS) * node.satisfaction;
+            sprite.color = Color::rgb(brightness, brightness, brightness);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Item and block definitions loaded from `assets/items.ron`.
+//!
+//! Every `ItemTypes` variant needs exactly one entry. Placeable items also
+//! carry a `block` with the stats of the spawned block. Items mined by rigs name the
+//! sprite of their ore deposit in `deposit_sprite`:
+//!
+//! ```text
+//! (
+//!     item: WallBlock,
+//!     name: "Wall",
+//!     sprite_index: 0,
+//!     max_stack: 99,
+//!     placeable: true,
+//!     food: None,
+//!     deposit_sprite: None,
+//!     block: Some((
+//!         health: Some(400.0),
+//!         collider: Some((20.0, 20.0)),
+//!         priority: Some(Low),
+//!         drop: None,
+//!         power: None,
+//!     )),
+//! ),
+//! ```
+//!
+//! The file is validated when the game starts, and the game refuses to start if it is invalid.
+
+use std::{collections::HashMap, fs};
+
+use bevy::ecs::system::EntityCommands;
+use serde::Deserialize;
+
+use crate::{prelude::*, SHEET_COLUMNS, SHEET_ROWS};
+
+pub const REGISTRY_PATH: &str = "assets/items.ron";
+
+const ALL_ITEMS: [ItemTypes; 21] = [
+    ItemTypes::WallBlock,
+    ItemTypes::TurretBlock,
+    ItemTypes::TripMine,
+    ItemTypes::Fence,
+    ItemTypes::Wheat,
+    ItemTypes::WindMill,
+    ItemTypes::WoodFence,
+    ItemTypes::Chest,
+    ItemTypes::LandingPad,
+    ItemTypes::MiningRig,
+    ItemTypes::IronIngot,
+    ItemTypes::Coal,
+    ItemTypes::CraftingTable,
+    ItemTypes::Steak,
+    ItemTypes::Wood,
+    ItemTypes::Ammo,
+    ItemTypes::Seeds,
+    ItemTypes::Pistol,
+    ItemTypes::Shotgun,
+    ItemTypes::Rifle,
+    ItemTypes::Shells
+];
+
+pub struct RegistryPlugin;
+
+impl Plugin for RegistryPlugin
+{
+    fn build(&self, app: &mut App) {
+        let registry = read_registry(REGISTRY_PATH).unwrap_or_else(|e| panic!("{}", e));
+
+        app.insert_resource(registry);
+    }
+}
+
+#[derive(Deserialize)]
+struct RegistryFile
+{
+    items: Vec<ItemDef>
+}
+
+#[derive(Deserialize)]
+pub struct ItemDef
+{
+    pub item: ItemTypes,
+    pub name: String,
+    pub sprite_index: usize,
+    pub max_stack: u16,
+    pub placeable: bool,
+    // Hunger restored by eating the item
+    pub food: Option<f32>,
+    // Sprite of the ore deposit the item is mined from
+    pub deposit_sprite: Option<usize>,
+    pub block: Option<BlockDef>
+}
+
+#[derive(Deserialize)]
+pub struct BlockDef
+{
+    pub health: Option<f32>,
+    pub collider: Option<(f32, f32)>,
+    pub priority: Option<TargetPriority>,
+    pub drop: Option<Item>,
+    pub power: Option<PowerDef>
+}
+
+#[derive(Deserialize)]
+pub struct PowerDef
+{
+    pub role: PowerRole,
+    pub watts: f32
+}
+
+pub struct ItemRegistry
+{
+    items: HashMap<ItemTypes, ItemDef>
+}
+
+impl ItemRegistry
+{
+    pub fn get(&self, item_type: ItemTypes) -> &ItemDef {
+        // Validation guarantees an entry for every item type
+        &self.items[&item_type]
+    }
+
+    pub fn sprite_index(&self, item_type: ItemTypes) -> usize {
+        self.get(item_type).sprite_index
+    }
+
+    pub fn max_stack(&self, item_type: ItemTypes) -> u16 {
+        self.get(item_type).max_stack
+    }
+
+    pub fn deposit_sprite(&self, ore: OreKind) -> usize {
+        // Validation guarantees a deposit sprite for every ore
+        self.get(ore.item()).deposit_sprite.unwrap()
+    }
+
+    pub fn from_defs(defs: Vec<ItemDef>) -> Result<Self, String> {
+        let mut errors: Vec<String> = Vec::new();
+        let mut items: HashMap<ItemTypes, ItemDef> = HashMap::new();
+
+        for def in defs {
+            if items.contains_key(&def.item) {
+                errors.push(format!("{:?} is defined more than once", def.item));
+                continue;
+            }
+
+            items.insert(def.item, def);
+        }
+
+        for item_type in ALL_ITEMS {
+            if !items.contains_key(&item_type) {
+                errors.push(format!("{:?} is missing", item_type));
+            }
+        }
+
+        for ore in miningrig::ORES {
+            if items.get(&ore.item()).is_some_and(|def| def.deposit_sprite.is_none()) {
+                errors.push(format!("{:?}: the item of {:?} deposits needs a deposit_sprite", ore.item(), ore));
+            }
+        }
+
+        for def in items.values() {
+            validate_def(def, &items, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(ItemRegistry { items })
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+fn validate_def(def: &ItemDef, items: &HashMap<ItemTypes, ItemDef>, errors: &mut Vec<String>) {
+    let mut error = |msg: &str| errors.push(format!("{:?}: {}", def.item, msg));
+
+    if def.name.is_empty() {
+        error("name is empty");
+    }
+
+    if def.sprite_index >= SHEET_COLUMNS * SHEET_ROWS {
+        error("sprite_index is outside of Sheet.png");
+    }
+
+    if def.deposit_sprite.is_some_and(|sprite| sprite >= SHEET_COLUMNS * SHEET_ROWS) {
+        error("deposit_sprite is outside of Sheet.png");
+    }
+
+    if def.max_stack == 0 {
+        error("max_stack has to be at least 1");
+    }
+
+    if def.food.is_some_and(|food| food <= 0.0) {
+        error("food has to be positive");
+    }
+
+    match (&def.block, def.placeable) {
+        (None, true) => error("placeable items need a block"),
+        (Some(_), false) => error("only placeable items can have a block"),
+        _ => {}
+    }
+
+    if let Some(block) = &def.block {
+        if block.health.is_some_and(|health| health <= 0.0) {
+            error("block health has to be positive");
+        }
+
+        if block.collider.is_some_and(|(x, y)| x <= 0.0 || y <= 0.0) {
+            error("block collider has to have a positive size");
+        }
+
+        if block.power.as_ref().is_some_and(|power| power.watts <= 0.0) {
+            error("block power has to be positive");
+        }
+
+        if let Some(drop) = &block.drop {
+            let max_stack = items.get(&drop.item_type).map_or(0, |d| d.max_stack);
+
+            if drop.quantity == 0 || drop.quantity > max_stack {
+                error("block drop quantity has to be between 1 and the max_stack of the dropped item");
+            }
+        }
+    }
+}
+
+pub fn read_registry(path: &str) -> Result<ItemRegistry, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let file: RegistryFile = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    ItemRegistry::from_defs(file.items).map_err(|e| format!("Invalid item definitions in {}:\n{}", path, e))
+}
+
+/// Inserts the health, collider, attack priority, drop and power of a placeable item on a spawned block.
+pub fn insert_block_stats(
+    entity: &mut EntityCommands,
+    def: &ItemDef,
+    func_destruct: fn(&mut Commands, &Entity, &Res<GameAssets>, &Transform)
+) {
+    entity.insert(BlockType(def.item));
+
+    let block = match &def.block {
+        Some(block) => block,
+        None => return
+    };
+
+    if let Some(val) = block.health {
+        entity.insert(EntityHealth { val, func_destruct });
+    }
+
+    if let Some((x, y)) = block.collider {
+        entity.insert(BoxCollider { size: Vec2::new(x, y) });
+    }
+
+    if let Some(priority) = block.priority {
+        entity.insert(Attackable(priority));
+    }
+
+    if let Some(item) = &block.drop {
+        entity.insert(DropsItem { item: item.clone() });
+    }
+
+    if let Some(power) = &block.power {
+        entity.insert(PowerNode { role: power.role, watts: power.watts, network: None, satisfaction: 0.0 });
+    }
+}
@@ -0,0 +1,743 @@
+//! Saving and loading of a running game.
+//!
+//! A save is written to `savegame.ron` as a RON encoded `SaveGame`. The
+//! `version` field is bumped whenever the layout changes, and a file with a
+//! different version is refused instead of being partially loaded.
+//!
+//! The world is saved as the chunks that were changed by the player, see `environment`,
+//! and everything else is generated again from the seed.
+//!
+//! Press F5 in game to save and F9 to load the last save. Only the host of a
+//! multiplayer game can do either, and only the host's own player is saved. The players
+//! of connected clients keep their place and items when the host loads.
+
+use std::{collections::HashMap, fs, time::Duration};
+
+use bevy::ecs::system::SystemState;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub const SAVE_VERSION: u32 = 14;
+pub const SAVE_PATH: &str = "savegame.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin
+{
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(AppState::InGame)
+            .with_system(save_game.exclusive_system())
+            .with_system(load_game.exclusive_system()));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame
+{
+    pub version: u32,
+    pub seed: u64,
+    pub player: PlayerSave,
+    pub zombies: Vec<ZombieSave>,
+    pub chunks: Vec<(environment::Chunk, ChunkSave)>,
+    pub wave: WaveSave,
+    pub clock: ClockSave
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSave
+{
+    pub pos: Vec3,
+    pub health: f32,
+    pub hunger: f32,
+    pub weapon: Option<ItemTypes>,
+    // Rounds left in the magazine of every weapon, including the held one
+    pub magazines: HashMap<ItemTypes, u8>,
+    pub items: Vec<Option<Item>>,
+    pub hotbar: Hotbar
+}
+
+/// Everything in a chunk that the player can change. The ground is always generated.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChunkSave
+{
+    pub trees: Vec<EntitySave>,
+    pub deposits: Vec<DepositSave>,
+    pub animals: Vec<AnimalSave>,
+    pub blocks: Vec<BlockSave>,
+    pub dropped: Vec<DroppedSave>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EntitySave
+{
+    pub pos: Vec3,
+    pub health: f32
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DepositSave
+{
+    pub pos: Vec3,
+    pub ore: OreKind,
+    pub quantity: u32
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AnimalSave
+{
+    pub pos: Vec3,
+    pub health: f32,
+    pub sprite: usize
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ZombieSave
+{
+    pub pos: Vec3,
+    pub health: f32,
+    // Name of the zombie archetype
+    pub kind: String,
+    pub stats: ZombieStats
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BlockSave
+{
+    pub block: ItemTypes,
+    pub pos: Vec3,
+    pub health: Option<f32>,
+    pub wheat_state: Option<u8>,
+    pub timer: Option<TimerSave>,
+    pub items: Option<Vec<Option<Item>>>,
+    pub ammo: Option<u16>,
+    pub targeting: Option<TurretTargeting>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DroppedSave
+{
+    pub pos: Vec3,
+    pub scale: Vec3,
+    pub item: Item
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WaveSave
+{
+    pub number: u32,
+    pub phase: WavePhase,
+    pub build_timer: TimerSave,
+    pub attack_timer: TimerSave,
+    pub spawn_timer: TimerSave,
+    pub pending: Vec<waves::PendingSpawn>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClockSave
+{
+    pub day: u32,
+    pub hour: f32
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TimerSave
+{
+    pub elapsed: f32,
+    pub just_finished: bool
+}
+
+impl TimerSave
+{
+    pub fn from_timer(timer: &Timer) -> Self {
+        TimerSave {
+            elapsed: timer.elapsed_secs(),
+            just_finished: timer.just_finished()
+        }
+    }
+
+    pub fn apply(&self, timer: &mut Timer) {
+        // Some systems branch on `just_finished`, so it has to survive the round trip.
+        // Timers that take their duration from the balance on their first tick, like those
+        // of rigs and crops, are still empty here, and ticking an empty repeating timer panics.
+        if self.just_finished && !timer.duration().is_zero() {
+            let duration = timer.duration();
+            timer.set_elapsed(duration);
+            timer.tick(Duration::ZERO);
+        }
+
+        timer.set_elapsed(Duration::from_secs_f32(self.elapsed));
+    }
+}
+
+pub fn write_save(path: &str, save: &SaveGame) -> Result<(), String> {
+    let serialized = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default())
+        .map_err(|e| format!("Could not serialize save: {}", e))?;
+
+    fs::write(path, serialized).map_err(|e| format!("Could not write {}: {}", path, e))
+}
+
+pub fn read_save(path: &str) -> Result<SaveGame, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+
+    let save: SaveGame = ron::from_str(&contents).map_err(|e| format!("Could not parse {}: {}", path, e))?;
+
+    if save.version != SAVE_VERSION {
+        return Err(format!("Save version {} is not supported, expected {}", save.version, SAVE_VERSION));
+    }
+
+    Ok(save)
+}
+
+fn save_game(world: &mut World) {
+    if !world.resource::<Input<Action>>().just_pressed(Action::QuickSave) {
+        return;
+    }
+
+    // A client only has what the host sent it
+    if world.resource::<Network>().is_client() {
+        println!("Only the host can save the game");
+        return;
+    }
+
+    let save = snapshot_game(world);
+
+    match write_save(SAVE_PATH, &save) {
+        Ok(()) => println!("Saved game to {}", SAVE_PATH),
+        Err(e) => eprintln!("{}", e)
+    }
+}
+
+/// Collects every saved kind of entity and resource in the world into a `SaveGame`.
+/// Only the player of this machine is saved, the others join a loaded game anew.
+pub fn snapshot_game(world: &mut World) -> SaveGame {
+    let (player_trans, player_health, player_hunger, weapon, holstered, player_items, hotbar) = world
+        .query_filtered::<(&Transform, &EntityHealth, &Hunger, Option<&Weapon>, &HolsteredAmmo, &InventoryItems, &Hotbar), With<LocalPlayer>>()
+        .single(world);
+
+    let mut magazines = holstered.0.clone();
+
+    if let Some(weapon) = weapon {
+        magazines.insert(weapon.stats.item, weapon.magazine);
+    }
+
+    let player = PlayerSave {
+        pos: player_trans.translation,
+        health: player_health.val,
+        hunger: player_hunger.val,
+        weapon: weapon.map(|weapon| weapon.stats.item),
+        magazines,
+        items: player_items.slots.clone(),
+        hotbar: hotbar.clone()
+    };
+
+    let zombies = world
+        .query_filtered::<(&Transform, &EntityHealth, &ZombieArchetype, &ZombieStats), With<Zombie>>()
+        .iter(world)
+        .map(|(trans, health, archetype, stats)| ZombieSave { pos: trans.translation, health: health.val, kind: archetype.0.name.clone(), stats: *stats })
+        .collect();
+
+    let current_wave = world.resource::<CurrentWave>();
+
+    let wave = WaveSave {
+        number: current_wave.number,
+        phase: current_wave.phase,
+        build_timer: TimerSave::from_timer(&current_wave.build_timer),
+        attack_timer: TimerSave::from_timer(&current_wave.attack_timer),
+        spawn_timer: TimerSave::from_timer(&current_wave.spawn_timer),
+        pending: current_wave.pending.clone()
+    };
+
+    let current_clock = world.resource::<Clock>();
+
+    let clock = ClockSave {
+        day: current_clock.day,
+        hour: current_clock.hour
+    };
+
+    // Bring the snapshots of the loaded chunks up to date
+    let mut loaded: Vec<environment::Chunk> = world.resource::<WorldChunks>().loaded.iter().copied().collect();
+    loaded.sort();
+
+    for chunk in loaded {
+        let chunk_save = snapshot_chunk(world, chunk);
+        environment::store_chunk(world, chunk, chunk_save);
+    }
+
+    let world_chunks = world.resource::<WorldChunks>();
+    let seed = world_chunks.seed;
+
+    let mut chunks: Vec<(environment::Chunk, ChunkSave)> = world_chunks.modified.iter()
+        .map(|(chunk, chunk_save)| (*chunk, chunk_save.clone()))
+        .collect();
+    chunks.sort_by_key(|(chunk, _)| *chunk);
+
+    SaveGame {
+        version: SAVE_VERSION,
+        seed,
+        player,
+        zombies,
+        chunks,
+        wave,
+        clock
+    }
+}
+
+/// Collects the trees, deposits, animals, blocks and dropped items in a chunk.
+pub fn snapshot_chunk(world: &mut World, chunk: environment::Chunk) -> ChunkSave {
+    let in_chunk = |trans: &Transform| environment::chunk_of(trans.translation) == chunk;
+
+    let trees = world
+        .query_filtered::<(&Transform, &EntityHealth), With<Tree>>()
+        .iter(world)
+        .filter(|(trans, _)| in_chunk(trans))
+        .map(|(trans, health)| EntitySave { pos: trans.translation, health: health.val })
+        .collect();
+
+    let deposits = world
+        .query::<(&Transform, &Deposit)>()
+        .iter(world)
+        .filter(|(trans, _)| in_chunk(trans))
+        .map(|(trans, deposit)| DepositSave { pos: trans.translation, ore: deposit.ore, quantity: deposit.quantity })
+        .collect();
+
+    let animals = world
+        .query_filtered::<(&Transform, &EntityHealth, &TextureAtlasSprite), With<Animal>>()
+        .iter(world)
+        .filter(|(trans, ..)| in_chunk(trans))
+        .map(|(trans, health, sprite)| AnimalSave { pos: trans.translation, health: health.val, sprite: sprite.index })
+        .collect();
+
+    let blocks = world
+        .query::<(&Transform, &BlockType, Option<&EntityHealth>, Option<&Wheat>, Option<&MiningRig>, Option<&InventoryItems>, Option<&AmmoStore>, Option<&TurretTargeting>)>()
+        .iter(world)
+        .filter(|(trans, ..)| in_chunk(trans))
+        .map(|(trans, block, health, wheat, rig, items, ammo, targeting)| {
+            let wheat_timer = wheat.map(|w| TimerSave::from_timer(&w.timer));
+            let rig_timer = rig.map(|r| TimerSave::from_timer(&r.0));
+
+            BlockSave {
+                block: block.0,
+                pos: trans.translation,
+                health: health.map(|h| h.val),
+                wheat_state: wheat.map(|w| w.state),
+                timer: wheat_timer.or(rig_timer),
+                items: items.map(|i| i.slots.clone()),
+                ammo: ammo.map(|a| a.rounds),
+                targeting: targeting.copied()
+            }
+        })
+        .collect();
+
+    let dropped = world
+        .query::<(&Transform, &CollectableItem)>()
+        .iter(world)
+        .filter(|(trans, _)| in_chunk(trans))
+        .map(|(trans, collectable)| DroppedSave { pos: trans.translation, scale: trans.scale, item: collectable.item.clone() })
+        .collect();
+
+    ChunkSave {
+        trees,
+        deposits,
+        animals,
+        blocks,
+        dropped
+    }
+}
+
+fn load_game(world: &mut World) {
+    if !world.resource::<Input<Action>>().just_pressed(Action::QuickLoad) {
+        return;
+    }
+
+    if world.resource::<Network>().is_client() {
+        println!("Only the host can load a game");
+        return;
+    }
+
+    match read_save(SAVE_PATH) {
+        Ok(save) => {
+            restore_game(world, &save);
+            println!("Loaded game from {}", SAVE_PATH);
+        },
+        Err(e) => eprintln!("{}", e)
+    }
+}
+
+type RestoreParams<'w, 's> = (Commands<'w, 's>, Res<'w, GameAssets>, Res<'w, ItemRegistry>, Res<'w, ZombieArchetypes>, ResMut<'w, GameRng>, Res<'w, Balance>);
+
+/// Replaces every saved kind of entity in the world with the contents of `save`.
+/// The chunks around the player are loaded from the saved chunks in the next frame.
+pub fn restore_game(world: &mut World, save: &SaveGame) {
+    // Only the own player is saved, the players of connected clients stay as they are
+    let mut to_despawn = world.query_filtered::<Entity, Or<(
+        With<LocalPlayer>,
+        With<Ground>,
+        With<Tree>,
+        With<Deposit>,
+        With<Animal>,
+        With<Zombie>,
+        With<BlockType>,
+        With<CollectableItem>,
+        With<Bullet>,
+        With<Spit>,
+        With<Explosion>,
+        With<TempZombieDead>,
+        With<TempTurretDestroyed>
+    )>>();
+
+    let entities: Vec<Entity> = to_despawn.iter(world).collect();
+
+    for entity in entities {
+        // Children (e.g. windmill blades) may already be gone with their parent.
+        if world.get_entity(entity).is_some() {
+            despawn_with_children_recursive(world, entity);
+        }
+    }
+
+    let mut world_chunks = WorldChunks::new(save.seed);
+    world_chunks.modified = save.chunks.iter().cloned().collect();
+    world.insert_resource(world_chunks);
+
+    let mut system_state: SystemState<RestoreParams> = SystemState::new(world);
+
+    let mut health_patches: Vec<(Entity, f32)> = Vec::new();
+
+    let player_entity;
+
+    {
+        let (mut commands, game_assets, _, archetypes, mut game_rng, balance) = system_state.get_mut(world);
+        let rng = &mut game_rng.gameplay;
+
+        player_entity = player::spawn_player(&mut commands,
+            &game_assets,
+            &balance,
+            save.player.pos,
+            InventoryItems { slots: save.player.items.clone() });
+        health_patches.push((player_entity, save.player.health));
+
+        for zombie in save.zombies.iter() {
+            // Zombies of archetypes that were removed from `assets/zombies.ron` are left out
+            if let Some(entity) = zombie::spawn_zombie_kind(&mut commands, &archetypes, &zombie.kind, zombie.pos, &game_assets, zombie.stats, rng) {
+                health_patches.push((entity, zombie.health));
+            }
+        }
+    }
+
+    system_state.apply(world);
+
+    let mut holstered = save.player.magazines.clone();
+    let weapon = save.player.weapon
+        .and_then(|item_type| world.resource::<weapons::Weapons>().get(item_type).copied())
+        .map(|stats| Weapon::new(stats, holstered.remove(&stats.item).unwrap_or(stats.magazine)));
+
+    if let Some(weapon) = weapon {
+        world.entity_mut(player_entity).insert(weapon);
+    }
+
+    world.get_mut::<HolsteredAmmo>(player_entity).unwrap().0 = holstered;
+    world.get_mut::<Hunger>(player_entity).unwrap().val = save.player.hunger;
+    world.entity_mut(player_entity)
+        .insert(save.player.hotbar.clone())
+        .insert(LocalPlayer)
+        .insert(network::HOST_ID);
+
+    for (entity, val) in health_patches {
+        world.get_mut::<EntityHealth>(entity).unwrap().val = val;
+    }
+
+    let mut current_wave = world.resource_mut::<CurrentWave>();
+
+    current_wave.number = save.wave.number;
+    current_wave.phase = save.wave.phase;
+    current_wave.pending = save.wave.pending.clone();
+    save.wave.build_timer.apply(&mut current_wave.build_timer);
+    save.wave.attack_timer.apply(&mut current_wave.attack_timer);
+    save.wave.spawn_timer.apply(&mut current_wave.spawn_timer);
+
+    let night = world.resource::<DayCycle>().is_night(save.clock.hour);
+    let mut clock = world.resource_mut::<Clock>();
+
+    clock.day = save.clock.day;
+    clock.hour = save.clock.hour;
+    clock.night = night;
+}
+
+/// Spawns the trees, deposits, animals, blocks and dropped items of a chunk snapshot.
+pub fn restore_chunk(world: &mut World, save: &ChunkSave) {
+    let mut system_state: SystemState<RestoreParams> = SystemState::new(world);
+
+    let mut health_patches: Vec<(Entity, f32)> = Vec::new();
+    let mut sprite_patches: Vec<(Entity, usize)> = Vec::new();
+    let mut block_patches: Vec<(Entity, &BlockSave)> = Vec::new();
+
+    {
+        let (mut commands, game_assets, registry, _, mut game_rng, balance) = system_state.get_mut(world);
+        let rng = &mut game_rng.gameplay;
+
+        for tree in save.trees.iter() {
+            let entity = environment::spawn_tree(&mut commands, tree.pos, &game_assets);
+            health_patches.push((entity, tree.health));
+        }
+
+        for deposit in save.deposits.iter() {
+            miningrig::spawn_deposit(&mut commands, deposit.pos, deposit.ore, deposit.quantity, &game_assets, &registry, &balance);
+        }
+
+        for animal in save.animals.iter() {
+            let entity = animals::spawn_animal(&mut commands, animal.pos, &game_assets, rng);
+            health_patches.push((entity, animal.health));
+            sprite_patches.push((entity, animal.sprite));
+        }
+
+        for block in save.blocks.iter() {
+            let spawn_trans = Transform::from_translation(block.pos);
+
+            if let Some(entity) = construct::spawn_block(&mut commands, &game_assets, &registry, &balance, block.block, &spawn_trans) {
+                block_patches.push((entity, block));
+            }
+        }
+
+        for dropped in save.dropped.iter() {
+            spawn_dropped(&mut commands,
+                &game_assets,
+                &registry,
+                &Transform::from_translation(dropped.pos).with_scale(dropped.scale),
+                dropped.item.clone());
+        }
+    }
+
+    system_state.apply(world);
+
+    for (entity, val) in health_patches {
+        world.get_mut::<EntityHealth>(entity).unwrap().val = val;
+    }
+
+    for (entity, index) in sprite_patches {
+        world.get_mut::<TextureAtlasSprite>(entity).unwrap().index = index;
+    }
+
+    for (entity, block) in block_patches {
+        if let (Some(val), Some(mut health)) = (block.health, world.get_mut::<EntityHealth>(entity)) {
+            health.val = val;
+        }
+
+        if let Some(mut wheat) = world.get_mut::<Wheat>(entity) {
+            wheat.state = block.wheat_state.unwrap_or(0);
+
+            if let Some(timer) = &block.timer {
+                timer.apply(&mut wheat.timer);
+            }
+        }
+
+        if let (Some(timer), Some(mut rig)) = (&block.timer, world.get_mut::<MiningRig>(entity)) {
+            timer.apply(&mut rig.0);
+        }
+
+        if let (Some(items), Some(mut inv_items)) = (&block.items, world.get_mut::<InventoryItems>(entity)) {
+            inv_items.slots = items.clone();
+        }
+
+        if let (Some(rounds), Some(mut ammo)) = (block.ammo, world.get_mut::<AmmoStore>(entity)) {
+            // The ammo capacity may have been lowered in the balance since the game was saved
+            ammo.rounds = rounds.min(ammo.capacity);
+        }
+
+        if let (Some(saved), Some(mut targeting)) = (block.targeting, world.get_mut::<TurretTargeting>(entity)) {
+            *targeting = saved;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::headless::{headless_app, HeadlessPlugin};
+
+    const CHEST_POS: Vec3 = Vec3::new(140.0, 60.0, 1.0);
+
+    fn running_game(seed: u64) -> App {
+        let mut app = headless_app(HeadlessPlugin::default(), seed);
+
+        while *app.world.resource::<State<AppState>>().current() != AppState::InGame {
+            app.update();
+        }
+
+        // Streams in the chunks around the player
+        app.update();
+
+        app
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}.ron", name, std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn populate(world: &mut World) {
+        let mut state: SystemState<RestoreParams> = SystemState::new(world);
+        let (mut commands, game_assets, registry, archetypes, mut game_rng, balance) = state.get_mut(world);
+
+        for (i, block_type) in [ItemTypes::WallBlock, ItemTypes::WallBlock, ItemTypes::Chest].into_iter().enumerate() {
+            let trans = Transform::from_xyz(100.0 + i as f32 * 20.0, 60.0, 1.0);
+            construct::spawn_block(&mut commands, &game_assets, &registry, &balance, block_type, &trans);
+        }
+
+        // Far enough away to leave everything alone for a frame
+        for i in 0..3 {
+            let pos = Vec3::new(-600.0, -600.0 + i as f32 * 40.0, 2.0);
+            zombie::spawn_zombie_kind(&mut commands, &archetypes, "normal", pos, &game_assets, ZombieStats::default(), &mut game_rng.waves);
+        }
+
+        state.apply(world);
+
+        for (trans, mut items) in world.query::<(&Transform, &mut InventoryItems)>().iter_mut(world) {
+            if trans.translation == CHEST_POS {
+                items.slots[0] = Some(Item { item_type: ItemTypes::Coal, quantity: 7 });
+            }
+        }
+
+        for (i, mut health) in world.query_filtered::<&mut EntityHealth, With<Zombie>>().iter_mut(world).enumerate() {
+            health.val = 10.0 + i as f32;
+        }
+
+        for mut targeting in world.query::<&mut TurretTargeting>().iter_mut(world) {
+            *targeting = TurretTargeting { mode: TargetingMode::LowestHealth, range: 90.0, lead: false };
+        }
+
+        for (block, mut health) in world.query::<(&BlockType, &mut EntityHealth)>().iter_mut(world) {
+            if block.0 == ItemTypes::WallBlock {
+                health.val = 37.0;
+            }
+        }
+
+        let (mut trans, mut health, mut hunger, mut items) = world
+            .query_filtered::<(&mut Transform, &mut EntityHealth, &mut Hunger, &mut InventoryItems), With<LocalPlayer>>()
+            .single_mut(world);
+
+        trans.translation = Vec3::new(40.0, -20.0, trans.translation.z);
+        health.val = 55.0;
+        hunger.val = 42.0;
+        items.slots[3] = Some(Item { item_type: ItemTypes::Wood, quantity: 12 });
+    }
+
+    // The player of a connected client
+    fn spawn_remote_player(world: &mut World) -> Entity {
+        let mut state: SystemState<RestoreParams> = SystemState::new(world);
+        let (mut commands, game_assets, registry, _, _, balance) = state.get_mut(world);
+
+        let entity = player::spawn_player(&mut commands, &game_assets, &balance, player::SPAWN_POS, player::starting_items(&registry));
+        commands.entity(entity).insert(PlayerId(1));
+        state.apply(world);
+
+        entity
+    }
+
+    fn blocks(world: &mut World) -> Vec<String> {
+        let mut blocks: Vec<String> = world
+            .query::<(&Transform, &BlockType, Option<&EntityHealth>, Option<&InventoryItems>, Option<&TurretTargeting>)>()
+            .iter(world)
+            .map(|(trans, block, health, items, targeting)| {
+                format!("{:?} {} {:?} {:?} {:?}", block.0, trans.translation, health.map(|h| h.val), items.map(|i| &i.slots), targeting)
+            })
+            .collect();
+        blocks.sort();
+
+        blocks
+    }
+
+    fn zombie_healths(world: &mut World) -> Vec<f32> {
+        let mut healths: Vec<f32> = world
+            .query_filtered::<&EntityHealth, With<Zombie>>()
+            .iter(world)
+            .map(|health| health.val)
+            .collect();
+        healths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        healths
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let mut app = running_game(7);
+        populate(&mut app.world);
+
+        let save = snapshot_game(&mut app.world);
+        assert_eq!(save.version, SAVE_VERSION);
+        assert!(save.chunks.iter().any(|(chunk, _)| *chunk == environment::chunk_of(CHEST_POS)));
+
+        let path = temp_path("save_round_trip");
+        write_save(&path, &save).unwrap();
+        let loaded = read_save(&path);
+        let _ = fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+
+        let mut restored = running_game(8);
+        let remote = spawn_remote_player(&mut restored.world);
+        restore_game(&mut restored.world, &loaded);
+        assert_eq!(restored.world.resource::<WorldChunks>().seed, 7);
+
+        let (trans, health, hunger, items) = restored.world
+            .query_filtered::<(&Transform, &EntityHealth, &Hunger, &InventoryItems), With<LocalPlayer>>()
+            .single(&restored.world);
+
+        assert_eq!(trans.translation.truncate(), Vec2::new(40.0, -20.0));
+        assert_eq!(health.val, 55.0);
+        assert_eq!(hunger.val, 42.0);
+        assert_eq!(items.slots[3], Some(Item { item_type: ItemTypes::Wood, quantity: 12 }));
+        assert_eq!(restored.world.query::<&Player>().iter(&restored.world).count(), 2);
+        assert_eq!(restored.world.get::<PlayerId>(remote), Some(&PlayerId(1)));
+
+        assert_eq!(zombie_healths(&mut restored.world), vec![10.0, 11.0, 12.0]);
+
+        // Blocks come back when their chunks are streamed in again
+        restored.update();
+
+        let expected = blocks(&mut app.world);
+        assert!(expected.len() >= 3);
+        assert!(expected.iter().any(|block| block.contains("LowestHealth")));
+        assert_eq!(blocks(&mut restored.world), expected);
+
+        let clock = restored.world.resource::<Clock>();
+        assert_eq!(clock.day, save.clock.day);
+        assert_eq!(restored.world.resource::<CurrentWave>().number, save.wave.number);
+    }
+
+    #[test]
+    fn finished_timers_survive_the_round_trip() {
+        let mut timer = Timer::from_seconds(2.0, true);
+        timer.tick(Duration::from_secs(2));
+        let save = TimerSave::from_timer(&timer);
+
+        let mut restored = Timer::from_seconds(2.0, true);
+        save.apply(&mut restored);
+        assert!(restored.just_finished());
+
+        // Rigs and crops only get their duration from the balance on their first tick
+        let mut empty = Timer::new(Duration::ZERO, true);
+        save.apply(&mut empty);
+        assert_eq!(empty.elapsed(), timer.elapsed());
+    }
+
+    #[test]
+    fn older_save_is_rejected() {
+        let mut app = running_game(7);
+
+        let mut save = snapshot_game(&mut app.world);
+        save.version = SAVE_VERSION - 1;
+
+        let path = temp_path("older_save");
+        write_save(&path, &save).unwrap();
+        let loaded = read_save(&path);
+        let _ = fs::remove_file(&path);
+
+        match loaded {
+            Ok(_) => panic!("A version {} save was accepted", SAVE_VERSION - 1),
+            Err(e) => assert!(e.contains("not supported"), "{}", e)
+        }
+    }
+}
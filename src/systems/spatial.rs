@@ -0,0 +1,88 @@
+//! Uniform grid over the positions of the entities in the world, to find the entities
+//! near a point without looking at all of them.
+//!
+//! `SpatialHash` is rebuilt in `CoreStage::PreUpdate` every frame, so the positions are
+//! those at the start of the frame. Queries return candidates, and callers check the
+//! exact distance or overlap against the current `Transform`.
+
+use std::collections::HashMap;
+
+use crate::prelude::*;
+
+pub const SPATIAL_CELL_SIZE: f32 = 40.0;
+
+// How far an entity may move within a frame and still be found by a query
+const MOVE_MARGIN: f32 = 20.0;
+
+type SpatialCell = (i32, i32);
+
+// Entities that take part in collisions, attacks or pickups
+type SpatialFilter = Or<(With<BoxCollider>, With<EntityHealth>, With<Rigidbody>, With<CollectableItem>)>;
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin
+{
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpatialHash>()
+        .add_system_to_stage(CoreStage::PreUpdate, update_spatial_hash);
+    }
+}
+
+#[derive(Default)]
+pub struct SpatialHash
+{
+    cells: HashMap<SpatialCell, Vec<Entity>>,
+    // Half of the largest collider, as entities are only stored in the cell of their center
+    max_half_size: Vec2
+}
+
+impl SpatialHash
+{
+    fn cell(pos: Vec2) -> SpatialCell {
+        ((pos.x / SPATIAL_CELL_SIZE).floor() as i32, (pos.y / SPATIAL_CELL_SIZE).floor() as i32)
+    }
+
+    pub fn clear(&mut self) {
+        // Keep the allocations of the cells that were used in the last frame
+        self.cells.retain(|_, entities| !entities.is_empty());
+        self.cells.values_mut().for_each(|entities| entities.clear());
+        self.max_half_size = Vec2::ZERO;
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3, size: Vec2) {
+        self.cells.entry(Self::cell(pos.truncate())).or_default().push(entity);
+        self.max_half_size = self.max_half_size.max(size / 2.0);
+    }
+
+    /// Entities whose center may be within `radius` of `pos`.
+    pub fn within_radius(&self, pos: Vec3, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+        self.query(pos.truncate(), Vec2::splat(radius + MOVE_MARGIN))
+    }
+
+    /// Entities whose collider may overlap a box of `size` at `pos`.
+    pub fn overlapping(&self, pos: Vec3, size: Vec2) -> impl Iterator<Item = Entity> + '_ {
+        self.query(pos.truncate(), size / 2.0 + self.max_half_size + Vec2::splat(MOVE_MARGIN))
+    }
+
+    fn query(&self, pos: Vec2, half_extent: Vec2) -> impl Iterator<Item = Entity> + '_ {
+        let min = Self::cell(pos - half_extent);
+        let max = Self::cell(pos + half_extent);
+
+        (min.0..=max.0)
+            .flat_map(move |x| (min.1..=max.1).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flat_map(|entities| entities.iter().copied())
+    }
+}
+
+fn update_spatial_hash(
+    mut hash: ResMut<SpatialHash>,
+    query: Query<(Entity, &Transform, Option<&BoxCollider>), SpatialFilter>
+) {
+    hash.clear();
+
+    for (entity, trans, collider) in query.iter() {
+        hash.insert(entity, trans.translation, collider.map_or(Vec2::ZERO, |c| c.size));
+    }
+}
@@ -1,6 +1,6 @@
 use bevy::render::camera::RenderTarget;
 
-use crate::prelude::{*, interaction::INTERACTION_DISTANCE};
+use crate::prelude::*;
 
 pub fn my_cursor_system(
     wnds: Res<Windows>,
@@ -17,7 +17,7 @@ pub fn my_cursor_system(
 
     if let Some(screen_pos) = wnd.cursor_position() {
         // get the size of the window
-        let window_size = Vec2::new(wnd.width() as f32, wnd.height() as f32);
+        let window_size = Vec2::new(wnd.width(), wnd.height());
 
         // convert screen position [0..resolution] to ndc [-1..1] (gpu coordinates)
         let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
@@ -64,102 +64,97 @@ pub fn dist_between(a: Vec3, b: Vec3) -> f32 {
     ((b.y - a.y).powf(2.0) + (b.x - a.x).powf(2.0)).sqrt()
 }
 
+/// Moves every player by its own input. Opening menus and interacting are left to the
+/// player of this machine, a client sends its interactions on to the host.
+#[allow(clippy::too_many_arguments)]
 pub fn keyboard_actions(
-    mut query_rb: Query<(&mut Rigidbody, &Transform), With<Player>>,
-    mut block: ResMut<BlockSelection>,
-    mut magazine: Query<&mut Magazine>,
+    mut query_rb: Query<(&mut Rigidbody, &PlayerInput, Option<&mut Weapon>), With<Player>>,
+    local_query: Query<(Entity, &Transform), With<LocalPlayer>>,
     mut state: ResMut<State<AppState>>,
-    mut input: ResMut<Input<KeyCode>>,
+    mut actions: ResMut<Input<Action>>,
     // mouse_loc: Res<MouseLoc>,
     interactables_query: Query<(Entity, &Transform, &InteractableEntity)>,
     time: Res<Time>,
+    balance: Res<Balance>,
 
     // Interactions
-    mut chest_writer: EventWriter<ChestInteractEvent>
+    mut chest_writer: EventWriter<ChestInteractEvent>,
+    mut crafting_writer: EventWriter<CraftingInteractEvent>,
+    mut harvest_writer: EventWriter<HarvestInteractEvent>,
+    mut turret_writer: EventWriter<TurretInteractEvent>
 ) {
-    let (mut rb, player_trans) = query_rb.single_mut();
+    for (mut rb, input, weapon) in query_rb.iter_mut() {
+        let actions = &input.actions;
 
-    if input.pressed(KeyCode::D) {
-        rb.vx += PLAYER_ACC*time.delta_seconds();
-    }
-
-    if input.pressed(KeyCode::A) {
-        rb.vx += -PLAYER_ACC*time.delta_seconds();
-    }
-
-    if input.pressed(KeyCode::W) {
-        rb.vy += PLAYER_ACC*time.delta_seconds();
-    }
-
-    if input.pressed(KeyCode::S) {
-        rb.vy += -PLAYER_ACC*time.delta_seconds();
-    }
-
-    if input.just_pressed(KeyCode::Key1)
-    {
-        block.block = ItemTypes::WallBlock;
-    }
-
-    if input.just_pressed(KeyCode::Key2)
-    {
-        block.block = ItemTypes::TurretBlock;
-    }
-
-    if input.just_pressed(KeyCode::Key3) {
-        block.block = ItemTypes::TripMine;
-    }
-
-    if input.just_pressed(KeyCode::Key4) {
-        block.block = ItemTypes::Fence;
-    }
+        if actions.pressed(Action::MoveRight) {
+            rb.vx += balance.player.acceleration*time.delta_seconds();
+        }
 
-    if input.just_pressed(KeyCode::Key5) {
-        block.block = ItemTypes::Wheat;
-    }
+        if actions.pressed(Action::MoveLeft) {
+            rb.vx += -balance.player.acceleration*time.delta_seconds();
+        }
 
-    if input.just_pressed(KeyCode::Key6)
-    {
-        block.block = ItemTypes::WindMill;
-    }
+        if actions.pressed(Action::MoveUp) {
+            rb.vy += balance.player.acceleration*time.delta_seconds();
+        }
 
-    if input.just_pressed(KeyCode::Key7) {
-        block.block = ItemTypes::WoodFence;
-    }
+        if actions.pressed(Action::MoveDown) {
+            rb.vy += -balance.player.acceleration*time.delta_seconds();
+        }
 
-    if input.just_pressed(KeyCode::Key8) {
-        block.block = ItemTypes::MiningRig;
-    }
+        if actions.just_pressed(Action::Reload)
+        {
+            if let Some(mut weapon) = weapon {
+                weapon.reloading = weapon.magazine < weapon.stats.magazine;
+            }
+        }
 
-    if input.just_pressed(KeyCode::Key9) {
-        block.block = ItemTypes::CraftingTable;
+        let max_speed = balance.player.move_speed;
+        rb.vx = rb.vx.clamp(-max_speed, max_speed);
+        rb.vy = rb.vy.clamp(-max_speed, max_speed);
     }
 
-    if input.just_pressed(KeyCode::Key0) {
-        block.block = ItemTypes::Chest;
+    // The other players keep moving while the own player is in a menu
+    if state.current() != &AppState::InGame {
+        return;
     }
 
-    if input.just_pressed(KeyCode::R)
-    {
-        let mut magazine = magazine.single_mut();
-        magazine.0 = 0;
-    }
+    let (player_entity, player_trans) = match local_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return
+    };
 
-    if input.clear_just_pressed(KeyCode::I)
+    if actions.clear_just_pressed(Action::OpenInventory)
     {
         state.set(AppState::Inventory).unwrap();
     }
 
-    if input.just_pressed(KeyCode::E) {
-        for (entity, trans, inter_ent) in interactables_query.iter() {
-            if (trans.translation - player_trans.translation).length() < INTERACTION_DISTANCE {
-                match inter_ent.interact_type {
-                    InteractionType::ChestOpen => chest_writer.send(ChestInteractEvent{chest_entity: entity})
-                }
+    if actions.clear_just_pressed(Action::Interact) {
+        // Only interact with the closest entity, as every interaction changes the state
+        let closest = interactables_query.iter()
+            .map(|(entity, trans, inter_ent)| (entity, (trans.translation - player_trans.translation).length(), inter_ent))
+            .filter(|(_, dist, _)| *dist < balance.player.interaction_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((entity, _, inter_ent)) = closest {
+            match inter_ent.interact_type {
+                InteractionType::ChestOpen => chest_writer.send(ChestInteractEvent{chest_entity: entity}),
+                InteractionType::CraftingOpen => crafting_writer.send(CraftingInteractEvent{table_entity: entity}),
+                InteractionType::Harvest => harvest_writer.send(HarvestInteractEvent{crop_entity: entity}),
+                InteractionType::Turret => turret_writer.send(TurretInteractEvent{turret_entity: entity, player_entity, cycle_mode: false})
             }
         }
     }
 
-    rb.vx = rb.vx.clamp(-MOVESPEED, MOVESPEED);
-    rb.vy = rb.vy.clamp(-MOVESPEED, MOVESPEED);
+    if actions.clear_just_pressed(Action::TurretMode) {
+        let closest = interactables_query.iter()
+            .filter(|(_, _, inter_ent)| matches!(inter_ent.interact_type, InteractionType::Turret))
+            .map(|(entity, trans, _)| (entity, (trans.translation - player_trans.translation).length()))
+            .filter(|(_, dist)| *dist < balance.player.interaction_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
 
-}
\ No newline at end of file
+        if let Some((entity, _)) = closest {
+            turret_writer.send(TurretInteractEvent{turret_entity: entity, player_entity, cycle_mode: true});
+        }
+    }
+}